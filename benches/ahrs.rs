@@ -1,6 +1,8 @@
-use ahrs::{Ahrs, Madgwick, Mahony};
+use ahrs::{Ahrs, Aqua, Complementary, Fourati, Madgwick, Mahony, SimdAhrs, Ukf, Vqf};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nalgebra::Vector3;
 use rand::{self, thread_rng, Rng};
+use simba::simd::{SimdValue, WideF32x8};
 use std::stringify;
 
 macro_rules! get_rand_n(
@@ -29,6 +31,11 @@ macro_rules! _bench_function(
         let (a, g) = ( get_rand_n!($rng, $n), get_rand_n!($rng, $n) );
         _bench_iterations!($b, $t, update_imu, $n, a, g);
     };
+    // operation is `update_mag`
+    ($b: ident, $rng: ident, $t: ident, update_mag, $n: expr) => {
+        let m = get_rand_n!($rng, $n);
+        _bench_iterations!($b, $t, update_mag, $n, m);
+    };
 );
 
 macro_rules! _bench_iterations(
@@ -56,10 +63,82 @@ bench_ahrs!(_bench_madgwick_update,           Madgwick, update,     1);
 bench_ahrs!(_bench_madgwick_update_x1000,     Madgwick, update,     1000);
 bench_ahrs!(_bench_madgwick_update_imu,       Madgwick, update_imu, 1);
 bench_ahrs!(_bench_madgwick_update_imu_x1000, Madgwick, update_imu, 1000);
+bench_ahrs!(_bench_madgwick_update_mag,       Madgwick, update_mag, 1);
+bench_ahrs!(_bench_madgwick_update_mag_x1000, Madgwick, update_mag, 1000);
 bench_ahrs!(_bench_mahony_update,             Mahony,   update,     1);
 bench_ahrs!(_bench_mahony_update_x1000,       Mahony,   update,     1000);
 bench_ahrs!(_bench_mahony_update_imu,         Mahony,   update_imu, 1);
 bench_ahrs!(_bench_mahony_update_imu_x1000,   Mahony,   update_imu, 1000);
+bench_ahrs!(_bench_mahony_update_mag,         Mahony,   update_mag, 1);
+bench_ahrs!(_bench_mahony_update_mag_x1000,   Mahony,   update_mag, 1000);
+bench_ahrs!(_bench_complementary_update,           Complementary, update,     1);
+bench_ahrs!(_bench_complementary_update_x1000,     Complementary, update,     1000);
+bench_ahrs!(_bench_complementary_update_imu,       Complementary, update_imu, 1);
+bench_ahrs!(_bench_complementary_update_imu_x1000, Complementary, update_imu, 1000);
+bench_ahrs!(_bench_vqf_update,           Vqf, update,     1);
+bench_ahrs!(_bench_vqf_update_x1000,     Vqf, update,     1000);
+bench_ahrs!(_bench_vqf_update_imu,       Vqf, update_imu, 1);
+bench_ahrs!(_bench_vqf_update_imu_x1000, Vqf, update_imu, 1000);
+bench_ahrs!(_bench_fourati_update,           Fourati, update,     1);
+bench_ahrs!(_bench_fourati_update_x1000,     Fourati, update,     1000);
+bench_ahrs!(_bench_fourati_update_imu,       Fourati, update_imu, 1);
+bench_ahrs!(_bench_fourati_update_imu_x1000, Fourati, update_imu, 1000);
+bench_ahrs!(_bench_aqua_update,           Aqua, update,     1);
+bench_ahrs!(_bench_aqua_update_x1000,     Aqua, update,     1000);
+bench_ahrs!(_bench_aqua_update_imu,       Aqua, update_imu, 1);
+bench_ahrs!(_bench_aqua_update_imu_x1000, Aqua, update_imu, 1000);
+bench_ahrs!(_bench_ukf_update,           Ukf, update,     1);
+bench_ahrs!(_bench_ukf_update_x1000,     Ukf, update,     1000);
+bench_ahrs!(_bench_ukf_update_imu,       Ukf, update_imu, 1);
+bench_ahrs!(_bench_ukf_update_imu_x1000, Ukf, update_imu, 1000);
+
+macro_rules! bench_simd(
+    // compares `$lanes` scalar filters against a single SIMD filter running one filter per lane
+    ($name: ident, $scalar: expr, $simd: expr, $op: ident, $simd_op: ident, $lanes: expr, $( $x: ident ),* ) => {
+        fn $name(b: &mut Criterion) {
+            let mut rng = thread_rng();
+            $( let $x: Vec<Vector3<f32>> = get_rand_n!(rng, $lanes); )*
+
+            let mut group = b.benchmark_group(stringify!($name));
+
+            let mut scalars = vec![$scalar; $lanes];
+            group.bench_function("scalar", |b| {
+                b.iter(|| {
+                    for (n, ahrs) in scalars.iter_mut().enumerate() {
+                        let _ = black_box(ahrs.$op( $( &$x[n] ),* ));
+                    }
+                })
+            });
+
+            let mut simd = $simd;
+            $( let $x: Vector3<WideF32x8> = Vector3::from_fn(|i, _| {
+                WideF32x8::from(std::array::from_fn(|n| $x[n][i]))
+            }); )*
+            group.bench_function("simd", |b| {
+                b.iter(|| black_box(simd.$simd_op( $( &$x ),* ).1))
+            });
+
+            group.finish();
+        }
+    };
+);
+
+bench_simd!(_bench_madgwick_update_f32x8,
+    Madgwick::new(1.0f32 / 256.0, 0.1),
+    Madgwick::new(WideF32x8::splat(1.0 / 256.0), WideF32x8::splat(0.1)),
+    update, simd_update, 8, g, a, m);
+bench_simd!(_bench_madgwick_update_imu_f32x8,
+    Madgwick::new(1.0f32 / 256.0, 0.1),
+    Madgwick::new(WideF32x8::splat(1.0 / 256.0), WideF32x8::splat(0.1)),
+    update_imu, simd_update_imu, 8, g, a);
+bench_simd!(_bench_mahony_update_f32x8,
+    Mahony::new(1.0f32 / 256.0, 0.5, 0.0),
+    Mahony::new(WideF32x8::splat(1.0 / 256.0), WideF32x8::splat(0.5), WideF32x8::splat(0.0)),
+    update, simd_update, 8, g, a, m);
+bench_simd!(_bench_mahony_update_imu_f32x8,
+    Mahony::new(1.0f32 / 256.0, 0.5, 0.0),
+    Mahony::new(WideF32x8::splat(1.0 / 256.0), WideF32x8::splat(0.5), WideF32x8::splat(0.0)),
+    update_imu, simd_update_imu, 8, g, a);
 
 criterion_group!(
     benches,
@@ -67,9 +146,37 @@ criterion_group!(
     _bench_madgwick_update_x1000,
     _bench_madgwick_update_imu,
     _bench_madgwick_update_imu_x1000,
+    _bench_madgwick_update_mag,
+    _bench_madgwick_update_mag_x1000,
     _bench_mahony_update,
     _bench_mahony_update_x1000,
     _bench_mahony_update_imu,
     _bench_mahony_update_imu_x1000,
+    _bench_mahony_update_mag,
+    _bench_mahony_update_mag_x1000,
+    _bench_complementary_update,
+    _bench_complementary_update_x1000,
+    _bench_complementary_update_imu,
+    _bench_complementary_update_imu_x1000,
+    _bench_vqf_update,
+    _bench_vqf_update_x1000,
+    _bench_vqf_update_imu,
+    _bench_vqf_update_imu_x1000,
+    _bench_fourati_update,
+    _bench_fourati_update_x1000,
+    _bench_fourati_update_imu,
+    _bench_fourati_update_imu_x1000,
+    _bench_aqua_update,
+    _bench_aqua_update_x1000,
+    _bench_aqua_update_imu,
+    _bench_aqua_update_imu_x1000,
+    _bench_ukf_update,
+    _bench_ukf_update_x1000,
+    _bench_ukf_update_imu,
+    _bench_ukf_update_imu_x1000,
+    _bench_madgwick_update_f32x8,
+    _bench_madgwick_update_imu_f32x8,
+    _bench_mahony_update_f32x8,
+    _bench_mahony_update_imu_f32x8,
 );
 criterion_main!(benches);
@@ -1,23 +1,18 @@
-use ahrs::{Ahrs, Madgwick};
+use ahrs::{Acceleration, Ahrs, AngularRate, Madgwick, MagneticField};
 use nalgebra::Vector3;
-use std::f64;
 
 fn main() {
     // Initialize filter with default values
     let mut ahrs = Madgwick::default();
 
-    // Obtain sensor values from a source
-    let gyroscope = Vector3::new(60.1, 30.2, 20.3);
-    let accelerometer = Vector3::new(0.1, 0.2, 0.3);
-    let magnetometer = Vector3::new(0.5, 0.6, 0.7);
+    // Obtain sensor values from a source, tagged with their units
+    let gyroscope = AngularRate::from_degrees_per_second(Vector3::new(60.1, 30.2, 20.3));
+    let accelerometer = Acceleration::from_g(Vector3::new(0.1, 0.2, 0.3));
+    let magnetometer = MagneticField::from_gauss(Vector3::new(0.5, 0.6, 0.7));
 
-    // Run inputs through AHRS filter (gyroscope must be radians/s)
+    // Run inputs through AHRS filter
     let quat = ahrs
-        .update(
-            &(gyroscope * (f64::consts::PI / 180.0)),
-            &accelerometer,
-            &magnetometer,
-        )
+        .update_with_units(&gyroscope, &accelerometer, &magnetometer)
         .unwrap();
     let (roll, pitch, yaw) = quat.euler_angles();
 
@@ -1,10 +1,18 @@
-use nalgebra::{Scalar, UnitQuaternion, Vector3};
-use simba::simd::SimdValue;
+use crate::{
+    units::{Acceleration, AngularRate, MagneticField},
+    util::wrap_to_pi,
+};
+use nalgebra::{SVector, Scalar, UnitQuaternion, Vector3};
+use simba::{
+    scalar::RealField,
+    simd::{SimdBool, SimdRealField, SimdValue},
+};
 
 #[derive(Debug)]
 pub enum AhrsError {
     AccelerometerNormZero,
     MagnetometerNormZero,
+    TimestampNotIncreasing,
 }
 
 /// Trait for implementing an AHRS filter.
@@ -39,4 +47,420 @@ pub trait Ahrs<N: Scalar + SimdValue> {
         &mut self,
         gyroscope: &Vector3<N>,
     ) -> &UnitQuaternion<N>;
+
+    /// Attempts to correct the heading of the current state quaternion towards `magnetometer`,
+    /// without integrating the gyroscope or affecting roll and pitch. This allows fusing a
+    /// magnetometer sampled at a different rate than the gyroscope and accelerometer, between
+    /// calls to `update_imu`.
+    ///
+    /// The correction is the filter's heading correction for a single sample, so its rate scales
+    /// with how often the magnetometer is sampled.
+    ///
+    /// Returns a reference to the updated quaternion on success, or in the case of failure, an
+    /// `AhrsError` enum, which describes the reason.
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError>;
+
+    /// Unit-safe equivalent of `update`, taking measurements which carry their units.
+    ///
+    /// # Example
+    /// ```
+    /// # use ahrs::{Acceleration, Ahrs, AngularRate, Madgwick, MagneticField};
+    /// # use nalgebra::Vector3;
+    /// let mut ahrs = Madgwick::default();
+    ///
+    /// let gyroscope = AngularRate::from_degrees_per_second(Vector3::new(60.1, 30.2, 20.3));
+    /// let accelerometer = Acceleration::from_g(Vector3::new(0.1, 0.2, 0.3));
+    /// let magnetometer = MagneticField::from_gauss(Vector3::new(0.5, 0.6, 0.7));
+    ///
+    /// let quat = ahrs
+    ///     .update_with_units(&gyroscope, &accelerometer, &magnetometer)
+    ///     .unwrap();
+    /// ```
+    fn update_with_units(
+        &mut self,
+        gyroscope: &AngularRate<N>,
+        accelerometer: &Acceleration<N>,
+        magnetometer: &MagneticField<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        self.update(
+            gyroscope.radians_per_second(),
+            accelerometer.meters_per_second_squared(),
+            magnetometer.microtesla(),
+        )
+    }
+
+    /// Unit-safe equivalent of `update_imu`, taking measurements which carry their units.
+    fn update_imu_with_units(
+        &mut self,
+        gyroscope: &AngularRate<N>,
+        accelerometer: &Acceleration<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        self.update_imu(
+            gyroscope.radians_per_second(),
+            accelerometer.meters_per_second_squared(),
+        )
+    }
+
+    /// Unit-safe equivalent of `update_gyro`, taking a measurement which carries its units.
+    fn update_gyro_with_units(&mut self, gyroscope: &AngularRate<N>) -> &UnitQuaternion<N> {
+        self.update_gyro(gyroscope.radians_per_second())
+    }
+
+    /// Unit-safe equivalent of `update_mag`, taking a measurement which carries its units.
+    fn update_mag_with_units(
+        &mut self,
+        magnetometer: &MagneticField<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        self.update_mag(magnetometer.microtesla())
+    }
+
+    /// Equivalent of `update` which first removes the centripetal acceleration `gyroscope x
+    /// velocity` from `accelerometer`, so that coordinated turns do not tilt the estimate.
+    ///
+    /// `velocity` is the vehicle's velocity in the body frame, in meters per second, such as
+    /// `(airspeed, 0, 0)` or a GNSS velocity rotated into the body frame. The gyroscope must be
+    /// in radians per second and the accelerometer in meters per second squared.
+    ///
+    /// # Example
+    /// ```
+    /// # use ahrs::{Ahrs, Madgwick};
+    /// # use nalgebra::Vector3;
+    /// let mut ahrs = Madgwick::default();
+    ///
+    /// // Level left turn at 20 m/s
+    /// let gyroscope = Vector3::new(0.0, 0.0, 0.5);
+    /// let accelerometer = Vector3::new(0.0, 10.0, 9.81);
+    /// let magnetometer = Vector3::new(20.0, 0.0, -40.0);
+    /// let velocity = Vector3::new(20.0, 0.0, 0.0);
+    ///
+    /// let quat = ahrs
+    ///     .update_with_velocity(&gyroscope, &accelerometer, &magnetometer, &velocity)
+    ///     .unwrap();
+    /// ```
+    fn update_with_velocity(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+        velocity: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError>
+    where
+        N: RealField + Copy,
+    {
+        let accelerometer = accelerometer - gyroscope.cross(velocity);
+        self.update(gyroscope, &accelerometer, magnetometer)
+    }
+
+    /// Equivalent of `update_imu` which first removes the centripetal acceleration `gyroscope x
+    /// velocity` from `accelerometer`, as in `update_with_velocity`.
+    fn update_imu_with_velocity(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        velocity: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError>
+    where
+        N: RealField + Copy,
+    {
+        let accelerometer = accelerometer - gyroscope.cross(velocity);
+        self.update_imu(gyroscope, &accelerometer)
+    }
+}
+
+/// Trait for accessing the sampling period and state quaternion of an AHRS filter, allowing
+/// generic processing of recorded or irregularly-timed sensor data.
+pub trait AhrsState<N: Scalar + SimdValue + Copy> {
+    /// Expected sampling period, in seconds.
+    fn sample_period(&self) -> N;
+
+    /// Sets the expected sampling period, in seconds, used by subsequent updates.
+    fn set_sample_period(&mut self, sample_period: N);
+
+    /// Filter state quaternion.
+    fn quat(&self) -> UnitQuaternion<N>;
+
+    /// Replaces the filter state quaternion.
+    fn set_quat(&mut self, quat: UnitQuaternion<N>);
+}
+
+/// Trait for estimating the uncertainty of an AHRS filter's attitude, allowing downstream fusion to
+/// weight it.
+///
+/// Kalman filters report their covariance exactly. Other filters estimate it heuristically from the
+/// running magnitude of their measurement residuals, which reflects noise and disturbances but not
+/// a consistent bias of the estimate. As tracking the residuals costs several `atan2`s per update,
+/// these filters only do so once enabled with their `with_uncertainty` builder, and report every
+/// angle as unknown until then.
+pub trait AhrsUncertainty<N: RealField + Copy> {
+    /// Estimated standard deviations of the roll, pitch and yaw angles returned by
+    /// `UnitQuaternion::euler_angles`, in radians.
+    fn attitude_std_dev(&self) -> Vector3<N>;
+
+    /// Confidence in the attitude, from 1 when exact towards 0 as it becomes unknown, computed as
+    /// `1 / (1 + σ)` with `σ` the norm of `attitude_std_dev`.
+    fn confidence(&self) -> N {
+        N::one() / (N::one() + self.attitude_std_dev().norm())
+    }
+}
+
+/// Mode of an AHRS update, given by the measurements which corrected the attitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdateMode {
+    /// Gyroscope, accelerometer and magnetometer.
+    Marg,
+    /// Gyroscope and accelerometer only.
+    Imu,
+    /// Gyroscope only.
+    Gyro,
+    /// Magnetometer heading correction only, from `Ahrs::update_mag`.
+    Mag,
+    /// External heading correction only, such as from `Madgwick::update_heading`.
+    Heading,
+}
+
+/// Trait for running an AHRS filter backward in time, as by `smooth`.
+///
+/// Fed the time-reversed samples with the gyroscope negated, a filter sees the body rotating the
+/// opposite way, so any state which is itself a rotation rate, such as an estimated gyroscope
+/// bias, an integral feedback term or a compensated rotation rate of the Earth, must be negated
+/// with it.
+pub trait AhrsReverse<N: RealField + Copy>: Sized {
+    /// Returns the filter with its rotation rate states negated, to be updated backward in time
+    /// with the negated gyroscope.
+    fn reversed(&self) -> Self;
+}
+
+/// Diagnostics of the most recent update of an AHRS filter, for monitoring filter health.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Diagnostics<N: Scalar> {
+    /// Angle between the measured and estimated directions of gravity before the update, in
+    /// radians, or `None` if no accelerometer measurement was used or diagnostics are disabled.
+    pub accel_error: Option<N>,
+    /// Angle between the measured and estimated directions of Earth's magnetic field before the
+    /// update, in radians, or `None` if no magnetometer measurement was used or diagnostics are
+    /// disabled.
+    pub mag_error: Option<N>,
+    /// Norm of the filter's correction before it is scaled by the gains, such as the gradient of
+    /// the objective function for `Madgwick` or the error vector for `Mahony`.
+    pub step_norm: N,
+    /// Mode which the update actually ran in. This is less than requested if a measurement was
+    /// rejected, such as a disturbed magnetometer in `Vqf`.
+    pub mode: UpdateMode,
+    /// Whether the correction could not be computed, so only the gyroscope was integrated.
+    pub gyro_fallback: bool,
+}
+
+impl<N: Scalar + num_traits::Zero> Default for Diagnostics<N> {
+    fn default() -> Self {
+        Diagnostics {
+            accel_error: None,
+            mag_error: None,
+            step_norm: N::zero(),
+            mode: UpdateMode::Gyro,
+            gyro_fallback: false,
+        }
+    }
+}
+
+impl<N: RealField + Copy> Diagnostics<N> {
+    /// Diagnostics of an update which ran in `mode`, without the measurement errors.
+    pub(crate) fn from_mode(step_norm: N, mode: UpdateMode) -> Self {
+        Diagnostics {
+            accel_error: None,
+            mag_error: None,
+            step_norm,
+            mode,
+            gyro_fallback: mode == UpdateMode::Gyro,
+        }
+    }
+
+    /// Diagnostics of an update which ran in `mode` from the estimate `quat`, measuring the errors
+    /// of `accel` and `mag` against it if `enabled`.
+    pub(crate) fn from_measurements(
+        enabled: bool,
+        quat: &UnitQuaternion<N>,
+        accel: &Vector3<N>,
+        mag: Option<&Vector3<N>>,
+        step_norm: N,
+        mode: UpdateMode,
+    ) -> Self {
+        if !enabled {
+            return Diagnostics::from_mode(step_norm, mode);
+        }
+        Diagnostics {
+            accel_error: Some(angle_between(
+                &quat.inverse_transform_vector(&Vector3::z()),
+                accel,
+            )),
+            mag_error: mag.map(|mag| mag_error(quat, mag)),
+            ..Diagnostics::from_mode(step_norm, mode)
+        }
+    }
+
+    /// Diagnostics of a heading correction by `mag` from the estimate `quat`, measuring the error
+    /// of `mag` against it if `enabled`.
+    pub(crate) fn from_heading(
+        enabled: bool,
+        quat: &UnitQuaternion<N>,
+        mag: &Vector3<N>,
+        step_norm: N,
+    ) -> Self {
+        Diagnostics {
+            mag_error: enabled.then(|| mag_error(quat, mag)),
+            ..Diagnostics::from_mode(step_norm, UpdateMode::Mag)
+        }
+    }
+
+    /// Diagnostics of a heading correction by an external yaw measurement.
+    pub(crate) fn from_yaw(step_norm: N) -> Self {
+        Diagnostics {
+            accel_error: None,
+            mag_error: None,
+            step_norm,
+            mode: UpdateMode::Heading,
+            gyro_fallback: false,
+        }
+    }
+
+    /// Diagnostics of an update which fell back to integrating the gyroscope, after computing
+    /// these diagnostics.
+    pub(crate) fn into_fallback(self) -> Self {
+        Diagnostics {
+            mode: UpdateMode::Gyro,
+            gyro_fallback: true,
+            ..self
+        }
+    }
+}
+
+/// Trait for querying the diagnostics of the most recent update of an AHRS filter.
+///
+/// The mode, step norm and fallback of every update are always reported. As measuring the
+/// accelerometer and magnetometer errors costs an `atan2` each, filters only do so once enabled
+/// with their `with_diagnostics` builder.
+pub trait AhrsDiagnostics<N: Scalar> {
+    /// Diagnostics of the most recent update.
+    fn diagnostics(&self) -> Diagnostics<N>;
+}
+
+/// Lane-wise outcome of a `SimdAhrs` update.
+///
+/// Each field is a SIMD mask which is set for every lane that failed to update, mirroring the
+/// variants of `AhrsError`. Failed lanes are left untouched, while all other lanes are updated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimdAhrsStatus<B: SimdBool> {
+    /// Lanes whose accelerometer measurement has zero norm.
+    pub accelerometer_norm_zero: B,
+    /// Lanes whose magnetometer measurement has zero norm.
+    pub magnetometer_norm_zero: B,
+}
+
+impl<B: SimdBool> SimdAhrsStatus<B> {
+    /// Mask of lanes which failed to update for any reason.
+    pub fn failed(&self) -> B {
+        self.accelerometer_norm_zero | self.magnetometer_norm_zero
+    }
+
+    /// Whether every lane was updated.
+    pub fn is_ok(&self) -> bool {
+        self.failed().none()
+    }
+}
+
+/// Trait for implementing an AHRS filter over SIMD types, running one independent filter per lane.
+///
+/// This is the lane-wise counterpart of `Ahrs`: rather than returning an `AhrsError`, lanes which
+/// cannot be updated are masked out and reported through `SimdAhrsStatus`.
+pub trait SimdAhrs<N: SimdRealField> {
+    /// Updates each lane of the current state quaternion using 9dof IMU values, made up by
+    /// `gyroscope`, `accelerometer`, and `magnetometer`.
+    ///
+    /// Returns a reference to the updated quaternion along with the lanes which failed to update.
+    fn simd_update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> (&UnitQuaternion<N>, SimdAhrsStatus<N::SimdBool>);
+
+    /// Updates each lane of the current state quaternion using 6dof IMU values, made up by
+    /// `gyroscope` & `accelerometer`.
+    ///
+    /// Returns a reference to the updated quaternion along with the lanes which failed to update.
+    fn simd_update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> (&UnitQuaternion<N>, SimdAhrsStatus<N::SimdBool>);
+
+    /// Updates each lane of the current state quaternion using only 3dof IMU values, made up by
+    /// `gyroscope`.
+    ///
+    /// Returns a reference to the updated quaternion.
+    fn simd_update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N>;
+}
+
+/// Mask with every lane unset.
+pub(crate) fn simd_false<N: SimdRealField>() -> N::SimdBool {
+    N::zero().simd_ne(N::zero())
+}
+
+/// Lane-wise equivalent of `Matrix::try_normalize`, returning the normalized vector along with a
+/// mask of lanes whose norm is zero. Those lanes are left as zero rather than becoming NaN.
+pub(crate) fn simd_try_normalize<N: SimdRealField, const D: usize>(
+    v: &SVector<N, D>,
+) -> (SVector<N, D>, N::SimdBool)
+where
+    N::Element: SimdRealField,
+{
+    let norm = v.norm();
+    let norm_zero = norm.clone().simd_le(N::zero());
+
+    (v / N::one().select(norm_zero, norm), norm_zero)
+}
+
+/// Angle between the vectors `a` and `b`, in radians.
+pub(crate) fn angle_between<N: RealField + Copy>(a: &Vector3<N>, b: &Vector3<N>) -> N {
+    a.cross(b).norm().atan2(a.dot(b))
+}
+
+/// Angle between `mag` and the direction of Earth's magnetic field estimated by `quat`, with the
+/// dip angle taken from `mag`.
+fn mag_error<N: RealField + Copy>(quat: &UnitQuaternion<N>, mag: &Vector3<N>) -> N {
+    // Reference direction of Earth's magnetic field
+    let h = quat * mag;
+    let b = Vector3::new(h.xy().norm(), nalgebra::zero(), h[2]);
+
+    angle_between(&quat.inverse_transform_vector(&b), mag)
+}
+
+/// Heading error of `quat` measured by `magnetometer`, as the angle about the Earth frame's
+/// z-axis from north to the horizontal component of the field in the Earth frame.
+///
+/// Returns an error if the field has no horizontal component from which to derive the heading.
+pub(crate) fn heading_error<N: RealField + Copy>(
+    quat: &UnitQuaternion<N>,
+    magnetometer: &Vector3<N>,
+) -> Result<N, AhrsError> {
+    let h = quat * magnetometer;
+    if h.xy().norm_squared() == nalgebra::zero() {
+        return Err(AhrsError::MagnetometerNormZero);
+    }
+
+    Ok(h[1].atan2(h[0]))
+}
+
+/// Heading error of `quat` measured by an external `yaw`, as the angle about the Earth frame's
+/// z-axis from `yaw` to the body's x-axis, wrapped to `[-pi, pi]`.
+pub(crate) fn yaw_error<N: RealField + Copy>(quat: &UnitQuaternion<N>, yaw: N) -> N {
+    let x = quat * Vector3::x();
+    wrap_to_pi(x[1].atan2(x[0]) - yaw)
+}
+
+/// Rotates `quat` about the Earth frame's z-axis by `angle`, which leaves roll and pitch unchanged.
+pub(crate) fn rotate_heading<N: RealField + Copy>(
+    quat: &UnitQuaternion<N>,
+    angle: N,
+) -> UnitQuaternion<N> {
+    UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle) * quat
 }
@@ -0,0 +1,313 @@
+#![allow(non_snake_case)]
+
+//! Closed-form attitude estimators from a single accelerometer and magnetometer measurement.
+//!
+//! All estimators return the quaternion rotating the body frame into the Earth frame, whose x-axis
+//! points towards magnetic north and z-axis points up, like the state quaternion of the filters.
+//! With noise-free measurements they agree exactly; they differ in cost, singularities and how
+//! they distribute errors between the two measurements.
+
+use crate::ahrs::AhrsError;
+use nalgebra::{Matrix4, Quaternion, UnitQuaternion, Vector3, Vector4};
+use simba::scalar::RealField;
+
+/// Normalizes the accelerometer and magnetometer measurements, ensuring that the magnetometer has
+/// a component perpendicular to the accelerometer from which to derive the heading.
+pub(crate) fn normalize_measurements<N: RealField + Copy>(
+    accelerometer: &Vector3<N>,
+    magnetometer: &Vector3<N>,
+) -> Result<(Vector3<N>, Vector3<N>), AhrsError> {
+    let zero: N = nalgebra::zero();
+
+    let Some(accel) = accelerometer.try_normalize(zero) else {
+        return Err(AhrsError::AccelerometerNormZero);
+    };
+    let Some(mag) = magnetometer.try_normalize(zero) else {
+        return Err(AhrsError::MagnetometerNormZero);
+    };
+    if accel.cross(&mag).norm_squared() == zero {
+        return Err(AhrsError::MagnetometerNormZero);
+    }
+
+    Ok((accel, mag))
+}
+
+/// Rotation about a horizontal axis which aligns the normalized `accel` with the z-axis.
+///
+/// Only for an upside-down `accel` is a different axis used, to avoid the singularity at
+/// `accel = -z`.
+pub(crate) fn tilt_quaternion<N: RealField + Copy>(accel: &Vector3<N>) -> UnitQuaternion<N> {
+    let zero: N = nalgebra::zero();
+    let one: N = N::one();
+    let two: N = one + one;
+
+    let quat = if accel[2] >= zero {
+        let w = ((accel[2] + one) / two).sqrt();
+        Quaternion::new(w, accel[1] / (two * w), -accel[0] / (two * w), zero)
+    } else {
+        // Rotate half a turn about the x-axis first
+        let x = ((one - accel[2]) / two).sqrt();
+        Quaternion::new(accel[1] / (two * x), x, zero, accel[0] / (two * x))
+    };
+
+    UnitQuaternion::new_unchecked(if quat.w < zero { -quat } else { quat })
+}
+
+/// Rotation about the z-axis which aligns the horizontal component of `level`, expressed in a
+/// levelled frame, with the x-axis. Returns `None` if `level` has no horizontal component.
+pub(crate) fn heading_quaternion<N: RealField + Copy>(
+    level: &Vector3<N>,
+) -> Option<UnitQuaternion<N>> {
+    let zero: N = nalgebra::zero();
+    let two: N = N::one() + N::one();
+
+    let gamma = level[0] * level[0] + level[1] * level[1];
+    if gamma <= zero {
+        return None;
+    }
+    let norm = gamma.sqrt();
+
+    let quat = if level[0] >= zero {
+        let t = (gamma + level[0] * norm).sqrt();
+        Quaternion::new(
+            t / (two * gamma).sqrt(),
+            zero,
+            zero,
+            -level[1] / (two.sqrt() * t),
+        )
+    } else {
+        // Avoid the singularity when pointing along the negative x-axis
+        let t = (gamma - level[0] * norm).sqrt();
+        Quaternion::new(
+            -level[1] / (two.sqrt() * t),
+            zero,
+            zero,
+            t / (two * gamma).sqrt(),
+        )
+    };
+
+    Some(UnitQuaternion::new_unchecked(if quat.w < zero {
+        -quat
+    } else {
+        quat
+    }))
+}
+
+/// Algebraic Quaternion Algorithm (AQUA).
+///
+/// Based on R. Valenti et al., "Keeping a Good Attitude: A Quaternion-Based Orientation Filter for
+/// IMUs and MARGs", Sensors, 2015. The attitude is factored into a tilt quaternion derived from
+/// the accelerometer, followed by a heading quaternion about the vertical derived from the
+/// levelled magnetometer, so magnetometer errors never affect roll and pitch.
+///
+/// # Example
+/// ```
+/// # use ahrs::aqua;
+/// # use nalgebra::Vector3;
+/// let accelerometer = Vector3::new(0.0f64, 0.0, 1.0);
+/// let magnetometer = Vector3::new(0.0, -0.5, -0.8);
+///
+/// let quat = aqua(&accelerometer, &magnetometer).unwrap();
+/// let (roll, pitch, yaw) = quat.euler_angles();
+/// assert!((yaw - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+/// ```
+pub fn aqua<N: RealField + Copy>(
+    accelerometer: &Vector3<N>,
+    magnetometer: &Vector3<N>,
+) -> Result<UnitQuaternion<N>, AhrsError> {
+    let (accel, mag) = normalize_measurements(accelerometer, magnetometer)?;
+
+    let tilt = tilt_quaternion(&accel);
+    let heading = heading_quaternion(&(tilt * mag)).ok_or(AhrsError::MagnetometerNormZero)?;
+
+    Ok(heading * tilt)
+}
+
+/// Factored Quaternion Algorithm (FQA).
+///
+/// Based on X. Yun et al., "A Simplified Quaternion-Based Algorithm for Orientation Estimation
+/// From Earth Gravity and Magnetic Field Measurements", IEEE Transactions on Instrumentation and
+/// Measurement, 2008. Elevation, roll and azimuth quaternions are computed separately from
+/// half-angle formulas, without evaluating any trigonometric functions.
+///
+/// At an elevation of exactly 90 degrees the roll is undefined, and taken to be zero.
+pub fn fqa<N: RealField + Copy>(
+    accelerometer: &Vector3<N>,
+    magnetometer: &Vector3<N>,
+) -> Result<UnitQuaternion<N>, AhrsError> {
+    let (accel, mag) = normalize_measurements(accelerometer, magnetometer)?;
+
+    let zero: N = nalgebra::zero();
+    let one: N = N::one();
+
+    // Half-angle cosine and sine of the angle with cosine `cos` and the sign of `sin`
+    let half_angle = |cos: N, sin: N| {
+        let two = one + one;
+        let half_cos = ((one + cos) / two).max(zero).sqrt();
+        let half_sin = ((one - cos) / two).max(zero).sqrt();
+        (half_cos, if sin < zero { -half_sin } else { half_sin })
+    };
+
+    // Elevation quaternion, about the y-axis
+    let sin_elevation = -accel[0];
+    let cos_elevation = (one - sin_elevation * sin_elevation).max(zero).sqrt();
+    let (c, s) = half_angle(cos_elevation, sin_elevation);
+    let elevation = UnitQuaternion::new_unchecked(Quaternion::new(c, zero, s, zero));
+
+    // Roll quaternion, about the x-axis
+    let (sin_roll, cos_roll) = if cos_elevation > zero {
+        (accel[1] / cos_elevation, accel[2] / cos_elevation)
+    } else {
+        (zero, one)
+    };
+    let (c, s) = half_angle(cos_roll, sin_roll);
+    let roll = UnitQuaternion::new_unchecked(Quaternion::new(c, s, zero, zero));
+
+    // Azimuth quaternion, about the z-axis, from the levelled magnetometer
+    let level = elevation * roll * mag;
+    let norm = (level[0] * level[0] + level[1] * level[1]).sqrt();
+    if norm <= zero {
+        return Err(AhrsError::MagnetometerNormZero);
+    }
+    let (c, s) = half_angle(level[0] / norm, -level[1] / norm);
+    let azimuth = UnitQuaternion::new_unchecked(Quaternion::new(c, zero, zero, s));
+
+    Ok(azimuth * elevation * roll)
+}
+
+/// Super-fast Attitude from Accelerometer and Magnetometer (SAAM).
+///
+/// Based on J. Wu et al., "Super-Fast Attitude Determination Using Accelerometer and Magnetometer
+/// with Analytical Solution", Journal of Sensors, 2018. The quaternion is given by a single
+/// closed-form expression, using the dip angle between the measurements to build the magnetic
+/// reference. At the attitude where this expression vanishes, the result of `aqua` is returned.
+pub fn saam<N: RealField + Copy>(
+    accelerometer: &Vector3<N>,
+    magnetometer: &Vector3<N>,
+) -> Result<UnitQuaternion<N>, AhrsError> {
+    let (a, m) = normalize_measurements(accelerometer, magnetometer)?;
+
+    let one: N = N::one();
+
+    // Vertical and horizontal components of the magnetic reference
+    let m_d = a.dot(&m);
+    let m_n = (one - m_d * m_d).sqrt();
+
+    let quat = Quaternion::new(
+        a[0] * m[1] - a[1] * (m_n + m[0]),
+        (a[2] - one) * (m_n + m[0]) + a[0] * (m_d - m[2]),
+        (a[2] - one) * m[1] + a[1] * (m_d - m[2]),
+        a[2] * m_d - a[0] * m_n - m[2],
+    );
+
+    match UnitQuaternion::try_new(quat, nalgebra::convert(1e-12)) {
+        Some(quat) => Ok(quat),
+        None => aqua(&a, &m),
+    }
+}
+
+/// Fast Linear Attitude Estimator (FLAE).
+///
+/// Based on J. Wu et al., "Fast Linear Quaternion Attitude Estimator Using Vector Observations",
+/// IEEE Transactions on Automation Science and Engineering, 2018. Solves Wahba's problem for the
+/// gravity and magnetic field directions with equal weights: the largest eigenvalue of the
+/// traceless 4x4 attitude matrix is found by Newton's method on its characteristic polynomial,
+/// and the quaternion is the corresponding null vector.
+pub fn flae<N: RealField + Copy>(
+    accelerometer: &Vector3<N>,
+    magnetometer: &Vector3<N>,
+) -> Result<UnitQuaternion<N>, AhrsError> {
+    let (accel, mag) = normalize_measurements(accelerometer, magnetometer)?;
+
+    let zero: N = nalgebra::zero();
+    let one: N = N::one();
+    let two: N = one + one;
+    let three: N = two + one;
+    let four: N = two + two;
+
+    // Reference directions of gravity and the magnetic field, using the measured dip angle
+    let m_d = accel.dot(&mag);
+    let m_n = (one - m_d * m_d).sqrt();
+    let observations = [(accel, Vector3::z()), (mag, Vector3::new(m_n, zero, m_d))];
+
+    // Attitude matrix W, such that qᵀ W q is the weighted sum of r · (q b q*)
+    let W = observations.iter().fold(Matrix4::zeros(), |W, (b, r)| {
+        W - left_matrix(r) * right_matrix(b)
+    }) / two;
+
+    // Characteristic polynomial λ⁴ + c2 λ² + c1 λ + c0, having no cubic term as W is traceless
+    let W2 = W * W;
+    let c2 = -W2.trace() / two;
+    let c1 = -(W2 * W).trace() / three;
+    let c0 = W.determinant();
+
+    // The largest eigenvalue is at most the sum of weights, which is the starting point
+    let mut lambda = one;
+    for _ in 0..10 {
+        let f = ((lambda * lambda + c2) * lambda + c1) * lambda + c0;
+        let df = (four * lambda * lambda + two * c2) * lambda + c1;
+        if df == zero {
+            break;
+        }
+        let step = f / df;
+        lambda -= step;
+        if step.abs() <= N::default_epsilon() {
+            break;
+        }
+    }
+
+    // Null vector of W - λI, taken from the adjugate column with the largest diagonal minor
+    let A = W - Matrix4::identity() * lambda;
+    let minor = |row: usize, column: usize| A.remove_row(row).remove_column(column).determinant();
+    let column = (1..4).fold(0, |best, j| {
+        if minor(j, j).abs() > minor(best, best).abs() {
+            j
+        } else {
+            best
+        }
+    });
+    let null = Vector4::from_fn(|i, _| {
+        let cofactor = minor(column, i);
+        if (i + column) % 2 == 0 {
+            cofactor
+        } else {
+            -cofactor
+        }
+    });
+
+    match UnitQuaternion::try_new(
+        Quaternion::new(null[0], null[1], null[2], null[3]),
+        nalgebra::convert(1e-12),
+    ) {
+        Some(quat) => Ok(quat),
+        None => aqua(&accel, &mag),
+    }
+}
+
+/// Matrix of left multiplication by the pure quaternion `v`, acting on `[w, x, y, z]`.
+fn left_matrix<N: RealField + Copy>(v: &Vector3<N>) -> Matrix4<N> {
+    let zero: N = nalgebra::zero();
+
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+        zero, -v[0], -v[1], -v[2],
+        v[0],  zero, -v[2],  v[1],
+        v[1],  v[2],  zero, -v[0],
+        v[2], -v[1],  v[0],  zero,
+    );
+    matrix
+}
+
+/// Matrix of right multiplication by the pure quaternion `v`, acting on `[w, x, y, z]`.
+fn right_matrix<N: RealField + Copy>(v: &Vector3<N>) -> Matrix4<N> {
+    let zero: N = nalgebra::zero();
+
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+        zero, -v[0], -v[1], -v[2],
+        v[0],  zero,  v[2], -v[1],
+        v[1], -v[2],  zero,  v[0],
+        v[2],  v[1], -v[0],  zero,
+    );
+    matrix
+}
@@ -0,0 +1,473 @@
+#![allow(non_snake_case)]
+
+use crate::{
+    ahrs::{
+        Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState, AhrsUncertainty, Diagnostics,
+        UpdateMode,
+    },
+    algebraic::{heading_quaternion, tilt_quaternion},
+    uncertainty::Residuals,
+};
+use core::hash;
+use nalgebra::{Quaternion, Scalar, UnitQuaternion, Vector3};
+use simba::{
+    scalar::RealField,
+    simd::{SimdRealField, SimdValue},
+};
+
+/// Corrections whose scalar part exceeds this are scaled by linear rather than spherical
+/// interpolation.
+const LERP_THRESHOLD: f64 = 0.9;
+
+/// Relative acceleration magnitude errors below which the full accelerometer gain is used.
+const ADAPTIVE_LOWER: f64 = 0.1;
+
+/// Relative acceleration magnitude errors above which the accelerometer is ignored.
+const ADAPTIVE_UPPER: f64 = 0.2;
+
+/// AQUA complementary filter AHRS implementation.
+///
+/// Based on R. Valenti et al., "Keeping a Good Attitude: A Quaternion-Based Orientation Filter for
+/// IMUs and MARGs", Sensors, 2015. The gyroscope prediction is corrected by a fraction `alpha` of
+/// the tilt measured by the accelerometer, followed by a fraction `beta` of the heading measured by
+/// the magnetometer. The magnetometer correction is about the vertical only, so magnetic
+/// disturbances never affect roll and pitch.
+///
+/// The state is not initialized from the first measurement; use [`aqua`](crate::aqua) with
+/// `AhrsState::set_quat` to start from the measured attitude.
+///
+/// # Example
+/// ```
+/// # use ahrs::Aqua;
+/// let mut ahrs = Aqua::new(0.002390625f64, 0.01, 0.01);
+/// println!("aqua filter: {:?}", ahrs);
+///
+/// // Can now process IMU data using `Ahrs::update_imu`, etc.
+/// ```
+#[derive(Debug)]
+pub struct Aqua<N: Scalar + SimdValue + Copy> {
+    /// Expected sampling period, in seconds.
+    sample_period: N,
+    /// Fraction of the accelerometer tilt correction applied on each update.
+    alpha: N,
+    /// Fraction of the magnetometer heading correction applied on each update.
+    beta: N,
+    /// Magnitude of gravity in accelerometer units, when the accelerometer gain is adaptive.
+    gravity: Option<N>,
+    /// Measurement residuals, from which the attitude uncertainty is estimated.
+    residuals: Residuals<N>,
+    /// Diagnostics of the most recent update.
+    diagnostics: Diagnostics<N>,
+    /// Whether the diagnostics measure the accelerometer and magnetometer errors.
+    diagnostics_enabled: bool,
+    /// Filter state quaternion.
+    pub quat: UnitQuaternion<N>,
+}
+
+impl<N: SimdRealField + Eq + Copy> Eq for Aqua<N> where N::Element: SimdRealField + Copy {}
+
+impl<N: SimdRealField + Copy> PartialEq for Aqua<N>
+where
+    N::Element: SimdRealField + Copy,
+{
+    fn eq(&self, rhs: &Self) -> bool {
+        self.sample_period == rhs.sample_period
+            && self.alpha == rhs.alpha
+            && self.beta == rhs.beta
+            && self.gravity == rhs.gravity
+            && self.residuals == rhs.residuals
+            && self.quat == rhs.quat
+    }
+}
+
+impl<N: SimdRealField + hash::Hash + Copy> hash::Hash for Aqua<N> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.sample_period.hash(state);
+        self.alpha.hash(state);
+        self.beta.hash(state);
+        self.gravity.hash(state);
+        self.residuals.hash(state);
+        self.quat.hash(state);
+    }
+}
+
+impl<N: Scalar + Copy + SimdValue> Copy for Aqua<N> {}
+
+impl<N: Scalar + SimdValue + Copy> Clone for Aqua<N> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Default for Aqua<f64> {
+    /// Creates a new `Aqua` instance with default filter parameters.
+    ///
+    /// ```
+    /// # use ahrs::Aqua;
+    /// dbg!(Aqua::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // Aqua {
+    /// //     sample_period: 1.0f64/256.0,
+    /// //     alpha: 0.01f64,
+    /// //     beta: 0.01f64,
+    /// //     gravity: None,
+    /// //     residuals: Residuals { enabled: false, .. },
+    /// //     diagnostics: Diagnostics { .. },
+    /// //     diagnostics_enabled: false,
+    /// //     quat: Quaternion { w: 1.0f64, i: 0.0, j: 0.0, k: 0.0 },
+    /// // };
+    /// ```
+    fn default() -> Aqua<f64> {
+        Aqua {
+            sample_period: (1.0f64) / (256.0),
+            alpha: 0.01f64,
+            beta: 0.01f64,
+            gravity: None,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat: UnitQuaternion::new_unchecked(Quaternion::new(1.0f64, 0.0, 0.0, 0.0)),
+        }
+    }
+}
+
+impl<N: Scalar + SimdValue + num_traits::One + num_traits::Zero + Copy> Aqua<N> {
+    /// Creates a new `Aqua` AHRS instance with identity quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `alpha` - Fraction of the accelerometer correction applied on each update, between 0 and 1.
+    /// * `beta` - Fraction of the magnetometer correction applied on each update, between 0 and 1.
+    pub fn new(sample_period: N, alpha: N, beta: N) -> Self {
+        Aqua::new_with_quat(
+            sample_period,
+            alpha,
+            beta,
+            UnitQuaternion::new_unchecked(Quaternion::new(
+                N::one(),
+                N::zero(),
+                N::zero(),
+                N::zero(),
+            )),
+        )
+    }
+
+    /// Creates a new `Aqua` AHRS instance with given quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `alpha` - Fraction of the accelerometer correction applied on each update, between 0 and 1.
+    /// * `beta` - Fraction of the magnetometer correction applied on each update, between 0 and 1.
+    /// * `quat` - Existing filter state quaternion.
+    pub fn new_with_quat(sample_period: N, alpha: N, beta: N, quat: UnitQuaternion<N>) -> Self {
+        Aqua {
+            sample_period,
+            alpha,
+            beta,
+            gravity: None,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat,
+        }
+    }
+
+    /// Makes the accelerometer gain adaptive, reducing it linearly from `alpha` to zero as the
+    /// magnitude of the acceleration deviates by 10% to 20% from `gravity`.
+    ///
+    /// # Arguments
+    ///
+    /// * `gravity` - Magnitude of gravity in accelerometer units.
+    pub fn with_adaptive_gain(mut self, gravity: N) -> Self {
+        self.gravity = Some(gravity);
+        self
+    }
+}
+
+impl<N: RealField + Copy> Aqua<N> {
+    /// Integrates `gyroscope` into the current state quaternion.
+    fn integrate(&self, gyroscope: &Vector3<N>) -> UnitQuaternion<N> {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Compute rate of change for quaternion
+        let qDot = q * Quaternion::from_parts(zero, *gyroscope) * half;
+
+        // Integrate to yield quaternion
+        UnitQuaternion::from_quaternion(q + qDot * self.sample_period)
+    }
+
+    /// Accelerometer gain for a measurement of magnitude `norm`.
+    fn accelerometer_gain(&self, norm: N) -> N {
+        let Some(gravity) = self.gravity else {
+            return self.alpha;
+        };
+        let lower: N = nalgebra::convert(ADAPTIVE_LOWER);
+        let upper: N = nalgebra::convert(ADAPTIVE_UPPER);
+
+        let error = (norm - gravity).abs() / gravity;
+        if error <= lower {
+            self.alpha
+        } else if error >= upper {
+            nalgebra::zero()
+        } else {
+            self.alpha * (upper - error) / (upper - lower)
+        }
+    }
+
+    /// Tilt correction rotating the up direction of the normalized `accel`, predicted in the
+    /// Earth frame by `predicted`, towards the Earth frame's z-axis, and the angle of the
+    /// correction before it is scaled by `gain`.
+    fn tilt_correction(
+        &self,
+        predicted: &UnitQuaternion<N>,
+        accel: &Vector3<N>,
+        gain: N,
+    ) -> (UnitQuaternion<N>, N) {
+        let correction = tilt_quaternion(&(predicted * accel));
+        let angle = correction.angle();
+
+        (scale_correction(correction, gain) * predicted, angle)
+    }
+
+    /// Returns the filter with tracking of the measurement residuals reported by
+    /// `AhrsUncertainty` enabled or disabled.
+    pub fn with_uncertainty(self, enabled: bool) -> Self {
+        Aqua {
+            residuals: Residuals::with_tracking(enabled, self.sample_period),
+            ..self
+        }
+    }
+
+    /// Returns the filter with measurement of the accelerometer and magnetometer errors reported
+    /// by `AhrsDiagnostics` enabled or disabled.
+    pub fn with_diagnostics(self, enabled: bool) -> Self {
+        Aqua {
+            diagnostics_enabled: enabled,
+            ..self
+        }
+    }
+}
+
+/// Scales the rotation angle of `correction` by `gain`, interpolating linearly from the identity
+/// for small angles.
+fn scale_correction<N: RealField + Copy>(
+    correction: UnitQuaternion<N>,
+    gain: N,
+) -> UnitQuaternion<N> {
+    let identity = UnitQuaternion::identity();
+    if correction.w > nalgebra::convert(LERP_THRESHOLD) {
+        UnitQuaternion::from_quaternion(identity.into_inner().lerp(&correction, gain))
+    } else {
+        // Scaling the rotation vector, unlike interpolation, remains well defined near 180 degrees
+        UnitQuaternion::from_scaled_axis(correction.scaled_axis() * gain)
+    }
+}
+
+#[cfg(feature = "field_access")]
+impl<N: Scalar + SimdValue + Copy> Aqua<N> {
+    /// Expected sampling period, in seconds.
+    pub fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    /// Mutable reference to expected sampling period, in seconds.
+    pub fn sample_period_mut(&mut self) -> &mut N {
+        &mut self.sample_period
+    }
+
+    /// Fraction of the accelerometer correction applied on each update.
+    pub fn alpha(&self) -> N {
+        self.alpha
+    }
+
+    /// Mutable reference to fraction of the accelerometer correction applied on each update.
+    pub fn alpha_mut(&mut self) -> &mut N {
+        &mut self.alpha
+    }
+
+    /// Fraction of the magnetometer correction applied on each update.
+    pub fn beta(&self) -> N {
+        self.beta
+    }
+
+    /// Mutable reference to fraction of the magnetometer correction applied on each update.
+    pub fn beta_mut(&mut self) -> &mut N {
+        &mut self.beta
+    }
+
+    /// Filter state quaternion.
+    pub fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    /// Mutable reference to filter state quaternion.
+    pub fn quat_mut(&mut self) -> &mut UnitQuaternion<N> {
+        &mut self.quat
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsState<N> for Aqua<N> {
+    fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    fn set_sample_period(&mut self, sample_period: N) {
+        self.sample_period = sample_period;
+    }
+
+    fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    fn set_quat(&mut self, quat: UnitQuaternion<N>) {
+        self.quat = quat;
+    }
+}
+
+impl<N: RealField + Copy> AhrsUncertainty<N> for Aqua<N> {
+    fn attitude_std_dev(&self) -> Vector3<N> {
+        self.residuals.std_dev()
+    }
+}
+
+impl<N: RealField + Copy> AhrsReverse<N> for Aqua<N> {
+    fn reversed(&self) -> Self {
+        *self
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsDiagnostics<N> for Aqua<N> {
+    fn diagnostics(&self) -> Diagnostics<N> {
+        self.diagnostics
+    }
+}
+
+impl<N: RealField + Copy> Ahrs<N> for Aqua<N> {
+    fn update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize accelerometer measurement
+        let norm = accelerometer.norm();
+        if norm == zero {
+            return Err(AhrsError::AccelerometerNormZero);
+        }
+        let accel = accelerometer / norm;
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+
+        let predicted = self.integrate(gyroscope);
+        let (tilted, tilt_angle) =
+            self.tilt_correction(&predicted, &accel, self.accelerometer_gain(norm));
+
+        // Heading correction about the Earth frame's z-axis, from the magnetometer in the Earth
+        // frame
+        let Some(heading) = heading_quaternion(&(tilted * mag)) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+
+        // Residuals of the measurements against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, &accel, self.sample_period);
+        self.residuals
+            .update_heading(&self.quat, &mag, self.sample_period);
+
+        // Tilt and heading corrections are about orthogonal axes
+        let heading_angle = heading.angle();
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &self.quat,
+            &accel,
+            Some(&mag),
+            (tilt_angle * tilt_angle + heading_angle * heading_angle).sqrt(),
+            UpdateMode::Marg,
+        );
+
+        self.quat = scale_correction(heading, self.beta) * tilted;
+
+        Ok(&self.quat)
+    }
+
+    fn update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize accelerometer measurement
+        let norm = accelerometer.norm();
+        if norm == zero {
+            return Err(AhrsError::AccelerometerNormZero);
+        }
+        let accel = accelerometer / norm;
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, &accel, self.sample_period);
+
+        let predicted = self.integrate(gyroscope);
+        let (tilted, tilt_angle) =
+            self.tilt_correction(&predicted, &accel, self.accelerometer_gain(norm));
+
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &self.quat,
+            &accel,
+            None,
+            tilt_angle,
+            UpdateMode::Imu,
+        );
+
+        self.quat = tilted;
+
+        Ok(&self.quat)
+    }
+
+    fn update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N> {
+        self.diagnostics = Diagnostics::default();
+        self.quat = self.integrate(gyroscope);
+
+        &self.quat
+    }
+
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+
+        // Heading correction about the Earth frame's z-axis, from the magnetometer in the Earth
+        // frame
+        let Some(heading) = heading_quaternion(&(self.quat * mag)) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_heading(&self.quat, &mag, self.sample_period);
+        self.diagnostics =
+            Diagnostics::from_heading(self.diagnostics_enabled, &self.quat, &mag, heading.angle());
+
+        self.quat = scale_correction(heading, self.beta) * self.quat;
+
+        Ok(&self.quat)
+    }
+}
@@ -0,0 +1,264 @@
+use crate::ahrs::{Ahrs, AhrsError, AhrsState};
+use nalgebra::{UnitQuaternion, Vector3};
+use simba::scalar::RealField;
+
+/// A single set of sensor readings, optionally stamped with the time it was sampled at.
+///
+/// # Example
+/// ```
+/// # use ahrs::Sample;
+/// # use nalgebra::Vector3;
+/// let gyroscope = Vector3::new(0.01f64, 0.02, 0.03);
+/// let accelerometer = Vector3::new(0.0, 0.0, 1.0);
+/// let magnetometer = Vector3::new(0.5, 0.0, -0.8);
+///
+/// let sample = Sample::new(gyroscope, accelerometer, magnetometer).with_timestamp(1.5);
+/// assert_eq!(sample, (gyroscope, accelerometer, magnetometer, 1.5).into());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample<N: RealField + Copy> {
+    /// Gyroscope measurement, in radians per second.
+    pub gyroscope: Vector3<N>,
+    /// Accelerometer measurement.
+    pub accelerometer: Vector3<N>,
+    /// Magnetometer measurement, if one was taken.
+    pub magnetometer: Option<Vector3<N>>,
+    /// Time the sample was taken, in seconds.
+    pub timestamp: Option<N>,
+}
+
+impl<N: RealField + Copy> Sample<N> {
+    /// Creates a new 9dof sample without a timestamp.
+    pub fn new(gyroscope: Vector3<N>, accelerometer: Vector3<N>, magnetometer: Vector3<N>) -> Self {
+        Sample {
+            gyroscope,
+            accelerometer,
+            magnetometer: Some(magnetometer),
+            timestamp: None,
+        }
+    }
+
+    /// Creates a new 6dof sample without a magnetometer measurement or timestamp.
+    pub fn new_imu(gyroscope: Vector3<N>, accelerometer: Vector3<N>) -> Self {
+        Sample {
+            gyroscope,
+            accelerometer,
+            magnetometer: None,
+            timestamp: None,
+        }
+    }
+
+    /// Returns the sample stamped with `timestamp`, in seconds.
+    pub fn with_timestamp(self, timestamp: N) -> Self {
+        Sample {
+            timestamp: Some(timestamp),
+            ..self
+        }
+    }
+}
+
+impl<N: RealField + Copy> From<(Vector3<N>, Vector3<N>)> for Sample<N> {
+    fn from((gyroscope, accelerometer): (Vector3<N>, Vector3<N>)) -> Self {
+        Sample::new_imu(gyroscope, accelerometer)
+    }
+}
+
+impl<N: RealField + Copy> From<(Vector3<N>, Vector3<N>, Vector3<N>)> for Sample<N> {
+    fn from(
+        (gyroscope, accelerometer, magnetometer): (Vector3<N>, Vector3<N>, Vector3<N>),
+    ) -> Self {
+        Sample::new(gyroscope, accelerometer, magnetometer)
+    }
+}
+
+impl<N: RealField + Copy> From<(Vector3<N>, Vector3<N>, Vector3<N>, N)> for Sample<N> {
+    fn from(
+        (gyroscope, accelerometer, magnetometer, timestamp): (
+            Vector3<N>,
+            Vector3<N>,
+            Vector3<N>,
+            N,
+        ),
+    ) -> Self {
+        Sample::new(gyroscope, accelerometer, magnetometer).with_timestamp(timestamp)
+    }
+}
+
+/// Describes how a batch update handles a sample that fails to update the filter.
+///
+/// A timestamp which does not increase past the last valid one fails with
+/// `AhrsError::TimestampNotIncreasing`, in which case `Fallback` updates with the current sampling
+/// period instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Leave the filter state untouched, repeating the current estimate for the failed sample.
+    Skip,
+    /// Retry with `Ahrs::update_imu` on a magnetometer error, and with `Ahrs::update_gyro` on an
+    /// accelerometer error.
+    Fallback,
+    /// Stop processing and return the error.
+    Abort,
+}
+
+/// Error returned by a batch update aborted under `ErrorPolicy::Abort`.
+#[derive(Debug)]
+pub struct BatchError {
+    /// Index of the sample which failed to update the filter.
+    pub index: usize,
+    /// Reason the update failed.
+    pub error: AhrsError,
+}
+
+/// Extension trait for running an AHRS filter over a batch of samples.
+///
+/// Implemented for every filter which implements both `Ahrs` and `AhrsState`.
+///
+/// # Example
+/// ```
+/// # use ahrs::{AhrsBatch, ErrorPolicy, Madgwick};
+/// # use nalgebra::{UnitQuaternion, Vector3};
+/// let mut ahrs = Madgwick::default();
+///
+/// let samples = [
+///     (Vector3::new(0.01, 0.02, 0.03), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.5, 0.0, -0.8)),
+///     (Vector3::new(0.01, 0.02, 0.03), Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.5, 0.0, -0.8)),
+/// ];
+/// let mut quats = [UnitQuaternion::identity(); 2];
+///
+/// let written = ahrs.update_batch_into(samples, &mut quats, ErrorPolicy::Skip).unwrap();
+/// assert_eq!(written, 2);
+/// assert_eq!(quats[0], quats[1]);
+/// ```
+pub trait AhrsBatch<N: RealField + Copy>: Ahrs<N> + AhrsState<N> {
+    /// Updates the filter with a single sample, handling failure according to `policy`.
+    ///
+    /// The sample's timestamp, if any, is ignored. Returns the updated quaternion on success, or
+    /// the `AhrsError` which caused the update to fail under `ErrorPolicy::Abort`.
+    fn update_sample(
+        &mut self,
+        sample: &Sample<N>,
+        policy: ErrorPolicy,
+    ) -> Result<UnitQuaternion<N>, AhrsError> {
+        let result = match sample.magnetometer {
+            Some(ref magnetometer) => {
+                self.update(&sample.gyroscope, &sample.accelerometer, magnetometer)
+            }
+            None => self.update_imu(&sample.gyroscope, &sample.accelerometer),
+        };
+
+        match (result.copied(), policy) {
+            (Ok(quat), _) => Ok(quat),
+            (Err(error), ErrorPolicy::Abort) => Err(error),
+            (Err(_), ErrorPolicy::Skip) => Ok(self.quat()),
+            (Err(AhrsError::MagnetometerNormZero), ErrorPolicy::Fallback) => self
+                .update_imu(&sample.gyroscope, &sample.accelerometer)
+                .copied()
+                .or_else(|_| Ok(*self.update_gyro(&sample.gyroscope))),
+            (Err(_), ErrorPolicy::Fallback) => Ok(*self.update_gyro(&sample.gyroscope)),
+        }
+    }
+
+    /// Updates the filter with each of `samples` in order, writing the resulting quaternions to
+    /// `output`.
+    ///
+    /// Processing stops once either `samples` or `output` is exhausted, returning the number of
+    /// quaternions written. Whenever consecutive samples are both timestamped, the sampling period
+    /// is set to the difference between them before updating, which must be positive.
+    fn update_batch_into<I>(
+        &mut self,
+        samples: I,
+        output: &mut [UnitQuaternion<N>],
+        policy: ErrorPolicy,
+    ) -> Result<usize, BatchError>
+    where
+        I: IntoIterator,
+        I::Item: Into<Sample<N>>,
+    {
+        let mut last_timestamp = None;
+        let mut written = 0;
+
+        for (index, (sample, out)) in samples.into_iter().zip(output.iter_mut()).enumerate() {
+            *out = update_timestamped(self, &mut last_timestamp, &sample.into(), policy)
+                .map_err(|error| BatchError { index, error })?;
+            written += 1;
+        }
+
+        Ok(written)
+    }
+
+    /// Updates the filter with each of `samples` in order, collecting the resulting quaternions.
+    ///
+    /// Whenever consecutive samples are both timestamped, the sampling period is set to the
+    /// difference between them before updating, which must be positive.
+    #[cfg(feature = "std")]
+    fn update_batch<I>(
+        &mut self,
+        samples: I,
+        policy: ErrorPolicy,
+    ) -> Result<Vec<UnitQuaternion<N>>, BatchError>
+    where
+        I: IntoIterator,
+        I::Item: Into<Sample<N>>,
+    {
+        let mut last_timestamp = None;
+
+        samples
+            .into_iter()
+            .enumerate()
+            .map(|(index, sample)| {
+                update_timestamped(self, &mut last_timestamp, &sample.into(), policy)
+                    .map_err(|error| BatchError { index, error })
+            })
+            .collect()
+    }
+}
+
+impl<N: RealField + Copy, A: Ahrs<N> + AhrsState<N>> AhrsBatch<N> for A {}
+
+/// Sets the sampling period of `ahrs` from the time elapsed since the last timestamped sample,
+/// failing without changing either if `sample` is not later than it.
+pub(crate) fn update_sample_period<N: RealField + Copy, A: AhrsState<N> + ?Sized>(
+    ahrs: &mut A,
+    last_timestamp: &mut Option<N>,
+    sample: &Sample<N>,
+) -> Result<(), AhrsError> {
+    if let (Some(last), Some(timestamp)) = (*last_timestamp, sample.timestamp) {
+        if timestamp <= last {
+            return Err(AhrsError::TimestampNotIncreasing);
+        }
+        ahrs.set_sample_period(timestamp - last);
+    }
+    *last_timestamp = sample.timestamp;
+
+    Ok(())
+}
+
+/// Sets the sampling period of `ahrs` from the sample's timestamp, returning whether the sample
+/// is to be processed, as decided by `policy` for a timestamp which does not increase.
+pub(crate) fn accept_timestamp<N: RealField + Copy, A: AhrsState<N> + ?Sized>(
+    ahrs: &mut A,
+    last_timestamp: &mut Option<N>,
+    sample: &Sample<N>,
+    policy: ErrorPolicy,
+) -> Result<bool, AhrsError> {
+    match (update_sample_period(ahrs, last_timestamp, sample), policy) {
+        (Ok(()), _) | (Err(_), ErrorPolicy::Fallback) => Ok(true),
+        (Err(_), ErrorPolicy::Skip) => Ok(false),
+        (Err(error), ErrorPolicy::Abort) => Err(error),
+    }
+}
+
+/// Updates `ahrs` with a sample after setting its sampling period from the sample's timestamp,
+/// handling a timestamp which does not increase according to `policy`.
+pub(crate) fn update_timestamped<N: RealField + Copy, A: AhrsBatch<N> + ?Sized>(
+    ahrs: &mut A,
+    last_timestamp: &mut Option<N>,
+    sample: &Sample<N>,
+    policy: ErrorPolicy,
+) -> Result<UnitQuaternion<N>, AhrsError> {
+    if accept_timestamp(ahrs, last_timestamp, sample, policy)? {
+        ahrs.update_sample(sample, policy)
+    } else {
+        Ok(ahrs.quat())
+    }
+}
@@ -0,0 +1,411 @@
+#![allow(non_snake_case)]
+
+use crate::{
+    ahrs::{
+        heading_error, rotate_heading, Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState,
+        AhrsUncertainty, Diagnostics, UpdateMode,
+    },
+    uncertainty::Residuals,
+};
+use core::hash;
+use nalgebra::{Matrix3, Quaternion, Rotation3, Scalar, UnitQuaternion, Vector3};
+use simba::{
+    scalar::RealField,
+    simd::{SimdRealField, SimdValue},
+};
+
+/// Quaternion complementary filter AHRS implementation.
+///
+/// Each update integrates the gyroscope, then blends the result towards the attitude measured by
+/// the accelerometer (and magnetometer) by a fixed fraction `alpha`. Without a magnetometer, only
+/// the tilt is corrected and the heading is left to the gyroscope.
+///
+/// # Example
+/// ```
+/// # use ahrs::Complementary;
+/// let mut ahrs = Complementary::new(0.002390625f64, 0.02);
+/// println!("complementary filter: {:?}", ahrs);
+///
+/// // Can now process IMU data using `Ahrs::update_imu`, etc.
+/// ```
+#[derive(Debug)]
+pub struct Complementary<N: Scalar + SimdValue + Copy> {
+    /// Expected sampling period, in seconds.
+    sample_period: N,
+    /// Fraction of the measured attitude blended in on each update.
+    alpha: N,
+    /// Measurement residuals, from which the attitude uncertainty is estimated.
+    residuals: Residuals<N>,
+    /// Diagnostics of the most recent update.
+    diagnostics: Diagnostics<N>,
+    /// Whether the diagnostics measure the accelerometer and magnetometer errors.
+    diagnostics_enabled: bool,
+    /// Filter state quaternion.
+    pub quat: UnitQuaternion<N>,
+}
+
+impl<N: SimdRealField + Eq + Copy> Eq for Complementary<N> where N::Element: SimdRealField + Copy {}
+
+impl<N: SimdRealField + Copy> PartialEq for Complementary<N>
+where
+    N::Element: SimdRealField + Copy,
+{
+    fn eq(&self, rhs: &Self) -> bool {
+        self.sample_period == rhs.sample_period
+            && self.alpha == rhs.alpha
+            && self.residuals == rhs.residuals
+            && self.quat == rhs.quat
+    }
+}
+
+impl<N: SimdRealField + hash::Hash + Copy> hash::Hash for Complementary<N> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.sample_period.hash(state);
+        self.alpha.hash(state);
+        self.residuals.hash(state);
+        self.quat.hash(state);
+    }
+}
+
+impl<N: Scalar + Copy + SimdValue> Copy for Complementary<N> {}
+
+impl<N: Scalar + SimdValue + Copy> Clone for Complementary<N> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Default for Complementary<f64> {
+    /// Creates a new `Complementary` instance with default filter parameters.
+    ///
+    /// ```
+    /// # use ahrs::Complementary;
+    /// dbg!(Complementary::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // Complementary {
+    /// //     sample_period: 1.0f64/256.0,
+    /// //     alpha: 0.02f64,
+    /// //     residuals: Residuals { enabled: false, .. },
+    /// //     diagnostics: Diagnostics { .. },
+    /// //     diagnostics_enabled: false,
+    /// //     quat: Quaternion { w: 1.0f64, i: 0.0, j: 0.0, k: 0.0 },
+    /// // };
+    /// ```
+    fn default() -> Complementary<f64> {
+        Complementary {
+            sample_period: (1.0f64) / (256.0),
+            alpha: 0.02f64,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat: UnitQuaternion::new_unchecked(Quaternion::new(1.0f64, 0.0, 0.0, 0.0)),
+        }
+    }
+}
+
+impl<N: Scalar + SimdValue + num_traits::One + num_traits::Zero + Copy> Complementary<N> {
+    /// Creates a new `Complementary` AHRS instance with identity quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `alpha` - Fraction of the measured attitude blended in on each update, between 0 and 1.
+    pub fn new(sample_period: N, alpha: N) -> Self {
+        Complementary::new_with_quat(
+            sample_period,
+            alpha,
+            UnitQuaternion::new_unchecked(Quaternion::new(
+                N::one(),
+                N::zero(),
+                N::zero(),
+                N::zero(),
+            )),
+        )
+    }
+
+    /// Creates a new `Complementary` AHRS instance with given quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `alpha` - Fraction of the measured attitude blended in on each update, between 0 and 1.
+    /// * `quat` - Existing filter state quaternion.
+    pub fn new_with_quat(sample_period: N, alpha: N, quat: UnitQuaternion<N>) -> Self {
+        Complementary {
+            sample_period,
+            alpha,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat,
+        }
+    }
+}
+
+impl<N: RealField + Copy> Complementary<N> {
+    /// Creates a new `Complementary` AHRS instance with identity quaternion, deriving `alpha` from
+    /// the time constant of the filter.
+    ///
+    /// `alpha` is fixed at construction, so changing the sampling period afterwards also changes
+    /// the effective time constant.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `time_constant` - Time over which gyroscope drift is corrected, in seconds.
+    pub fn new_with_time_constant(sample_period: N, time_constant: N) -> Self {
+        Complementary::new(
+            sample_period,
+            sample_period / (time_constant + sample_period),
+        )
+    }
+
+    /// Integrates `gyroscope` into the current state quaternion.
+    fn integrate(&self, gyroscope: &Vector3<N>) -> UnitQuaternion<N> {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Compute rate of change for quaternion
+        let qDot = q * Quaternion::from_parts(zero, *gyroscope) * half;
+
+        // Integrate to yield quaternion
+        UnitQuaternion::from_quaternion(q + qDot * self.sample_period)
+    }
+
+    /// Blends `from` towards `to` by `alpha`, along the shortest path.
+    fn blend(&self, from: &UnitQuaternion<N>, to: &UnitQuaternion<N>) -> UnitQuaternion<N> {
+        let to = if from.coords.dot(&to.coords) < N::zero() {
+            -to.into_inner()
+        } else {
+            to.into_inner()
+        };
+
+        // Normalized linear interpolation is cheaper than slerp, and close to it for small alpha
+        UnitQuaternion::from_quaternion(from.into_inner().lerp(&to, self.alpha))
+    }
+
+    /// Returns the filter with tracking of the measurement residuals reported by
+    /// `AhrsUncertainty` enabled or disabled.
+    pub fn with_uncertainty(self, enabled: bool) -> Self {
+        Complementary {
+            residuals: Residuals::with_tracking(enabled, self.sample_period),
+            ..self
+        }
+    }
+
+    /// Returns the filter with measurement of the accelerometer and magnetometer errors reported
+    /// by `AhrsDiagnostics` enabled or disabled.
+    pub fn with_diagnostics(self, enabled: bool) -> Self {
+        Complementary {
+            diagnostics_enabled: enabled,
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "field_access")]
+impl<N: Scalar + SimdValue + Copy> Complementary<N> {
+    /// Expected sampling period, in seconds.
+    pub fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    /// Mutable reference to expected sampling period, in seconds.
+    pub fn sample_period_mut(&mut self) -> &mut N {
+        &mut self.sample_period
+    }
+
+    /// Fraction of the measured attitude blended in on each update.
+    pub fn alpha(&self) -> N {
+        self.alpha
+    }
+
+    /// Mutable reference to fraction of the measured attitude blended in on each update.
+    pub fn alpha_mut(&mut self) -> &mut N {
+        &mut self.alpha
+    }
+
+    /// Filter state quaternion.
+    pub fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    /// Mutable reference to filter state quaternion.
+    pub fn quat_mut(&mut self) -> &mut UnitQuaternion<N> {
+        &mut self.quat
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsState<N> for Complementary<N> {
+    fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    fn set_sample_period(&mut self, sample_period: N) {
+        self.sample_period = sample_period;
+    }
+
+    fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    fn set_quat(&mut self, quat: UnitQuaternion<N>) {
+        self.quat = quat;
+    }
+}
+
+impl<N: RealField + Copy> AhrsUncertainty<N> for Complementary<N> {
+    fn attitude_std_dev(&self) -> Vector3<N> {
+        self.residuals.std_dev()
+    }
+}
+
+impl<N: RealField + Copy> AhrsReverse<N> for Complementary<N> {
+    fn reversed(&self) -> Self {
+        *self
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsDiagnostics<N> for Complementary<N> {
+    fn diagnostics(&self) -> Diagnostics<N> {
+        self.diagnostics
+    }
+}
+
+impl<N: RealField + Copy> Ahrs<N> for Complementary<N> {
+    fn update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize accelerometer measurement, which points up in the body frame
+        let Some(up) = accelerometer.try_normalize(zero) else {
+            return Err(AhrsError::AccelerometerNormZero);
+        };
+
+        // Normalize horizontal component of the magnetometer measurement
+        let Some(west) = up.cross(magnetometer).try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+        let north = west.cross(&up);
+
+        // Residuals of the measurements against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, &up, self.sample_period);
+        self.residuals
+            .update_heading(&self.quat, magnetometer, self.sample_period);
+
+        // Attitude measured by the accelerometer and magnetometer (TRIAD), whose rows are the
+        // Earth frame axes expressed in the body frame
+        let measured = UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(
+            Matrix3::from_rows(&[north.transpose(), west.transpose(), up.transpose()]),
+        ));
+
+        let predicted = self.integrate(gyroscope);
+
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &self.quat,
+            &up,
+            Some(magnetometer),
+            predicted.angle_to(&measured),
+            UpdateMode::Marg,
+        );
+
+        self.quat = self.blend(&predicted, &measured);
+
+        Ok(&self.quat)
+    }
+
+    fn update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+        let two: N = N::one() + N::one();
+
+        // Normalize accelerometer measurement
+        let Some(accel) = accelerometer.try_normalize(zero) else {
+            return Err(AhrsError::AccelerometerNormZero);
+        };
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, &accel, self.sample_period);
+
+        let predicted = self.integrate(gyroscope);
+
+        // Measured up direction in the Earth frame
+        let g = predicted * accel;
+
+        // Tilt correction rotating the measured up direction onto the Earth frame's z-axis. Its
+        // rotation axis is horizontal, so the heading is unaffected
+        let w = (g[2] + N::one()) * two;
+        if w <= zero {
+            // Measured up direction is exactly opposite, so the correction axis is undefined
+            self.diagnostics = Diagnostics::from_measurements(
+                self.diagnostics_enabled,
+                &self.quat,
+                &accel,
+                None,
+                zero,
+                UpdateMode::Gyro,
+            );
+            self.quat = predicted;
+            return Ok(&self.quat);
+        }
+        let w = w.sqrt();
+        let correction =
+            UnitQuaternion::new_unchecked(Quaternion::new(w / two, g[1] / w, -g[0] / w, zero));
+
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &self.quat,
+            &accel,
+            None,
+            correction.angle(),
+            UpdateMode::Imu,
+        );
+
+        self.quat = self.blend(&predicted, &(correction * predicted));
+
+        Ok(&self.quat)
+    }
+
+    fn update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N> {
+        self.diagnostics = Diagnostics::default();
+        self.quat = self.integrate(gyroscope);
+
+        &self.quat
+    }
+
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+        let error = heading_error(&self.quat, &mag)?;
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_heading(&self.quat, &mag, self.sample_period);
+        self.diagnostics =
+            Diagnostics::from_heading(self.diagnostics_enabled, &self.quat, &mag, error.abs());
+
+        // Blend the heading towards the measured one
+        self.quat = rotate_heading(&self.quat, -self.alpha * error);
+
+        Ok(&self.quat)
+    }
+}
@@ -0,0 +1,1026 @@
+//! Partial `RealField` support for fixed-point numbers, for targets without an FPU.
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{
+    Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign,
+};
+use fixed::traits::ToFixed;
+use fixed::types::extra::{LeEqU32, U16};
+use fixed::types::I2F30;
+use num_traits::{Bounded, FromPrimitive, Num, One, Signed, Zero};
+use simba::scalar::{ComplexField, Field, RealField, SubsetOf};
+use simba::simd::{PrimitiveSimdValue, SimdValue};
+
+/// Signed 32-bit fixed-point number with `Frac` fractional bits, usable as the scalar type of the
+/// AHRS filters.
+///
+/// All arithmetic, including the square roots used to normalize measurements and renormalize the
+/// state quaternion, is carried out on integers. The transcendental functions are evaluated with
+/// fixed-point polynomial approximations accurate to roughly `1e-4`: `atan2` and `sin_cos`
+/// directly, and `exp`, `ln`, `powf`, the hyperbolic functions and those derived from them by
+/// reduction to `2^x` over `[0, 1)` and `ln(x)` over `[1, 2)`, relative to the magnitude of the
+/// result, except for `powf(x, n)` whose error grows with `n ln(x)`. Results which do not fit
+/// the format saturate, and arguments outside a function's domain, which would give NaN for
+/// floating-point numbers, return zero or, for logarithms of non-positive numbers, the minimum
+/// value.
+///
+/// Sensor measurements should be scaled so that their squared norms fit within the integer range
+/// of the chosen format, e.g. `I16F16` with accelerometer in g and magnetometer in gauss.
+///
+/// # Example
+/// ```
+/// # use ahrs::{Ahrs, I16F16, Madgwick};
+/// # use nalgebra::Vector3;
+/// let mut ahrs = Madgwick::new(I16F16::from_num(1.0 / 256.0), I16F16::from_num(0.1));
+///
+/// let gyroscope = Vector3::new(0.1, 0.2, 0.3).map(I16F16::from_num);
+/// let accelerometer = Vector3::new(0.0, 0.0, 1.0).map(I16F16::from_num);
+///
+/// let quat = ahrs.update_imu(&gyroscope, &accelerometer).unwrap();
+/// ```
+#[derive(Clone, Copy, Default)]
+#[repr(transparent)]
+pub struct FixedI32<Frac>(pub fixed::FixedI32<Frac>);
+
+/// Fixed-point number with 16 integer and 16 fractional bits.
+pub type I16F16 = FixedI32<U16>;
+
+impl<Frac: LeEqU32> FixedI32<Frac> {
+    /// Creates a fixed-point number from another number.
+    pub fn from_num<Src: ToFixed>(src: Src) -> Self {
+        FixedI32(fixed::FixedI32::from_num(src))
+    }
+
+    /// Converts a fixed-point number of another format, e.g. a polynomial coefficient.
+    fn from_fixed(src: I2F30) -> Self {
+        FixedI32(fixed::FixedI32::from_num(src))
+    }
+
+    /// Creates a fixed-point number from an integer.
+    fn from_int(src: i32) -> Self {
+        FixedI32(fixed::FixedI32::from_num(src))
+    }
+
+    /// Arctangent of `self`, which must lie within `[-1, 1]`.
+    fn atan_unit(self) -> Self {
+        // Minimax polynomial, accurate to about 1e-5 radians
+        const C1: I2F30 = I2F30::lit("0.9998660");
+        const C3: I2F30 = I2F30::lit("-0.3302995");
+        const C5: I2F30 = I2F30::lit("0.1801410");
+        const C7: I2F30 = I2F30::lit("-0.0851330");
+        const C9: I2F30 = I2F30::lit("0.0208351");
+
+        let x2 = self * self;
+        self * (Self::from_fixed(C1)
+            + x2 * (Self::from_fixed(C3)
+                + x2 * (Self::from_fixed(C5)
+                    + x2 * (Self::from_fixed(C7) + x2 * Self::from_fixed(C9)))))
+    }
+
+    /// Sine and cosine of `self`, which must lie within `[-pi/2, pi/2]`.
+    fn sin_cos_half_range(self) -> (Self, Self) {
+        // Taylor series evaluated in Horner form
+        let one = Self::one();
+        let x2 = self * self;
+        let term = |n: i32| x2 / Self::from_int(n);
+
+        let sin = self * (one - term(6) * (one - term(20) * (one - term(42) * (one - term(72)))));
+        let cos = one - term(2) * (one - term(12) * (one - term(30) * (one - term(56))));
+
+        (sin, cos)
+    }
+}
+
+impl<Frac: LeEqU32 + Send + Sync> FixedI32<Frac> {
+    /// `2^self`, for `self` within `[0, 1)`.
+    fn exp2_unit(self) -> Self {
+        // Taylor series of exp(x ln 2) evaluated in Horner form
+        let one = Self::one();
+        let x = self * Self::ln_2();
+        let term = |n: i32| x / Self::from_int(n);
+
+        one + term(1)
+            * (one
+                + term(2) * (one + term(3) * (one + term(4) * (one + term(5) * (one + term(6))))))
+    }
+
+    /// Inverse hyperbolic tangent of `self`, which must lie within `[-1/3, 1/3]`.
+    fn atanh_unit(self) -> Self {
+        // Taylor series evaluated in Horner form
+        let one = Self::one();
+        let x2 = self * self;
+        let coefficient = |n: i32| one / Self::from_int(n);
+
+        self * (one
+            + x2 * (coefficient(3)
+                + x2 * (coefficient(5) + x2 * (coefficient(7) + x2 * coefficient(9)))))
+    }
+
+    /// Natural logarithm of `self`, which must lie within `[1, 2)`.
+    fn ln_unit(self) -> Self {
+        // ln(x) = 2 atanh((x - 1) / (x + 1))
+        let one = Self::one();
+        let s = ((self - one) / (self + one)).atanh_unit();
+        s + s
+    }
+
+    /// Splits `self`, which must be positive, into a mantissa within `[1, 2)` and a binary
+    /// exponent.
+    fn frexp(self) -> (Self, i32) {
+        let bits = self.to_bits();
+        let exponent =
+            31 - bits.leading_zeros() as i32 - fixed::FixedI32::<Frac>::FRAC_NBITS as i32;
+        let mantissa = if exponent >= 0 {
+            bits >> exponent
+        } else {
+            bits << -exponent
+        };
+
+        (Self::from_bits(mantissa), exponent)
+    }
+
+    /// Product of `self` and `other`, saturating on overflow.
+    fn saturating_mul(self, other: Self) -> Self {
+        FixedI32(self.0.saturating_mul(other.0))
+    }
+}
+
+impl<Frac> FixedI32<Frac> {
+    /// Creates a fixed-point number that has a bitwise representation identical to the given
+    /// integer.
+    pub const fn from_bits(bits: i32) -> Self {
+        FixedI32(fixed::FixedI32::from_bits(bits))
+    }
+
+    /// Creates an integer that has a bitwise representation identical to the given fixed-point
+    /// number.
+    pub const fn to_bits(self) -> i32 {
+        self.0.to_bits()
+    }
+}
+
+impl<Frac: LeEqU32> PartialEq for FixedI32<Frac> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<Frac: LeEqU32> Eq for FixedI32<Frac> {}
+
+impl<Frac: LeEqU32> PartialOrd for FixedI32<Frac> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Frac: LeEqU32> Ord for FixedI32<Frac> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl<Frac: LeEqU32> Hash for FixedI32<Frac> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<Frac: LeEqU32> fmt::Debug for FixedI32<Frac> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl<Frac: LeEqU32> fmt::Display for FixedI32<Frac> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+macro_rules! impl_fixed_op(
+    ($($Op: ident, $op: ident, $OpAssign: ident, $op_assign: ident;)*) => {$(
+        impl<Frac: LeEqU32> $Op for FixedI32<Frac> {
+            type Output = Self;
+
+            #[inline]
+            fn $op(self, rhs: Self) -> Self {
+                FixedI32(self.0.$op(rhs.0))
+            }
+        }
+
+        impl<Frac: LeEqU32> $OpAssign for FixedI32<Frac> {
+            #[inline]
+            fn $op_assign(&mut self, rhs: Self) {
+                self.0.$op_assign(rhs.0)
+            }
+        }
+    )*}
+);
+
+impl_fixed_op!(
+    Add, add, AddAssign, add_assign;
+    Sub, sub, SubAssign, sub_assign;
+    Mul, mul, MulAssign, mul_assign;
+    Div, div, DivAssign, div_assign;
+    Rem, rem, RemAssign, rem_assign;
+);
+
+impl<Frac: LeEqU32> Neg for FixedI32<Frac> {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        FixedI32(-self.0)
+    }
+}
+
+impl<Frac: LeEqU32> Zero for FixedI32<Frac> {
+    fn zero() -> Self {
+        FixedI32(fixed::FixedI32::ZERO)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == fixed::FixedI32::<Frac>::ZERO
+    }
+}
+
+impl<Frac: LeEqU32> One for FixedI32<Frac> {
+    fn one() -> Self {
+        Self::from_int(1)
+    }
+}
+
+impl<Frac: LeEqU32> Num for FixedI32<Frac> {
+    type FromStrRadixErr = fixed::RadixParseFixedError;
+
+    /// Parses a string in base 2, 8, 10 or 16, failing with
+    /// `RadixParseFixedError::UnsupportedRadix` for any other base.
+    fn from_str_radix(src: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        match radix {
+            2 => fixed::FixedI32::from_str_binary(src),
+            8 => fixed::FixedI32::from_str_octal(src),
+            10 => src.parse(),
+            16 => fixed::FixedI32::from_str_hex(src),
+            _ => return Err(fixed::RadixParseFixedError::UnsupportedRadix),
+        }
+        .map(FixedI32)
+        .map_err(fixed::RadixParseFixedError::ParseFixedError)
+    }
+}
+
+impl<Frac: LeEqU32> Bounded for FixedI32<Frac> {
+    fn min_value() -> Self {
+        FixedI32(fixed::FixedI32::MIN)
+    }
+
+    fn max_value() -> Self {
+        FixedI32(fixed::FixedI32::MAX)
+    }
+}
+
+impl<Frac: LeEqU32> FromPrimitive for FixedI32<Frac> {
+    fn from_i64(n: i64) -> Option<Self> {
+        n.checked_to_fixed().map(FixedI32)
+    }
+
+    fn from_u64(n: u64) -> Option<Self> {
+        n.checked_to_fixed().map(FixedI32)
+    }
+
+    fn from_f32(n: f32) -> Option<Self> {
+        n.checked_to_fixed().map(FixedI32)
+    }
+
+    fn from_f64(n: f64) -> Option<Self> {
+        n.checked_to_fixed().map(FixedI32)
+    }
+}
+
+impl<Frac: LeEqU32> Signed for FixedI32<Frac> {
+    fn abs(&self) -> Self {
+        FixedI32(self.0.abs())
+    }
+
+    fn abs_sub(&self, other: &Self) -> Self {
+        if self > other {
+            *self - *other
+        } else {
+            Self::zero()
+        }
+    }
+
+    fn signum(&self) -> Self {
+        FixedI32(self.0.signum())
+    }
+
+    fn is_positive(&self) -> bool {
+        self.0.is_positive()
+    }
+
+    fn is_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+}
+
+impl<Frac: LeEqU32> PrimitiveSimdValue for FixedI32<Frac> {}
+
+impl<Frac: LeEqU32> SimdValue for FixedI32<Frac> {
+    const LANES: usize = 1;
+    type Element = Self;
+    type SimdBool = bool;
+
+    #[inline(always)]
+    fn splat(val: Self::Element) -> Self {
+        val
+    }
+
+    #[inline(always)]
+    fn extract(&self, _: usize) -> Self::Element {
+        *self
+    }
+
+    #[inline(always)]
+    unsafe fn extract_unchecked(&self, _: usize) -> Self::Element {
+        *self
+    }
+
+    #[inline(always)]
+    fn replace(&mut self, _: usize, val: Self::Element) {
+        *self = val
+    }
+
+    #[inline(always)]
+    unsafe fn replace_unchecked(&mut self, _: usize, val: Self::Element) {
+        *self = val
+    }
+
+    #[inline(always)]
+    fn select(self, cond: Self::SimdBool, other: Self) -> Self {
+        if cond {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl<Frac: LeEqU32> Field for FixedI32<Frac> {}
+
+macro_rules! impl_float_subset(
+    ($($T: ty),*) => {$(
+        impl<Frac: LeEqU32> SubsetOf<FixedI32<Frac>> for $T {
+            fn to_superset(&self) -> FixedI32<Frac> {
+                FixedI32::from_num(*self)
+            }
+
+            fn from_superset_unchecked(element: &FixedI32<Frac>) -> Self {
+                element.0.to_num()
+            }
+
+            fn is_in_subset(_: &FixedI32<Frac>) -> bool {
+                true
+            }
+        }
+    )*}
+);
+
+impl_float_subset!(f32, f64);
+
+impl<Frac: LeEqU32> SubsetOf<FixedI32<Frac>> for FixedI32<Frac> {
+    fn to_superset(&self) -> FixedI32<Frac> {
+        *self
+    }
+
+    fn from_superset_unchecked(element: &FixedI32<Frac>) -> Self {
+        *element
+    }
+
+    fn is_in_subset(_: &FixedI32<Frac>) -> bool {
+        true
+    }
+}
+
+impl<Frac: LeEqU32> approx::AbsDiffEq for FixedI32<Frac> {
+    type Epsilon = Self;
+
+    fn default_epsilon() -> Self::Epsilon {
+        Self::from_bits(1)
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        if self > other {
+            *self - *other <= epsilon
+        } else {
+            *other - *self <= epsilon
+        }
+    }
+}
+
+impl<Frac: LeEqU32> approx::RelativeEq for FixedI32<Frac> {
+    fn default_max_relative() -> Self::Epsilon {
+        Self::from_bits(1)
+    }
+
+    fn relative_eq(&self, other: &Self, epsilon: Self::Epsilon, max_relative: Self) -> bool {
+        let abs_diff = Signed::abs(&(*self - *other));
+
+        if abs_diff <= epsilon {
+            return true;
+        }
+
+        let largest = Ord::max(Signed::abs(self), Signed::abs(other));
+
+        abs_diff <= largest * max_relative
+    }
+}
+
+impl<Frac: LeEqU32> approx::UlpsEq for FixedI32<Frac> {
+    fn default_max_ulps() -> u32 {
+        4
+    }
+
+    fn ulps_eq(&self, other: &Self, epsilon: Self::Epsilon, max_ulps: u32) -> bool {
+        use approx::AbsDiffEq;
+
+        if self.abs_diff_eq(other, epsilon) {
+            return true;
+        }
+
+        if Signed::signum(self) != Signed::signum(other) {
+            return false;
+        }
+
+        self.to_bits().abs_diff(other.to_bits()) <= max_ulps
+    }
+}
+
+impl<Frac: LeEqU32 + Send + Sync> ComplexField for FixedI32<Frac> {
+    type RealField = Self;
+
+    #[inline]
+    fn from_real(re: Self::RealField) -> Self {
+        re
+    }
+
+    #[inline]
+    fn real(self) -> Self::RealField {
+        self
+    }
+
+    #[inline]
+    fn imaginary(self) -> Self::RealField {
+        Self::zero()
+    }
+
+    #[inline]
+    fn norm1(self) -> Self::RealField {
+        Signed::abs(&self)
+    }
+
+    #[inline]
+    fn modulus(self) -> Self::RealField {
+        Signed::abs(&self)
+    }
+
+    #[inline]
+    fn modulus_squared(self) -> Self::RealField {
+        self * self
+    }
+
+    #[inline]
+    fn argument(self) -> Self::RealField {
+        if self >= Self::zero() {
+            Self::zero()
+        } else {
+            Self::pi()
+        }
+    }
+
+    #[inline]
+    fn to_exp(self) -> (Self, Self) {
+        if self >= Self::zero() {
+            (self, Self::one())
+        } else {
+            (-self, -Self::one())
+        }
+    }
+
+    #[inline]
+    fn recip(self) -> Self {
+        Self::one() / self
+    }
+
+    #[inline]
+    fn conjugate(self) -> Self {
+        self
+    }
+
+    #[inline]
+    fn scale(self, factor: Self::RealField) -> Self {
+        self * factor
+    }
+
+    #[inline]
+    fn unscale(self, factor: Self::RealField) -> Self {
+        self / factor
+    }
+
+    #[inline]
+    fn floor(self) -> Self {
+        FixedI32(self.0.floor())
+    }
+
+    #[inline]
+    fn ceil(self) -> Self {
+        FixedI32(self.0.ceil())
+    }
+
+    #[inline]
+    fn round(self) -> Self {
+        FixedI32(self.0.round())
+    }
+
+    #[inline]
+    fn trunc(self) -> Self {
+        FixedI32(self.0.int())
+    }
+
+    #[inline]
+    fn fract(self) -> Self {
+        FixedI32(self.0.frac())
+    }
+
+    #[inline]
+    fn abs(self) -> Self {
+        Signed::abs(&self)
+    }
+
+    #[inline]
+    fn signum(self) -> Self {
+        Signed::signum(&self)
+    }
+
+    #[inline]
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        self * a + b
+    }
+
+    #[inline]
+    fn powi(self, n: i32) -> Self {
+        let base = if n < 0 { self.recip() } else { self };
+        (0..n.unsigned_abs()).fold(Self::one(), |acc, _| acc * base)
+    }
+
+    #[inline]
+    fn powf(self, n: Self) -> Self {
+        let zero = Self::zero();
+        if self.is_zero() {
+            return match n.cmp(&zero) {
+                Ordering::Greater => zero,
+                Ordering::Equal => Self::one(),
+                Ordering::Less => <Self as Bounded>::max_value(),
+            };
+        }
+
+        // Negative bases only have real powers for integer exponents
+        let magnitude = n.saturating_mul(Signed::abs(&self).ln()).exp();
+        if self > zero {
+            magnitude
+        } else if !n.fract().is_zero() {
+            zero
+        } else if (n / Self::from_int(2)).fract().is_zero() {
+            magnitude
+        } else {
+            -magnitude
+        }
+    }
+
+    #[inline]
+    fn powc(self, n: Self) -> Self {
+        self.powf(n)
+    }
+
+    #[inline]
+    fn sqrt(self) -> Self {
+        FixedI32(self.0.sqrt())
+    }
+
+    #[inline]
+    fn try_sqrt(self) -> Option<Self> {
+        if self >= Self::zero() {
+            Some(self.sqrt())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn exp(self) -> Self {
+        self.saturating_mul(Self::log2_e()).exp2()
+    }
+
+    #[inline]
+    fn exp2(self) -> Self {
+        // 2^x = 2^floor(x) 2^fract(x), the former applied by shifting
+        let int = self.floor();
+        let bits = (self - int).exp2_unit().to_bits();
+        let shift: i32 = int.0.to_num();
+
+        if shift >= 0 {
+            if shift < bits.leading_zeros() as i32 {
+                Self::from_bits(bits << shift)
+            } else {
+                <Self as Bounded>::max_value()
+            }
+        } else if shift > -32 {
+            Self::from_bits(bits >> -shift)
+        } else {
+            Self::zero()
+        }
+    }
+
+    #[inline]
+    fn exp_m1(self) -> Self {
+        let half = Self::one() / Self::from_int(2);
+        if Signed::abs(&self) < half {
+            // Taylor series evaluated in Horner form, avoiding the cancellation of exp(x) - 1
+            let one = Self::one();
+            let term = |n: i32| self / Self::from_int(n);
+
+            self * (one
+                + term(2) * (one + term(3) * (one + term(4) * (one + term(5) * (one + term(6))))))
+        } else {
+            self.exp() - Self::one()
+        }
+    }
+
+    #[inline]
+    fn ln_1p(self) -> Self {
+        let half = Self::one() / Self::from_int(2);
+        if Signed::abs(&self) < half {
+            // ln(1 + x) = 2 atanh(x / (2 + x)), avoiding the rounding of 1 + x
+            let s = (self / (Self::from_int(2) + self)).atanh_unit();
+            s + s
+        } else {
+            FixedI32(self.0.saturating_add(Self::one().0)).ln()
+        }
+    }
+
+    #[inline]
+    fn ln(self) -> Self {
+        if self <= Self::zero() {
+            return <Self as Bounded>::min_value();
+        }
+
+        let (mantissa, exponent) = self.frexp();
+        Self::from_int(exponent) * Self::ln_2() + mantissa.ln_unit()
+    }
+
+    #[inline]
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+
+    #[inline]
+    fn log2(self) -> Self {
+        if self <= Self::zero() {
+            return <Self as Bounded>::min_value();
+        }
+
+        let (mantissa, exponent) = self.frexp();
+        Self::from_int(exponent) + mantissa.ln_unit() * Self::log2_e()
+    }
+
+    #[inline]
+    fn log10(self) -> Self {
+        self.ln() * Self::log10_e()
+    }
+
+    #[inline]
+    fn cbrt(self) -> Self {
+        if self.is_zero() {
+            return self;
+        }
+
+        // Initial estimate from the logarithm, refined by Newton's method
+        let three = Self::from_int(3);
+        let x = Signed::abs(&self);
+        let mut y = (x.log2() / three).exp2();
+        for _ in 0..2 {
+            y = (y + y + x / (y * y)) / three;
+        }
+
+        if self.is_negative() {
+            -y
+        } else {
+            y
+        }
+    }
+
+    #[inline]
+    fn hypot(self, other: Self) -> Self::RealField {
+        (self * self + other * other).sqrt()
+    }
+
+    #[inline]
+    fn sin(self) -> Self {
+        self.sin_cos().0
+    }
+
+    #[inline]
+    fn cos(self) -> Self {
+        self.sin_cos().1
+    }
+
+    #[inline]
+    fn tan(self) -> Self {
+        let (sin, cos) = self.sin_cos();
+        sin / cos
+    }
+
+    #[inline]
+    fn asin(self) -> Self {
+        self.atan2((Self::one() - self * self).sqrt())
+    }
+
+    #[inline]
+    fn acos(self) -> Self {
+        (Self::one() - self * self).sqrt().atan2(self)
+    }
+
+    #[inline]
+    fn atan(self) -> Self {
+        self.atan2(Self::one())
+    }
+
+    #[inline]
+    fn sin_cos(self) -> (Self, Self) {
+        // Reduce to [-pi, pi], then reflect into [-pi/2, pi/2]
+        let mut x = self % Self::two_pi();
+        if x > Self::pi() {
+            x -= Self::two_pi();
+        } else if x < -Self::pi() {
+            x += Self::two_pi();
+        }
+
+        if x > Self::frac_pi_2() {
+            let (sin, cos) = (Self::pi() - x).sin_cos_half_range();
+            (sin, -cos)
+        } else if x < -Self::frac_pi_2() {
+            let (sin, cos) = (-Self::pi() - x).sin_cos_half_range();
+            (sin, -cos)
+        } else {
+            x.sin_cos_half_range()
+        }
+    }
+
+    #[inline]
+    fn sinh(self) -> Self {
+        let x = Signed::abs(&self);
+        let y = if x < Self::one() {
+            // Taylor series evaluated in Horner form, avoiding cancellation near zero
+            let one = Self::one();
+            let x2 = x * x;
+            let term = |n: i32| x2 / Self::from_int(n);
+
+            x * (one + term(6) * (one + term(20) * (one + term(42) * (one + term(72)))))
+        } else {
+            let (e, two) = (x.exp(), Self::from_int(2));
+            e / two - e.recip() / two
+        };
+
+        if self.is_negative() {
+            -y
+        } else {
+            y
+        }
+    }
+
+    #[inline]
+    fn cosh(self) -> Self {
+        let (e, two) = (Signed::abs(&self).exp(), Self::from_int(2));
+        e / two + e.recip() / two
+    }
+
+    #[inline]
+    fn tanh(self) -> Self {
+        // tanh(|x|) = -expm1(-2|x|) / (2 + expm1(-2|x|))
+        let x = Signed::abs(&self);
+        let e = (-FixedI32(x.0.saturating_add(x.0))).exp_m1();
+        let y = -e / (Self::from_int(2) + e);
+
+        if self.is_negative() {
+            -y
+        } else {
+            y
+        }
+    }
+
+    #[inline]
+    fn asinh(self) -> Self {
+        let one = Self::one();
+        let x = Signed::abs(&self);
+        let y = if x <= one {
+            (x + x * x / (one + (one + x * x).sqrt())).ln_1p()
+        } else {
+            let r = x.recip();
+            x.ln() + (one + (one + r * r).sqrt()).ln()
+        };
+
+        if self.is_negative() {
+            -y
+        } else {
+            y
+        }
+    }
+
+    #[inline]
+    fn acosh(self) -> Self {
+        let one = Self::one();
+        let two = Self::from_int(2);
+        if self < one {
+            Self::zero()
+        } else if self < two {
+            let d = self - one;
+            (d + (d * (two + d)).sqrt()).ln_1p()
+        } else {
+            let r = self.recip();
+            self.ln() + (one + (one - r * r).sqrt()).ln()
+        }
+    }
+
+    #[inline]
+    fn atanh(self) -> Self {
+        let one = Self::one();
+        let half = one / Self::from_int(2);
+        let x = Signed::abs(&self);
+        let y = if x >= one {
+            <Self as Bounded>::max_value()
+        } else if x < half {
+            half * ((x + x) / (one - x)).ln_1p()
+        } else {
+            half * ((one + x).ln() - (one - x).ln())
+        };
+
+        if self.is_negative() {
+            -y
+        } else {
+            y
+        }
+    }
+
+    #[inline]
+    fn is_finite(&self) -> bool {
+        true
+    }
+}
+
+impl<Frac: LeEqU32 + Send + Sync> RealField for FixedI32<Frac> {
+    #[inline]
+    fn is_sign_positive(&self) -> bool {
+        self.0.is_positive()
+    }
+
+    #[inline]
+    fn is_sign_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+
+    #[inline]
+    fn copysign(self, sign: Self) -> Self {
+        if sign >= Self::zero() {
+            Signed::abs(&self)
+        } else {
+            -Signed::abs(&self)
+        }
+    }
+
+    #[inline]
+    fn max(self, other: Self) -> Self {
+        Ord::max(self, other)
+    }
+
+    #[inline]
+    fn min(self, other: Self) -> Self {
+        Ord::min(self, other)
+    }
+
+    #[inline]
+    fn clamp(self, min: Self, max: Self) -> Self {
+        Ord::clamp(self, min, max)
+    }
+
+    #[inline]
+    fn atan2(self, other: Self) -> Self {
+        let (y, x) = (Signed::abs(&self), Signed::abs(&other));
+
+        if x.is_zero() && y.is_zero() {
+            return Self::zero();
+        }
+
+        // Reduce to the first octant, then unfold into the quadrant of (other, self)
+        let angle = if x >= y {
+            (y / x).atan_unit()
+        } else {
+            Self::frac_pi_2() - (x / y).atan_unit()
+        };
+        let angle = if other.is_negative() {
+            Self::pi() - angle
+        } else {
+            angle
+        };
+
+        if self.is_negative() {
+            -angle
+        } else {
+            angle
+        }
+    }
+
+    #[inline]
+    fn min_value() -> Option<Self> {
+        Some(Bounded::min_value())
+    }
+
+    #[inline]
+    fn max_value() -> Option<Self> {
+        Some(Bounded::max_value())
+    }
+
+    #[inline]
+    fn pi() -> Self {
+        FixedI32::from_num(fixed::consts::PI)
+    }
+
+    #[inline]
+    fn two_pi() -> Self {
+        FixedI32::from_num(fixed::consts::TAU)
+    }
+
+    #[inline]
+    fn frac_pi_2() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_PI_2)
+    }
+
+    #[inline]
+    fn frac_pi_3() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_PI_3)
+    }
+
+    #[inline]
+    fn frac_pi_4() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_PI_4)
+    }
+
+    #[inline]
+    fn frac_pi_6() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_PI_6)
+    }
+
+    #[inline]
+    fn frac_pi_8() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_PI_8)
+    }
+
+    #[inline]
+    fn frac_1_pi() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_1_PI)
+    }
+
+    #[inline]
+    fn frac_2_pi() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_2_PI)
+    }
+
+    #[inline]
+    fn frac_2_sqrt_pi() -> Self {
+        FixedI32::from_num(fixed::consts::FRAC_2_SQRT_PI)
+    }
+
+    #[inline]
+    fn e() -> Self {
+        FixedI32::from_num(fixed::consts::E)
+    }
+
+    #[inline]
+    fn log2_e() -> Self {
+        FixedI32::from_num(fixed::consts::LOG2_E)
+    }
+
+    #[inline]
+    fn log10_e() -> Self {
+        FixedI32::from_num(fixed::consts::LOG10_E)
+    }
+
+    #[inline]
+    fn ln_2() -> Self {
+        FixedI32::from_num(fixed::consts::LN_2)
+    }
+
+    #[inline]
+    fn ln_10() -> Self {
+        FixedI32::from_num(fixed::consts::LN_10)
+    }
+}
@@ -0,0 +1,408 @@
+#![allow(non_snake_case)]
+
+use crate::{
+    ahrs::{
+        heading_error, rotate_heading, Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState,
+        AhrsUncertainty, Diagnostics, UpdateMode,
+    },
+    uncertainty::Residuals,
+};
+use core::hash;
+use nalgebra::{Matrix3, Quaternion, Scalar, UnitQuaternion, Vector2, Vector3};
+use simba::{
+    scalar::RealField,
+    simd::{SimdRealField, SimdValue},
+};
+
+/// Damping factor of the Levenberg-Marquardt correction.
+const LAMBDA: f64 = 1e-5;
+
+/// Fourati nonlinear complementary filter AHRS implementation.
+///
+/// Based on H. Fourati et al., "A Nonlinear Filtering Approach for the Attitude and Dynamic Body
+/// Acceleration Estimation Based on Inertial and Magnetic Sensors: Bio-Logging Application", IEEE
+/// Sensors Journal, 2011. Rather than Madgwick's normalized gradient step, the correction is a
+/// damped Gauss-Newton (Levenberg-Marquardt) step towards the measured gravity and magnetic field
+/// directions, fed back as an angular rate scaled by the gain.
+///
+/// # Example
+/// ```
+/// # use ahrs::Fourati;
+/// let mut ahrs = Fourati::new(0.002390625f64, 0.1);
+/// println!("fourati filter: {:?}", ahrs);
+///
+/// // Can now process IMU data using `Ahrs::update_imu`, etc.
+/// ```
+#[derive(Debug)]
+pub struct Fourati<N: Scalar + SimdValue + Copy> {
+    /// Expected sampling period, in seconds.
+    sample_period: N,
+    /// Filter gain.
+    gain: N,
+    /// Measurement residuals, from which the attitude uncertainty is estimated.
+    residuals: Residuals<N>,
+    /// Diagnostics of the most recent update.
+    diagnostics: Diagnostics<N>,
+    /// Whether the diagnostics measure the accelerometer and magnetometer errors.
+    diagnostics_enabled: bool,
+    /// Filter state quaternion.
+    pub quat: UnitQuaternion<N>,
+}
+
+impl<N: SimdRealField + Eq + Copy> Eq for Fourati<N> where N::Element: SimdRealField + Copy {}
+
+impl<N: SimdRealField + Copy> PartialEq for Fourati<N>
+where
+    N::Element: SimdRealField + Copy,
+{
+    fn eq(&self, rhs: &Self) -> bool {
+        self.sample_period == rhs.sample_period
+            && self.gain == rhs.gain
+            && self.residuals == rhs.residuals
+            && self.quat == rhs.quat
+    }
+}
+
+impl<N: SimdRealField + hash::Hash + Copy> hash::Hash for Fourati<N> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.sample_period.hash(state);
+        self.gain.hash(state);
+        self.residuals.hash(state);
+        self.quat.hash(state);
+    }
+}
+
+impl<N: Scalar + Copy + SimdValue> Copy for Fourati<N> {}
+
+impl<N: Scalar + SimdValue + Copy> Clone for Fourati<N> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Default for Fourati<f64> {
+    /// Creates a new `Fourati` instance with default filter parameters.
+    ///
+    /// ```
+    /// # use ahrs::Fourati;
+    /// dbg!(Fourati::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // Fourati {
+    /// //     sample_period: 1.0f64/256.0,
+    /// //     gain: 0.1f64,
+    /// //     residuals: Residuals { enabled: false, .. },
+    /// //     diagnostics: Diagnostics { .. },
+    /// //     diagnostics_enabled: false,
+    /// //     quat: Quaternion { w: 1.0f64, i: 0.0, j: 0.0, k: 0.0 },
+    /// // };
+    /// ```
+    fn default() -> Fourati<f64> {
+        Fourati {
+            sample_period: (1.0f64) / (256.0),
+            gain: 0.1f64,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat: UnitQuaternion::new_unchecked(Quaternion::new(1.0f64, 0.0, 0.0, 0.0)),
+        }
+    }
+}
+
+impl<N: Scalar + SimdValue + num_traits::One + num_traits::Zero + Copy> Fourati<N> {
+    /// Creates a new `Fourati` AHRS instance with identity quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `gain` - Filter gain.
+    pub fn new(sample_period: N, gain: N) -> Self {
+        Fourati::new_with_quat(
+            sample_period,
+            gain,
+            UnitQuaternion::new_unchecked(Quaternion::new(
+                N::one(),
+                N::zero(),
+                N::zero(),
+                N::zero(),
+            )),
+        )
+    }
+
+    /// Creates a new `Fourati` AHRS instance with given quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `gain` - Filter gain.
+    /// * `quat` - Existing filter state quaternion.
+    pub fn new_with_quat(sample_period: N, gain: N, quat: UnitQuaternion<N>) -> Self {
+        Fourati {
+            sample_period,
+            gain,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat,
+        }
+    }
+}
+
+impl<N: RealField + Copy> Fourati<N> {
+    /// Integrates `gyroscope`, corrected by the Levenberg-Marquardt step which solves the normal
+    /// equations `XtX * delta = Xte` of the linearized measurement model.
+    fn update_corrected(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        XtX: &Matrix3<N>,
+        Xte: &Vector3<N>,
+    ) -> &UnitQuaternion<N> {
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Filter gain K = k (XtX + lambda I)^-1 Xt, applied to the measurement error
+        let Some(inverse) =
+            (XtX + Matrix3::identity() * nalgebra::convert::<_, N>(LAMBDA)).try_inverse()
+        else {
+            let diagnostics = self.diagnostics;
+            self.update_gyro(gyroscope);
+            self.diagnostics = diagnostics.into_fallback();
+            return &self.quat;
+        };
+        let delta = inverse * Xte * self.gain;
+
+        let q = self.quat.as_ref();
+
+        // Compute rate of change for quaternion, with the correction as vector part
+        let qDot = q * Quaternion::from_parts(zero, *gyroscope) * half
+            + q * Quaternion::from_parts(zero, delta);
+
+        // Integrate to yield quaternion
+        self.quat = UnitQuaternion::from_quaternion(q + qDot * self.sample_period);
+
+        &self.quat
+    }
+
+    /// Returns the filter with tracking of the measurement residuals reported by
+    /// `AhrsUncertainty` enabled or disabled.
+    pub fn with_uncertainty(self, enabled: bool) -> Self {
+        Fourati {
+            residuals: Residuals::with_tracking(enabled, self.sample_period),
+            ..self
+        }
+    }
+
+    /// Returns the filter with measurement of the accelerometer and magnetometer errors reported
+    /// by `AhrsDiagnostics` enabled or disabled.
+    pub fn with_diagnostics(self, enabled: bool) -> Self {
+        Fourati {
+            diagnostics_enabled: enabled,
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "field_access")]
+impl<N: Scalar + SimdValue + Copy> Fourati<N> {
+    /// Expected sampling period, in seconds.
+    pub fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    /// Mutable reference to expected sampling period, in seconds.
+    pub fn sample_period_mut(&mut self) -> &mut N {
+        &mut self.sample_period
+    }
+
+    /// Filter gain.
+    pub fn gain(&self) -> N {
+        self.gain
+    }
+
+    /// Mutable reference to filter gain.
+    pub fn gain_mut(&mut self) -> &mut N {
+        &mut self.gain
+    }
+
+    /// Filter state quaternion.
+    pub fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    /// Mutable reference to filter state quaternion.
+    pub fn quat_mut(&mut self) -> &mut UnitQuaternion<N> {
+        &mut self.quat
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsState<N> for Fourati<N> {
+    fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    fn set_sample_period(&mut self, sample_period: N) {
+        self.sample_period = sample_period;
+    }
+
+    fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    fn set_quat(&mut self, quat: UnitQuaternion<N>) {
+        self.quat = quat;
+    }
+}
+
+impl<N: RealField + Copy> AhrsUncertainty<N> for Fourati<N> {
+    fn attitude_std_dev(&self) -> Vector3<N> {
+        self.residuals.std_dev()
+    }
+}
+
+impl<N: RealField + Copy> AhrsReverse<N> for Fourati<N> {
+    fn reversed(&self) -> Self {
+        *self
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsDiagnostics<N> for Fourati<N> {
+    fn diagnostics(&self) -> Diagnostics<N> {
+        self.diagnostics
+    }
+}
+
+impl<N: RealField + Copy> Ahrs<N> for Fourati<N> {
+    fn update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let q = self.quat;
+
+        let zero: N = nalgebra::zero();
+        let two: N = N::one() + N::one();
+
+        // Normalize accelerometer measurement
+        let Some(accel) = accelerometer.try_normalize(zero) else {
+            return Err(AhrsError::AccelerometerNormZero);
+        };
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+
+        // Residuals of the measurements against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, &accel, self.sample_period);
+        self.residuals
+            .update_heading(&self.quat, &mag, self.sample_period);
+
+        // Reference direction of Earth's magnetic field
+        let h = q * mag;
+        let b = Vector3::new(Vector2::new(h[0], h[1]).norm(), zero, h[2]);
+
+        // Estimated directions of gravity and Earth's magnetic field in the body frame
+        let f = q.inverse_transform_vector(&Vector3::z());
+        let m = q.inverse_transform_vector(&b);
+
+        // Jacobian of the estimated directions with respect to the quaternion's vector part
+        let X_a = f.cross_matrix() * two;
+        let X_m = m.cross_matrix() * two;
+
+        let XtX = X_a.transpose() * X_a + X_m.transpose() * X_m;
+        let Xte = X_a.transpose() * (accel - f) + X_m.transpose() * (mag - m);
+
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &q,
+            &accel,
+            Some(&mag),
+            Xte.norm(),
+            UpdateMode::Marg,
+        );
+
+        Ok(self.update_corrected(gyroscope, &XtX, &Xte))
+    }
+
+    fn update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let q = self.quat;
+
+        let zero: N = nalgebra::zero();
+        let two: N = N::one() + N::one();
+
+        // Normalize accelerometer measurement
+        let Some(accel) = accelerometer.try_normalize(zero) else {
+            return Err(AhrsError::AccelerometerNormZero);
+        };
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, &accel, self.sample_period);
+
+        // Estimated direction of gravity in the body frame
+        let f = q.inverse_transform_vector(&Vector3::z());
+
+        // Jacobian of the estimated direction with respect to the quaternion's vector part
+        let X_a = f.cross_matrix() * two;
+
+        let XtX = X_a.transpose() * X_a;
+        let Xte = X_a.transpose() * (accel - f);
+
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &q,
+            &accel,
+            None,
+            Xte.norm(),
+            UpdateMode::Imu,
+        );
+
+        Ok(self.update_corrected(gyroscope, &XtX, &Xte))
+    }
+
+    fn update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N> {
+        self.diagnostics = Diagnostics::default();
+
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Compute rate of change for quaternion
+        let qDot = q * Quaternion::from_parts(zero, *gyroscope) * half;
+
+        // Integrate to yield quaternion
+        self.quat = UnitQuaternion::from_quaternion(q + qDot * self.sample_period);
+
+        &self.quat
+    }
+
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+        let error = heading_error(&self.quat, &mag)?;
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_heading(&self.quat, &mag, self.sample_period);
+        self.diagnostics =
+            Diagnostics::from_heading(self.diagnostics_enabled, &self.quat, &mag, error.abs());
+
+        // Feed the heading error back as an angular rate about the vertical
+        self.quat = rotate_heading(&self.quat, -self.gain * error * self.sample_period);
+
+        Ok(&self.quat)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::algebraic::{heading_quaternion, tilt_quaternion};
+use nalgebra::{UnitQuaternion, Vector3};
+use simba::scalar::RealField;
+
+/// Gyrocompassing, determining the attitude from the rotation rate of the Earth and gravity.
+///
+/// The horizontal component of the Earth's rotation points towards true north, so with a
+/// gyroscope precise enough to sense it, it takes the place of the magnetometer in `aqua`: the
+/// tilt is derived from the accelerometer, and the heading from the levelled gyroscope. Both
+/// measurements should be averaged over a stationary interval, such as by `Gyrocompass`, as the
+/// Earth rate is far below the noise of a single sample.
+///
+/// Returns the quaternion rotating the body frame into the Earth frame, whose x-axis points
+/// towards true north and z-axis points up, or `None` if the accelerometer has zero norm or the
+/// gyroscope has no horizontal component, such as at the poles.
+///
+/// # Example
+/// ```
+/// # use ahrs::gyrocompass;
+/// # use nalgebra::Vector3;
+/// // Level at 45 degrees latitude, facing west
+/// let gyroscope = Vector3::new(0.0, -5.156e-5, 5.156e-5);
+/// let accelerometer = Vector3::new(0.0, 0.0, 9.81);
+///
+/// let quat = gyrocompass(&gyroscope, &accelerometer).unwrap();
+/// let (roll, pitch, yaw) = quat.euler_angles();
+/// assert!((yaw - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+/// ```
+pub fn gyrocompass<N: RealField + Copy>(
+    gyroscope: &Vector3<N>,
+    accelerometer: &Vector3<N>,
+) -> Option<UnitQuaternion<N>> {
+    let accel = accelerometer.try_normalize(nalgebra::zero())?;
+
+    let tilt = tilt_quaternion(&accel);
+    let heading = heading_quaternion(&(tilt * gyroscope))?;
+
+    Some(heading * tilt)
+}
+
+/// Static alignment by gyrocompassing, averaging the measurements of a stationary interval.
+///
+/// # Example
+/// ```
+/// # use ahrs::{Gyrocompass, Madgwick};
+/// # use nalgebra::Vector3;
+/// let mut alignment = Gyrocompass::new();
+///
+/// let gyroscope = Vector3::new(5.156e-5f64, 0.0, 5.156e-5);
+/// let accelerometer = Vector3::new(0.0, 0.0, 9.81);
+/// for _ in 0..1000 {
+///     alignment.update(&gyroscope, &accelerometer);
+/// }
+///
+/// let ahrs = Madgwick::new_with_quat(0.01, 0.1, alignment.quat().unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gyrocompass<N: RealField + Copy> {
+    /// Sum of the gyroscope measurements.
+    gyroscope: Vector3<N>,
+    /// Sum of the accelerometer measurements.
+    accelerometer: Vector3<N>,
+    /// Number of measurements.
+    samples: usize,
+}
+
+impl<N: RealField + Copy> Default for Gyrocompass<N> {
+    fn default() -> Self {
+        Gyrocompass::new()
+    }
+}
+
+impl<N: RealField + Copy> Gyrocompass<N> {
+    /// Creates a new `Gyrocompass` instance without any measurements.
+    pub fn new() -> Self {
+        Gyrocompass {
+            gyroscope: Vector3::zeros(),
+            accelerometer: Vector3::zeros(),
+            samples: 0,
+        }
+    }
+
+    /// Adds a `gyroscope` and `accelerometer` measurement taken while stationary.
+    pub fn update(&mut self, gyroscope: &Vector3<N>, accelerometer: &Vector3<N>) {
+        self.gyroscope += gyroscope;
+        self.accelerometer += accelerometer;
+        self.samples += 1;
+    }
+
+    /// Number of measurements added.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Mean gyroscope and accelerometer measurements, or `None` without any measurements.
+    pub fn mean(&self) -> Option<(Vector3<N>, Vector3<N>)> {
+        if self.samples == 0 {
+            return None;
+        }
+        let samples: N = nalgebra::convert(self.samples as f64);
+
+        Some((self.gyroscope / samples, self.accelerometer / samples))
+    }
+
+    /// Attitude determined by `gyrocompass` from the mean measurements.
+    pub fn quat(&self) -> Option<UnitQuaternion<N>> {
+        let (gyroscope, accelerometer) = self.mean()?;
+        gyrocompass(&gyroscope, &accelerometer)
+    }
+
+    /// Latitude sensed from the elevation of the mean Earth rate above the horizontal, in
+    /// radians, or `None` if the accelerometer has zero norm.
+    pub fn latitude(&self) -> Option<N> {
+        let (gyroscope, accelerometer) = self.mean()?;
+        let up = accelerometer.try_normalize(nalgebra::zero())?;
+
+        let vertical = gyroscope.dot(&up);
+        let horizontal = (gyroscope - up * vertical).norm();
+
+        Some(vertical.atan2(horizontal))
+    }
+}
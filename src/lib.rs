@@ -3,8 +3,56 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![crate_name = "ahrs"]
 
-pub use crate::{ahrs::{Ahrs, AhrsError}, madgwick::Madgwick, mahony::Mahony};
+pub use crate::{
+    ahrs::{
+        Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState, AhrsUncertainty, Diagnostics,
+        SimdAhrs, SimdAhrsStatus, UpdateMode,
+    },
+    algebraic::{aqua, flae, fqa, saam},
+    aqua::Aqua,
+    batch::{AhrsBatch, BatchError, ErrorPolicy, Sample},
+    complementary::Complementary,
+    fourati::Fourati,
+    gyrocompass::{gyrocompass, Gyrocompass},
+    madgwick::Madgwick,
+    mahony::Mahony,
+    multirate::{HoldPolicy, MultiRate},
+    output::{Deadband, OneEuro, OutputFilter, SlerpSmoother},
+    pdr::{Pdr, Step},
+    strapdown::{normal_gravity, Strapdown},
+    ukf::Ukf,
+    units::{Acceleration, AngularRate, MagneticField},
+    vertical::VerticalChannel,
+    vqf::Vqf,
+    zupt::Zupt,
+};
+
+#[cfg(feature = "fixed")]
+pub use crate::fixed_point::{FixedI32, I16F16};
+#[cfg(feature = "std")]
+pub use crate::smoother::smooth;
 
 mod ahrs;
+mod algebraic;
+mod aqua;
+mod batch;
+mod complementary;
+#[cfg(feature = "fixed")]
+mod fixed_point;
+mod fourati;
+mod gyrocompass;
 mod madgwick;
 mod mahony;
+mod multirate;
+mod output;
+mod pdr;
+#[cfg(feature = "std")]
+mod smoother;
+mod strapdown;
+mod ukf;
+mod uncertainty;
+mod units;
+mod util;
+mod vertical;
+mod vqf;
+mod zupt;
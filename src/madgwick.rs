@@ -1,7 +1,15 @@
 #![allow(non_snake_case)]
 #![allow(clippy::many_single_char_names)]
 
-use crate::ahrs::{Ahrs, AhrsError};
+use crate::{
+    ahrs::{
+        angle_between, heading_error, rotate_heading, simd_false, simd_try_normalize, yaw_error,
+        Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState, AhrsUncertainty, Diagnostics,
+        SimdAhrs, SimdAhrsStatus, UpdateMode,
+    },
+    strapdown::earth_rate,
+    uncertainty::Residuals,
+};
 use core::hash;
 use nalgebra::{
     Matrix4, Matrix6, Quaternion, Scalar, UnitQuaternion, Vector2, Vector3, Vector4, Vector6,
@@ -24,6 +32,17 @@ pub struct Madgwick<N: Scalar + SimdValue + Copy> {
     sample_period: N,
     /// Filter gain.
     beta: N,
+    /// Whether the magnetometer correction only affects the heading.
+    decoupled_heading: bool,
+    /// Rotation rate of the Earth in the Earth frame, subtracted from the gyroscope if set.
+    earth_rate: Option<Vector3<N>>,
+    /// Measurement residuals of the scalar updates, from which the attitude uncertainty is
+    /// estimated.
+    residuals: Residuals<N>,
+    /// Diagnostics of the most recent scalar update.
+    diagnostics: Diagnostics<N>,
+    /// Whether the diagnostics measure the accelerometer and magnetometer errors.
+    diagnostics_enabled: bool,
     /// Filter state quaternion.
     pub quat: UnitQuaternion<N>,
 }
@@ -35,7 +54,12 @@ where
     N::Element: SimdRealField + Copy,
 {
     fn eq(&self, rhs: &Self) -> bool {
-        self.sample_period == rhs.sample_period && self.beta == rhs.beta && self.quat == rhs.quat
+        self.sample_period == rhs.sample_period
+            && self.beta == rhs.beta
+            && self.decoupled_heading == rhs.decoupled_heading
+            && self.earth_rate == rhs.earth_rate
+            && self.residuals == rhs.residuals
+            && self.quat == rhs.quat
     }
 }
 
@@ -43,6 +67,9 @@ impl<N: SimdRealField + hash::Hash + Copy> hash::Hash for Madgwick<N> {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
         self.sample_period.hash(state);
         self.beta.hash(state);
+        self.decoupled_heading.hash(state);
+        self.earth_rate.hash(state);
+        self.residuals.hash(state);
         self.quat.hash(state);
     }
 }
@@ -52,15 +79,7 @@ impl<N: Scalar + Copy + SimdValue> Copy for Madgwick<N> {}
 impl<N: Scalar + SimdValue + Copy> Clone for Madgwick<N> {
     #[inline]
     fn clone(&self) -> Self {
-        let sample_period = self.sample_period;
-        let beta = self.beta;
-        let quat = self.quat;
-
-        Madgwick {
-            sample_period,
-            beta,
-            quat,
-        }
+        *self
     }
 }
 
@@ -77,6 +96,11 @@ impl Default for Madgwick<f64> {
     /// // Madgwick {
     /// //     sample_period: 1.0f64/256.0,
     /// //     beta: 0.1f64,
+    /// //     decoupled_heading: false,
+    /// //     earth_rate: None,
+    /// //     residuals: Residuals { enabled: false, .. },
+    /// //     diagnostics: Diagnostics { .. },
+    /// //     diagnostics_enabled: false,
     /// //     quat: Quaternion { w: 1.0f64, i: 0.0, j: 0.0, k: 0.0 },
     /// // };
     /// ```
@@ -84,6 +108,11 @@ impl Default for Madgwick<f64> {
         Madgwick {
             sample_period: (1.0f64) / (256.0),
             beta: 0.1f64,
+            decoupled_heading: false,
+            earth_rate: None,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
             quat: UnitQuaternion::new_unchecked(Quaternion::new(1.0f64, 0.0, 0.0, 0.0)),
         }
     }
@@ -120,9 +149,66 @@ impl<N: Scalar + SimdValue + num_traits::One + num_traits::Zero + Copy> Madgwick
         Madgwick {
             sample_period,
             beta,
+            decoupled_heading: false,
+            earth_rate: None,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
             quat,
         }
     }
+
+    /// Returns the filter with the magnetometer correction projected onto rotations about the
+    /// vertical when `enabled`, so that magnetic disturbances can only affect the heading, and
+    /// never roll and pitch.
+    pub fn with_decoupled_heading(self, enabled: bool) -> Self {
+        Madgwick {
+            decoupled_heading: enabled,
+            ..self
+        }
+    }
+}
+
+impl<N: SimdRealField + Copy> Madgwick<N>
+where
+    N::Element: SimdRealField + Copy,
+{
+    /// Returns the filter with the rotation rate of the Earth at `latitude`, in radians,
+    /// subtracted from the gyroscope before integration. This is only significant with
+    /// high-grade gyroscopes, such as fiber-optic or tactical-grade ones, which can sense it.
+    pub fn with_latitude(self, latitude: N) -> Self {
+        Madgwick {
+            earth_rate: Some(earth_rate(latitude)),
+            ..self
+        }
+    }
+
+    /// Returns the filter with tracking of the measurement residuals reported by
+    /// `AhrsUncertainty` enabled or disabled.
+    pub fn with_uncertainty(self, enabled: bool) -> Self {
+        Madgwick {
+            residuals: Residuals::with_tracking(enabled, self.sample_period),
+            ..self
+        }
+    }
+
+    /// Returns the filter with measurement of the accelerometer and magnetometer errors reported
+    /// by `AhrsDiagnostics` enabled or disabled.
+    pub fn with_diagnostics(self, enabled: bool) -> Self {
+        Madgwick {
+            diagnostics_enabled: enabled,
+            ..self
+        }
+    }
+
+    /// `gyroscope` with the rotation rate of the Earth, rotated into the body frame, subtracted
+    /// if compensated.
+    fn compensate_earth_rate(&self, gyroscope: &Vector3<N>) -> Vector3<N> {
+        match self.earth_rate {
+            Some(earth_rate) => gyroscope - self.quat.inverse_transform_vector(&earth_rate),
+            None => *gyroscope,
+        }
+    }
 }
 
 #[cfg(feature = "field_access")]
@@ -147,6 +233,27 @@ impl<N: Scalar + SimdValue + Copy> Madgwick<N> {
         &mut self.beta
     }
 
+    /// Whether the magnetometer correction only affects the heading.
+    pub fn decoupled_heading(&self) -> bool {
+        self.decoupled_heading
+    }
+
+    /// Mutable reference to whether the magnetometer correction only affects the heading.
+    pub fn decoupled_heading_mut(&mut self) -> &mut bool {
+        &mut self.decoupled_heading
+    }
+
+    /// Rotation rate of the Earth in the Earth frame, subtracted from the gyroscope if set.
+    pub fn earth_rate(&self) -> Option<Vector3<N>> {
+        self.earth_rate
+    }
+
+    /// Mutable reference to rotation rate of the Earth in the Earth frame, subtracted from the
+    /// gyroscope if set.
+    pub fn earth_rate_mut(&mut self) -> &mut Option<Vector3<N>> {
+        &mut self.earth_rate
+    }
+
     /// Filter state quaternion.
     pub fn quat(&self) -> UnitQuaternion<N> {
         self.quat
@@ -158,6 +265,45 @@ impl<N: Scalar + SimdValue + Copy> Madgwick<N> {
     }
 }
 
+impl<N: Scalar + SimdValue + Copy> AhrsState<N> for Madgwick<N> {
+    fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    fn set_sample_period(&mut self, sample_period: N) {
+        self.sample_period = sample_period;
+    }
+
+    fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    fn set_quat(&mut self, quat: UnitQuaternion<N>) {
+        self.quat = quat;
+    }
+}
+
+impl<N: RealField + Copy> AhrsUncertainty<N> for Madgwick<N> {
+    fn attitude_std_dev(&self) -> Vector3<N> {
+        self.residuals.std_dev()
+    }
+}
+
+impl<N: RealField + Copy> AhrsReverse<N> for Madgwick<N> {
+    fn reversed(&self) -> Self {
+        Madgwick {
+            earth_rate: self.earth_rate.map(|earth_rate| -earth_rate),
+            ..*self
+        }
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsDiagnostics<N> for Madgwick<N> {
+    fn diagnostics(&self) -> Diagnostics<N> {
+        self.diagnostics
+    }
+}
+
 impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
     fn update(
         &mut self,
@@ -168,9 +314,9 @@ impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
         let q = self.quat.as_ref();
 
         let zero: N = nalgebra::zero();
-        let two: N = nalgebra::convert(2.0);
-        let four: N = nalgebra::convert(4.0);
-        let half: N = nalgebra::convert(0.5);
+        let two: N = N::one() + N::one();
+        let four: N = two + two;
+        let half: N = N::one() / two;
 
         // Normalize accelerometer measurement
         let Some(accel) = accelerometer.try_normalize(zero) else {
@@ -182,6 +328,10 @@ impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
             return Err(AhrsError::MagnetometerNormZero);
         };
 
+        // Residuals of the measurements against the current estimate
+        self.residuals.update_tilt(&self.quat, &accel, self.sample_period);
+        self.residuals.update_heading(&self.quat, &mag, self.sample_period);
+
         // Reference direction of Earth's magnetic field (Quaternion should still be conj of q)
         let h = q * (Quaternion::from_parts(zero, mag) * q.conjugate());
         let b = Quaternion::new(zero, Vector2::new(h[0], h[1]).norm(), zero, h[2]);
@@ -207,13 +357,26 @@ impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
              zero, zero, zero, zero, zero, zero
         );
 
-        // Try to normalize step, falling back to gyro update if not possible
-        let Some(step) = (J_t * F).try_normalize(zero) else {
-            return Ok(self.update_gyro(gyroscope));
-        };
+        let gradient = gradient(q, &J_t, &F, self.decoupled_heading);
+        let step_norm = gradient.norm();
+        let mut diagnostics = Diagnostics::from_mode(step_norm, UpdateMode::Marg);
+        if self.diagnostics_enabled {
+            diagnostics.accel_error = Some(angle_between(&(F.fixed_rows::<3>(0) + accel), &accel));
+            diagnostics.mag_error = Some(angle_between(&(F.fixed_rows::<3>(3) + mag), &mag));
+        }
+
+        // Normalize step, falling back to gyro update if not possible
+        if step_norm <= zero {
+            self.update_gyro(gyroscope);
+            self.diagnostics = diagnostics.into_fallback();
+            return Ok(&self.quat);
+        }
+        let step = gradient / step_norm;
+        self.diagnostics = diagnostics;
 
         // Compute rate of change for quaternion
-        let qDot = q * Quaternion::from_parts(zero, *gyroscope) * half
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = q * Quaternion::from_parts(zero, gyro) * half
             - Quaternion::new(step[0], step[1], step[2], step[3]) * self.beta;
 
         // Integrate to yield quaternion
@@ -230,15 +393,18 @@ impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
         let q = self.quat.as_ref();
 
         let zero: N = nalgebra::zero();
-        let two: N = nalgebra::convert(2.0);
-        let four: N = nalgebra::convert(4.0);
-        let half: N = nalgebra::convert(0.5);
+        let two: N = N::one() + N::one();
+        let four: N = two + two;
+        let half: N = N::one() / two;
 
         // Normalize accelerometer measurement
         let Some(accel) = accelerometer.try_normalize(zero) else {
             return Err(AhrsError::AccelerometerNormZero);
         };
 
+        // Residual of the measurement against the current estimate
+        self.residuals.update_tilt(&self.quat, &accel, self.sample_period);
+
         // Gradient descent algorithm corrective step
         #[rustfmt::skip]
         let F = Vector4::new(
@@ -256,13 +422,25 @@ impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
              two*q[0], two*q[1],       zero, zero
         );
 
-        // Try to normalize step, falling back to gyro update if not possible
-        let Some(step) = (J_t * F).try_normalize(zero) else {
-            return Ok(self.update_gyro(gyroscope));
-        };
+        let gradient = J_t * F;
+        let step_norm = gradient.norm();
+        let mut diagnostics = Diagnostics::from_mode(step_norm, UpdateMode::Imu);
+        if self.diagnostics_enabled {
+            diagnostics.accel_error = Some(angle_between(&(F.fixed_rows::<3>(0) + accel), &accel));
+        }
+
+        // Normalize step, falling back to gyro update if not possible
+        if step_norm <= zero {
+            self.update_gyro(gyroscope);
+            self.diagnostics = diagnostics.into_fallback();
+            return Ok(&self.quat);
+        }
+        let step = gradient / step_norm;
+        self.diagnostics = diagnostics;
 
         // Compute rate of change of quaternion
-        let qDot = (q * Quaternion::from_parts(zero, *gyroscope)) * half
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = (q * Quaternion::from_parts(zero, gyro)) * half
             - Quaternion::new(step[0], step[1], step[2], step[3]) * self.beta;
 
         // Integrate to yield quaternion
@@ -278,10 +456,207 @@ impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
         let q = self.quat.as_ref();
 
         let zero: N = nalgebra::zero();
-        let half: N = nalgebra::convert(0.5);
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Compute rate of change for quaternion
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = q * Quaternion::from_parts(zero, gyro) * half;
+
+        // Integrate to yield quaternion
+        self.quat = UnitQuaternion::from_quaternion(q + qDot * self.sample_period);
+        self.diagnostics = Diagnostics::default();
+
+        &self.quat
+    }
+
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+        let error = heading_error(&self.quat, &mag)?;
+
+        // Residual of the measurement against the current estimate
+        self.residuals.update_heading(&self.quat, &mag, self.sample_period);
+        self.diagnostics =
+            Diagnostics::from_heading(self.diagnostics_enabled, &self.quat, &mag, error.abs());
+
+        // Step towards magnetic north at the rate of the gradient descent
+        Ok(self.correct_heading(error, N::one()))
+    }
+}
+
+impl<N: RealField + Copy> Madgwick<N> {
+    /// Corrects the heading of the current state quaternion towards an external yaw measurement,
+    /// such as a GNSS course over ground or a dual-antenna compass, leaving roll and pitch
+    /// unchanged. This may be used instead of, or in addition to, `Ahrs::update_mag` where the
+    /// magnetic field is disturbed, such as near steel structures.
+    ///
+    /// Returns a reference to the updated quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `yaw` - Heading of the body's x-axis, in radians, measured counter-clockwise about the
+    ///   Earth frame's z-axis from north as returned by `UnitQuaternion::euler_angles`.
+    /// * `weight` - Non-negative weight of the measurement relative to a magnetometer heading
+    ///   correction, where `0` ignores it and `1` corrects as strongly as `Ahrs::update_mag`.
+    ///   Negative weights are treated as `0`.
+    pub fn update_heading(&mut self, yaw: N, weight: N) -> &UnitQuaternion<N> {
+        let error = yaw_error(&self.quat, yaw);
+        let weight = weight.max(N::zero());
+
+        // Residual of the measurement against the current estimate
+        self.residuals.update_yaw(error, self.sample_period);
+        self.diagnostics = Diagnostics::from_yaw(error.abs());
+
+        self.correct_heading(error, weight)
+    }
+
+    /// Rotates the state quaternion about the vertical to reduce a heading `error`, stepping at
+    /// `weight` times the rate of the gradient descent without overshooting.
+    fn correct_heading(&mut self, error: N, weight: N) -> &UnitQuaternion<N> {
+        let two: N = N::one() + N::one();
+
+        let limit = two * self.beta * self.sample_period * weight;
+        self.quat = rotate_heading(&self.quat, -error.clamp(-limit, limit));
+
+        &self.quat
+    }
+}
+
+impl<N: SimdRealField + Copy> SimdAhrs<N> for Madgwick<N>
+where
+    N::Element: SimdRealField + Copy,
+{
+    fn simd_update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> (&UnitQuaternion<N>, SimdAhrsStatus<N::SimdBool>) {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let two: N = N::one() + N::one();
+        let four: N = two + two;
+        let half: N = N::one() / two;
+
+        // Normalize accelerometer measurement
+        let (accel, accelerometer_norm_zero) = simd_try_normalize(accelerometer);
+
+        // Normalize magnetometer measurement
+        let (mag, magnetometer_norm_zero) = simd_try_normalize(magnetometer);
+
+        // Reference direction of Earth's magnetic field (Quaternion should still be conj of q)
+        let h = q * (Quaternion::from_parts(zero, mag) * q.conjugate());
+        let b = Quaternion::new(zero, Vector2::new(h[0], h[1]).norm(), zero, h[2]);
+
+        // Gradient descent algorithm corrective step
+        #[rustfmt::skip]
+        let F = Vector6::new(
+            two*(       q[0]*q[2] - q[3]*q[1]) - accel[0],
+            two*(       q[3]*q[0] + q[1]*q[2]) - accel[1],
+            two*(half - q[0]*q[0] - q[1]*q[1]) - accel[2],
+            two*b[0]*(half - q[1]*q[1] - q[2]*q[2]) + two*b[2]*(q[0]*q[2] - q[3]*q[1]) - mag[0],
+            two*b[0]*(q[0]*q[1] - q[3]*q[2])        + two*b[2]*(       q[3]*q[0] + q[1]*q[2]) - mag[1],
+            two*b[0]*(q[3]*q[1] + q[0]*q[2])        + two*b[2]*(half - q[0]*q[0] - q[1]*q[1]) - mag[2]
+        );
+
+        #[rustfmt::skip]
+        let J_t = Matrix6::new(
+            -two*q[1], two*q[0],       zero,                -two*b[2]*q[1], -two*b[0]*q[2]+two*b[2]*q[0], two*b[0]*q[1],
+             two*q[2], two*q[3], -four*q[0],                 two*b[2]*q[2],  two*b[0]*q[1]+two*b[2]*q[3], two*b[0]*q[2]-four*b[2]*q[0],
+            -two*q[3], two*q[2], -four*q[1], -four*b[0]*q[1]-two*b[2]*q[3],  two*b[0]*q[0]+two*b[2]*q[2], two*b[0]*q[3]-four*b[2]*q[1],
+             two*q[0], two*q[1],       zero, -four*b[0]*q[2]+two*b[2]*q[0], -two*b[0]*q[3]+two*b[2]*q[1], two*b[0]*q[0],
+             zero, zero, zero, zero, zero, zero,
+             zero, zero, zero, zero, zero, zero
+        );
+
+        // Normalize step, leaving it zero (i.e. a gyro update) in lanes where not possible
+        let (step, _) = simd_try_normalize(&gradient(q, &J_t, &F, self.decoupled_heading));
+
+        // Compute rate of change for quaternion
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = q * Quaternion::from_parts(zero, gyro) * half
+            - Quaternion::new(step[0], step[1], step[2], step[3]) * self.beta;
+
+        // Integrate to yield quaternion, keeping the previous state in lanes which failed
+        let status = SimdAhrsStatus {
+            accelerometer_norm_zero,
+            magnetometer_norm_zero,
+        };
+        self.quat = self.quat.select(
+            status.failed(),
+            UnitQuaternion::from_quaternion(q + qDot * self.sample_period),
+        );
+
+        (&self.quat, status)
+    }
+
+    fn simd_update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> (&UnitQuaternion<N>, SimdAhrsStatus<N::SimdBool>) {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let two: N = N::one() + N::one();
+        let four: N = two + two;
+        let half: N = N::one() / two;
+
+        // Normalize accelerometer measurement
+        let (accel, accelerometer_norm_zero) = simd_try_normalize(accelerometer);
+
+        // Gradient descent algorithm corrective step
+        #[rustfmt::skip]
+        let F = Vector4::new(
+            two*(       q[0]*q[2] - q[3]*q[1]) - accel[0],
+            two*(       q[3]*q[0] + q[1]*q[2]) - accel[1],
+            two*(half - q[0]*q[0] - q[1]*q[1]) - accel[2],
+            zero
+        );
+
+        #[rustfmt::skip]
+        let J_t = Matrix4::new(
+            -two*q[1], two*q[0],       zero, zero,
+             two*q[2], two*q[3], -four*q[0], zero,
+            -two*q[3], two*q[2], -four*q[1], zero,
+             two*q[0], two*q[1],       zero, zero
+        );
+
+        // Normalize step, leaving it zero (i.e. a gyro update) in lanes where not possible
+        let (step, _) = simd_try_normalize(&(J_t * F));
+
+        // Compute rate of change of quaternion
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = (q * Quaternion::from_parts(zero, gyro)) * half
+            - Quaternion::new(step[0], step[1], step[2], step[3]) * self.beta;
+
+        // Integrate to yield quaternion, keeping the previous state in lanes which failed
+        let status = SimdAhrsStatus {
+            accelerometer_norm_zero,
+            magnetometer_norm_zero: simd_false::<N>(),
+        };
+        self.quat = self.quat.select(
+            status.failed(),
+            UnitQuaternion::from_quaternion(q + qDot * self.sample_period),
+        );
+
+        (&self.quat, status)
+    }
+
+    fn simd_update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N> {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
 
         // Compute rate of change for quaternion
-        let qDot = q * Quaternion::from_parts(zero, *gyroscope) * half;
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = q * Quaternion::from_parts(zero, gyro) * half;
 
         // Integrate to yield quaternion
         self.quat = UnitQuaternion::from_quaternion(q + qDot * self.sample_period);
@@ -289,3 +664,36 @@ impl<N: RealField + Copy> Ahrs<N> for Madgwick<N> {
         &self.quat
     }
 }
+
+/// Gradient of the objective function, given its transposed Jacobian `J_t` and the accelerometer
+/// and magnetometer residuals `F` at `q`. If `decoupled_heading`, the magnetometer part is projected
+/// onto rotations about the vertical.
+fn gradient<N: SimdRealField + Copy>(
+    q: &Quaternion<N>,
+    J_t: &Matrix6<N>,
+    F: &Vector6<N>,
+    decoupled_heading: bool,
+) -> Vector6<N>
+where
+    N::Element: SimdRealField + Copy,
+{
+    if !decoupled_heading {
+        return J_t * F;
+    }
+
+    let zero: N = nalgebra::zero();
+
+    let accel_gradient = J_t.fixed_columns::<3>(0) * F.fixed_rows::<3>(0);
+    let g = J_t.fixed_columns::<3>(3) * F.fixed_rows::<3>(3);
+    let mag_gradient = Quaternion::new(g[0], g[1], g[2], g[3]);
+
+    // Estimated direction of gravity in the body frame
+    let up = (q.conjugate() * Quaternion::from_parts(zero, Vector3::z()) * q).imag();
+
+    // Magnetometer gradient as a rotation in the body frame, keeping only its component about the
+    // vertical
+    let rotation = (q.conjugate() * mag_gradient).imag();
+    let g = q * Quaternion::from_parts(zero, up * up.dot(&rotation));
+
+    accel_gradient + Vector6::new(g.w, g.i, g.j, g.k, zero, zero)
+}
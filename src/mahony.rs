@@ -1,7 +1,15 @@
 #![allow(non_snake_case)]
 #![allow(clippy::many_single_char_names)]
 
-use crate::ahrs::{Ahrs, AhrsError};
+use crate::{
+    ahrs::{
+        angle_between, heading_error, rotate_heading, simd_false, simd_try_normalize, yaw_error,
+        Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState, AhrsUncertainty, Diagnostics,
+        SimdAhrs, SimdAhrsStatus, UpdateMode,
+    },
+    strapdown::earth_rate,
+    uncertainty::Residuals,
+};
 use core::hash;
 use nalgebra::{Quaternion, Scalar, UnitQuaternion, Vector2, Vector3};
 use simba::simd::{SimdRealField as RealField, SimdRealField, SimdValue};
@@ -26,6 +34,17 @@ pub struct Mahony<N: Scalar + SimdValue + Copy> {
     ki: N,
     /// Integral error vector.
     e_int: Vector3<N>,
+    /// Whether the magnetometer correction only affects the heading.
+    decoupled_heading: bool,
+    /// Rotation rate of the Earth in the Earth frame, subtracted from the gyroscope if set.
+    earth_rate: Option<Vector3<N>>,
+    /// Measurement residuals of the scalar updates, from which the attitude uncertainty is
+    /// estimated.
+    residuals: Residuals<N>,
+    /// Diagnostics of the most recent scalar update.
+    diagnostics: Diagnostics<N>,
+    /// Whether the diagnostics measure the accelerometer and magnetometer errors.
+    diagnostics_enabled: bool,
     /// Filter state quaternion.
     pub quat: UnitQuaternion<N>,
 }
@@ -41,6 +60,9 @@ where
             && self.kp == rhs.kp
             && self.ki == rhs.ki
             && self.e_int == rhs.e_int
+            && self.decoupled_heading == rhs.decoupled_heading
+            && self.earth_rate == rhs.earth_rate
+            && self.residuals == rhs.residuals
             && self.quat == rhs.quat
     }
 }
@@ -51,6 +73,9 @@ impl<N: SimdRealField + hash::Hash + Copy> hash::Hash for Mahony<N> {
         self.kp.hash(state);
         self.ki.hash(state);
         self.e_int.hash(state);
+        self.decoupled_heading.hash(state);
+        self.earth_rate.hash(state);
+        self.residuals.hash(state);
         self.quat.hash(state);
     }
 }
@@ -60,19 +85,7 @@ impl<N: Scalar + Copy + SimdValue> Copy for Mahony<N> {}
 impl<N: Scalar + SimdValue + Copy> Clone for Mahony<N> {
     #[inline]
     fn clone(&self) -> Self {
-        let sample_period = self.sample_period;
-        let kp = self.kp;
-        let ki = self.ki;
-        let e_int = self.e_int;
-        let quat = self.quat;
-
-        Mahony {
-            sample_period,
-            kp,
-            ki,
-            e_int,
-            quat,
-        }
+        *self
     }
 }
 
@@ -91,6 +104,11 @@ impl Default for Mahony<f64> {
     /// //     kp: 0.5f64,
     /// //     ki: 0.0f64,
     /// //     e_int: Vector3 { x: 0.0f64, y: 0.0, z: 0.0 },
+    /// //     decoupled_heading: false,
+    /// //     earth_rate: None,
+    /// //     residuals: Residuals { enabled: false, .. },
+    /// //     diagnostics: Diagnostics { .. },
+    /// //     diagnostics_enabled: false,
     /// //     quat: Quaternion { w: 1.0f64, i: 0.0, j: 0.0, k: 0.0 }
     /// // };
     /// ```
@@ -100,6 +118,11 @@ impl Default for Mahony<f64> {
             kp: 0.5f64,
             ki: 0.0f64,
             e_int: Vector3::new(0.0, 0.0, 0.0),
+            decoupled_heading: false,
+            earth_rate: None,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
             quat: UnitQuaternion::new_unchecked(Quaternion::new(1.0f64, 0.0, 0.0, 0.0)),
         }
     }
@@ -141,9 +164,51 @@ impl<N: RealField + Copy> Mahony<N> {
             kp,
             ki,
             e_int: nalgebra::zero(),
+            decoupled_heading: false,
+            earth_rate: None,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
             quat,
         }
     }
+
+    /// Returns the filter with the magnetometer error projected onto the vertical when `enabled`,
+    /// so that magnetic disturbances can only affect the heading, and never roll and pitch.
+    pub fn with_decoupled_heading(self, enabled: bool) -> Self {
+        Mahony {
+            decoupled_heading: enabled,
+            ..self
+        }
+    }
+
+    /// Returns the filter with the rotation rate of the Earth at `latitude`, in radians,
+    /// subtracted from the gyroscope before integration. This is only significant with
+    /// high-grade gyroscopes, such as fiber-optic or tactical-grade ones, which can sense it.
+    pub fn with_latitude(self, latitude: N) -> Self {
+        Mahony {
+            earth_rate: Some(earth_rate(latitude)),
+            ..self
+        }
+    }
+
+    /// Returns the filter with tracking of the measurement residuals reported by
+    /// `AhrsUncertainty` enabled or disabled.
+    pub fn with_uncertainty(self, enabled: bool) -> Self {
+        Mahony {
+            residuals: Residuals::with_tracking(enabled, self.sample_period),
+            ..self
+        }
+    }
+
+    /// Returns the filter with measurement of the accelerometer and magnetometer errors reported
+    /// by `AhrsDiagnostics` enabled or disabled.
+    pub fn with_diagnostics(self, enabled: bool) -> Self {
+        Mahony {
+            diagnostics_enabled: enabled,
+            ..self
+        }
+    }
 }
 
 #[cfg(feature = "field_access")]
@@ -188,6 +253,27 @@ impl<N: Scalar + SimdValue + Copy> Mahony<N> {
         &mut self.e_int
     }
 
+    /// Whether the magnetometer correction only affects the heading.
+    pub fn decoupled_heading(&self) -> bool {
+        self.decoupled_heading
+    }
+
+    /// Mutable reference to whether the magnetometer correction only affects the heading.
+    pub fn decoupled_heading_mut(&mut self) -> &mut bool {
+        &mut self.decoupled_heading
+    }
+
+    /// Rotation rate of the Earth in the Earth frame, subtracted from the gyroscope if set.
+    pub fn earth_rate(&self) -> Option<Vector3<N>> {
+        self.earth_rate
+    }
+
+    /// Mutable reference to rotation rate of the Earth in the Earth frame, subtracted from the
+    /// gyroscope if set.
+    pub fn earth_rate_mut(&mut self) -> &mut Option<Vector3<N>> {
+        &mut self.earth_rate
+    }
+
     /// Filter state quaternion.
     pub fn quat(&self) -> UnitQuaternion<N> {
         self.quat
@@ -199,6 +285,46 @@ impl<N: Scalar + SimdValue + Copy> Mahony<N> {
     }
 }
 
+impl<N: Scalar + SimdValue + Copy> AhrsState<N> for Mahony<N> {
+    fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    fn set_sample_period(&mut self, sample_period: N) {
+        self.sample_period = sample_period;
+    }
+
+    fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    fn set_quat(&mut self, quat: UnitQuaternion<N>) {
+        self.quat = quat;
+    }
+}
+
+impl<N: simba::scalar::RealField + Copy> AhrsUncertainty<N> for Mahony<N> {
+    fn attitude_std_dev(&self) -> Vector3<N> {
+        self.residuals.std_dev()
+    }
+}
+
+impl<N: simba::scalar::RealField + Copy> AhrsReverse<N> for Mahony<N> {
+    fn reversed(&self) -> Self {
+        Mahony {
+            e_int: -self.e_int,
+            earth_rate: self.earth_rate.map(|earth_rate| -earth_rate),
+            ..*self
+        }
+    }
+}
+
+impl<N: Scalar + SimdValue + Copy> AhrsDiagnostics<N> for Mahony<N> {
+    fn diagnostics(&self) -> Diagnostics<N> {
+        self.diagnostics
+    }
+}
+
 impl<N: simba::scalar::RealField + Copy> Ahrs<N> for Mahony<N> {
     fn update(
         &mut self,
@@ -209,8 +335,8 @@ impl<N: simba::scalar::RealField + Copy> Ahrs<N> for Mahony<N> {
         let q = self.quat.as_ref();
 
         let zero: N = nalgebra::zero();
-        let two: N = nalgebra::convert(2.0);
-        let half: N = nalgebra::convert(0.5);
+        let two: N = N::one() + N::one();
+        let half: N = N::one() / two;
 
         // Normalize accelerometer measurement
         let Some(accel) = accelerometer.try_normalize(zero) else {
@@ -222,6 +348,10 @@ impl<N: simba::scalar::RealField + Copy> Ahrs<N> for Mahony<N> {
             return Err(AhrsError::MagnetometerNormZero);
         };
 
+        // Residuals of the measurements against the current estimate
+        self.residuals.update_tilt(&self.quat, &accel, self.sample_period);
+        self.residuals.update_heading(&self.quat, &mag, self.sample_period);
+
         // Reference direction of Earth's magnetic field (Quaternion should still be conj of q)
         let h = q * (Quaternion::from_parts(zero, mag) * q.conjugate());
         let b = Quaternion::new(zero, Vector2::new(h[0], h[1]).norm(), zero, h[2]);
@@ -241,13 +371,19 @@ impl<N: simba::scalar::RealField + Copy> Ahrs<N> for Mahony<N> {
         );
 
         // Error is sum of cross product between estimated direction and measured direction of fields
-        let e: Vector3<N> = accel.cross(&v) + mag.cross(&w);
+        let e: Vector3<N> = accel.cross(&v) + self.mag_feedback(&v, &mag.cross(&w));
+
+        self.diagnostics = Diagnostics::from_mode(e.norm(), UpdateMode::Marg);
+        if self.diagnostics_enabled {
+            self.diagnostics.accel_error = Some(angle_between(&v, &accel));
+            self.diagnostics.mag_error = Some(angle_between(&w, &mag));
+        }
 
         // Integrate error
         self.e_int += e * self.sample_period;
 
         // Apply feedback terms
-        let gyro = *gyroscope + e * self.kp + self.e_int * self.ki;
+        let gyro = self.compensate_earth_rate(gyroscope) + e * self.kp + self.e_int * self.ki;
 
         // Compute rate of change of quaternion
         let qDot = q * Quaternion::from_parts(zero, gyro) * half;
@@ -266,14 +402,17 @@ impl<N: simba::scalar::RealField + Copy> Ahrs<N> for Mahony<N> {
         let q = self.quat.as_ref();
 
         let zero: N = nalgebra::zero();
-        let two: N = nalgebra::convert(2.0);
-        let half: N = nalgebra::convert(0.5);
+        let two: N = N::one() + N::one();
+        let half: N = N::one() / two;
 
         // Normalize accelerometer measurement
         let Some(accel) = accelerometer.try_normalize(zero) else {
             return Err(AhrsError::AccelerometerNormZero);
         };
 
+        // Residual of the measurement against the current estimate
+        self.residuals.update_tilt(&self.quat, &accel, self.sample_period);
+
         #[rustfmt::skip]
         let v = Vector3::new(
             two*( q[0]*q[2] - q[3]*q[1] ),
@@ -284,11 +423,16 @@ impl<N: simba::scalar::RealField + Copy> Ahrs<N> for Mahony<N> {
         // Error is estimated direction direction of fields
         let e = accel.cross(&v);
 
+        self.diagnostics = Diagnostics::from_mode(e.norm(), UpdateMode::Imu);
+        if self.diagnostics_enabled {
+            self.diagnostics.accel_error = Some(angle_between(&v, &accel));
+        }
+
         // Integrate error
         self.e_int += e * self.sample_period;
 
         // Apply feedback terms
-        let gyro = *gyroscope + e * self.kp + self.e_int * self.ki;
+        let gyro = self.compensate_earth_rate(gyroscope) + e * self.kp + self.e_int * self.ki;
 
         // Compute rate of change of quaternion
         let qDot = q * Quaternion::from_parts(zero, gyro) * half;
@@ -306,14 +450,219 @@ impl<N: simba::scalar::RealField + Copy> Ahrs<N> for Mahony<N> {
         let q = self.quat.as_ref();
 
         let zero: N = nalgebra::zero();
-        let half: N = nalgebra::convert(0.5);
+        let half: N = N::one() / (N::one() + N::one());
 
         // Compute rate of change for quaternion
-        let qDot = q * Quaternion::from_parts(zero, *gyroscope) * half;
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = q * Quaternion::from_parts(zero, gyro) * half;
 
         // Integrate to yield quaternion
         self.quat = UnitQuaternion::from_quaternion(q + qDot * self.sample_period);
+        self.diagnostics = Diagnostics::default();
 
         &self.quat
     }
+
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+        let error = heading_error(&self.quat, &mag)?;
+
+        // Residual of the measurement against the current estimate
+        self.residuals.update_heading(&self.quat, &mag, self.sample_period);
+        self.diagnostics =
+            Diagnostics::from_heading(self.diagnostics_enabled, &self.quat, &mag, error.abs());
+
+        // Proportional feedback of the cross product of the measured and estimated horizontal
+        // field directions
+        Ok(self.correct_heading(error, N::one()))
+    }
+}
+
+impl<N: simba::scalar::RealField + Copy> Mahony<N> {
+    /// Corrects the heading of the current state quaternion towards an external yaw measurement,
+    /// such as a GNSS course over ground or a dual-antenna compass, leaving roll and pitch
+    /// unchanged. This may be used instead of, or in addition to, `Ahrs::update_mag` where the
+    /// magnetic field is disturbed, such as near steel structures.
+    ///
+    /// Returns a reference to the updated quaternion.
+    ///
+    /// # Arguments
+    ///
+    /// * `yaw` - Heading of the body's x-axis, in radians, measured counter-clockwise about the
+    ///   Earth frame's z-axis from north as returned by `UnitQuaternion::euler_angles`.
+    /// * `weight` - Non-negative weight of the measurement relative to a magnetometer heading
+    ///   correction, where `0` ignores it and `1` corrects as strongly as `Ahrs::update_mag`.
+    ///   Negative weights are treated as `0`.
+    pub fn update_heading(&mut self, yaw: N, weight: N) -> &UnitQuaternion<N> {
+        let error = yaw_error(&self.quat, yaw);
+        let weight = weight.max(N::zero());
+
+        // Residual of the measurement against the current estimate
+        self.residuals.update_yaw(error, self.sample_period);
+        self.diagnostics = Diagnostics::from_yaw(error.abs());
+
+        self.correct_heading(error, weight)
+    }
+
+    /// Rotates the state quaternion about the vertical to reduce a heading `error`, with
+    /// proportional feedback of the sine of the error scaled by `weight`.
+    fn correct_heading(&mut self, error: N, weight: N) -> &UnitQuaternion<N> {
+        let gain = self.kp * weight * self.sample_period;
+        self.quat = rotate_heading(&self.quat, -gain * error.sin());
+
+        &self.quat
+    }
+}
+
+impl<N: SimdRealField + Copy> SimdAhrs<N> for Mahony<N>
+where
+    N::Element: SimdRealField,
+{
+    fn simd_update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> (&UnitQuaternion<N>, SimdAhrsStatus<N::SimdBool>) {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let two: N = N::one() + N::one();
+        let half: N = N::one() / two;
+
+        // Normalize accelerometer measurement
+        let (accel, accelerometer_norm_zero) = simd_try_normalize(accelerometer);
+
+        // Normalize magnetometer measurement
+        let (mag, magnetometer_norm_zero) = simd_try_normalize(magnetometer);
+
+        // Reference direction of Earth's magnetic field (Quaternion should still be conj of q)
+        let h = q * (Quaternion::from_parts(zero, mag) * q.conjugate());
+        let b = Quaternion::new(zero, Vector2::new(h[0], h[1]).norm(), zero, h[2]);
+
+        #[rustfmt::skip]
+        let v = Vector3::new(
+            two*( q[0]*q[2] - q[3]*q[1] ),
+            two*( q[3]*q[0] + q[1]*q[2] ),
+            q[3]*q[3] - q[0]*q[0] - q[1]*q[1] + q[2]*q[2]
+        );
+
+        #[rustfmt::skip]
+        let w = Vector3::new(
+            two*b[0]*(half - q[1]*q[1] - q[2]*q[2]) + two*b[2]*(q[0]*q[2] - q[3]*q[1]),
+            two*b[0]*(q[0]*q[1] - q[3]*q[2])        + two*b[2]*(q[3]*q[0] + q[1]*q[2]),
+            two*b[0]*(q[3]*q[1] + q[0]*q[2])        + two*b[2]*(half - q[0]*q[0] - q[1]*q[1])
+        );
+
+        // Error is sum of cross product between estimated direction and measured direction of fields
+        let e: Vector3<N> = accel.cross(&v) + self.mag_feedback(&v, &mag.cross(&w));
+
+        let status = SimdAhrsStatus {
+            accelerometer_norm_zero,
+            magnetometer_norm_zero,
+        };
+        self.simd_apply_feedback(gyroscope, &e, status.failed());
+
+        (&self.quat, status)
+    }
+
+    fn simd_update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> (&UnitQuaternion<N>, SimdAhrsStatus<N::SimdBool>) {
+        let q = self.quat.as_ref();
+
+        let two: N = N::one() + N::one();
+
+        // Normalize accelerometer measurement
+        let (accel, accelerometer_norm_zero) = simd_try_normalize(accelerometer);
+
+        #[rustfmt::skip]
+        let v = Vector3::new(
+            two*( q[0]*q[2] - q[3]*q[1] ),
+            two*( q[3]*q[0] + q[1]*q[2] ),
+            q[3]*q[3] - q[0]*q[0] - q[1]*q[1] + q[2]*q[2]
+        );
+
+        // Error is estimated direction direction of fields
+        let e = accel.cross(&v);
+
+        let status = SimdAhrsStatus {
+            accelerometer_norm_zero,
+            magnetometer_norm_zero: simd_false::<N>(),
+        };
+        self.simd_apply_feedback(gyroscope, &e, status.failed());
+
+        (&self.quat, status)
+    }
+
+    fn simd_update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N> {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Compute rate of change for quaternion
+        let gyro = self.compensate_earth_rate(gyroscope);
+        let qDot = q * Quaternion::from_parts(zero, gyro) * half;
+
+        // Integrate to yield quaternion
+        self.quat = UnitQuaternion::from_quaternion(q + qDot * self.sample_period);
+
+        &self.quat
+    }
+}
+
+impl<N: SimdRealField + Copy> Mahony<N>
+where
+    N::Element: SimdRealField,
+{
+    /// `gyroscope` with the rotation rate of the Earth, rotated into the body frame, subtracted
+    /// if compensated.
+    fn compensate_earth_rate(&self, gyroscope: &Vector3<N>) -> Vector3<N> {
+        match self.earth_rate {
+            Some(earth_rate) => gyroscope - self.quat.inverse_transform_vector(&earth_rate),
+            None => *gyroscope,
+        }
+    }
+
+    /// Magnetometer error `e_mag` fed back, projected onto the estimated direction of gravity `v`
+    /// if the heading is decoupled.
+    fn mag_feedback(&self, v: &Vector3<N>, e_mag: &Vector3<N>) -> Vector3<N> {
+        if self.decoupled_heading {
+            v * v.dot(e_mag)
+        } else {
+            *e_mag
+        }
+    }
+
+    /// Applies the feedback terms of error `e` and integrates the result, leaving the lanes set in
+    /// `failed` untouched.
+    fn simd_apply_feedback(&mut self, gyroscope: &Vector3<N>, e: &Vector3<N>, failed: N::SimdBool) {
+        let q = self.quat.as_ref();
+
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Integrate error
+        let e_int = self.e_int + e * self.sample_period;
+
+        // Apply feedback terms
+        let gyro = self.compensate_earth_rate(gyroscope) + e * self.kp + e_int * self.ki;
+
+        // Compute rate of change of quaternion
+        let qDot = q * Quaternion::from_parts(zero, gyro) * half;
+
+        // Integrate to yield quaternion
+        let quat = UnitQuaternion::from_quaternion(q + qDot * self.sample_period);
+
+        self.e_int = self.e_int.select(failed, e_int);
+        self.quat = self.quat.select(failed, quat);
+    }
 }
@@ -0,0 +1,359 @@
+use crate::{
+    ahrs::{Ahrs, AhrsError, AhrsState},
+    batch::{AhrsBatch, ErrorPolicy, Sample},
+};
+use nalgebra::{UnitQuaternion, Vector3};
+use simba::scalar::RealField;
+
+/// Number of measurements buffered for each sensor, and of gyroscope samples awaiting
+/// interpolation.
+const CAPACITY: usize = 32;
+
+/// Describes which accelerometer or magnetometer measurement a multi-rate update corrects with,
+/// given the timestamp of the gyroscope sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HoldPolicy {
+    /// Use the most recent measurement taken no later than the gyroscope sample, holding it until
+    /// the next one is taken.
+    SampleAndHold,
+    /// Linearly interpolate between the measurements taken immediately before and after the
+    /// gyroscope sample. Gyroscope samples are delayed until every sensor has a measurement at or
+    /// after them, and otherwise hold the most recent measurement once they are older than the
+    /// maximum age, or once the buffer of delayed samples is full.
+    Interpolate,
+}
+
+/// Scheduler fusing a gyroscope, accelerometer and magnetometer which are sampled at different
+/// rates, wrapping any AHRS filter.
+///
+/// Accelerometer and magnetometer measurements are only stored when they arrive. Each gyroscope
+/// sample then propagates the filter, setting its sampling period from the gyroscope timestamps,
+/// and corrects it with the measurements selected by the `HoldPolicy`: a full update once both
+/// have arrived, an IMU update with only the accelerometer, a heading correction with
+/// `Ahrs::update_mag` with only the magnetometer, and a gyroscope-only update with neither.
+/// Measurements older than the maximum age set by `with_max_age` are no longer used, and gyroscope
+/// samples whose timestamp does not increase are handled according to the `ErrorPolicy`.
+///
+/// Measurements may be pushed ahead of the gyroscope, such as when reading sensor FIFOs in
+/// bursts, in which case up to 32 are buffered for each sensor, dropping the oldest.
+///
+/// # Example
+/// ```
+/// # use ahrs::{HoldPolicy, Madgwick, MultiRate};
+/// # use nalgebra::Vector3;
+/// let mut fusion = MultiRate::new(Madgwick::new(0.001f64, 0.1), HoldPolicy::SampleAndHold)
+///     .with_max_age(0.1);
+///
+/// fusion.push_accelerometer(0.0, Vector3::new(0.0, 0.0, 9.81));
+/// fusion.push_magnetometer(0.0, Vector3::new(20.0, 0.0, -40.0));
+///
+/// let quat = fusion
+///     .push_gyroscope(0.001, Vector3::new(0.01, 0.02, 0.03))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MultiRate<N: RealField + Copy, A> {
+    /// Wrapped AHRS filter.
+    ahrs: A,
+    /// Measurement used to correct each gyroscope sample.
+    policy: HoldPolicy,
+    /// Handling of updates which fail.
+    error_policy: ErrorPolicy,
+    /// Age beyond which a measurement is no longer used, in seconds, if any.
+    max_age: Option<N>,
+    /// Timestamp of the last gyroscope sample processed, in seconds.
+    gyroscope_timestamp: Option<N>,
+    /// Gyroscope samples awaiting the measurements to interpolate.
+    gyroscope: Timeline<N>,
+    /// Recent accelerometer measurements.
+    accelerometer: Timeline<N>,
+    /// Recent magnetometer measurements.
+    magnetometer: Timeline<N>,
+}
+
+impl<N: RealField + Copy, A: Ahrs<N> + AhrsState<N>> MultiRate<N, A> {
+    /// Creates a new `MultiRate` scheduler wrapping `ahrs`, falling back to an IMU or
+    /// gyroscope-only update when a measurement fails to update the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `ahrs` - AHRS filter to update, whose sampling period is used until two gyroscope
+    ///   samples have arrived.
+    /// * `policy` - Measurement used to correct each gyroscope sample.
+    pub fn new(ahrs: A, policy: HoldPolicy) -> Self {
+        MultiRate {
+            ahrs,
+            policy,
+            error_policy: ErrorPolicy::Fallback,
+            max_age: None,
+            gyroscope_timestamp: None,
+            gyroscope: Timeline::default(),
+            accelerometer: Timeline::default(),
+            magnetometer: Timeline::default(),
+        }
+    }
+
+    /// Returns the scheduler with updates which fail handled according to `error_policy`.
+    pub fn with_error_policy(self, error_policy: ErrorPolicy) -> Self {
+        MultiRate {
+            error_policy,
+            ..self
+        }
+    }
+
+    /// Returns the scheduler with measurements taken more than `max_age` seconds from a
+    /// gyroscope sample no longer used to correct it, so that a sensor which stops reporting is
+    /// dropped from the update rather than held forever.
+    pub fn with_max_age(self, max_age: N) -> Self {
+        MultiRate {
+            max_age: Some(max_age),
+            ..self
+        }
+    }
+
+    /// Wrapped AHRS filter.
+    pub fn ahrs(&self) -> &A {
+        &self.ahrs
+    }
+
+    /// Mutable reference to wrapped AHRS filter.
+    pub fn ahrs_mut(&mut self) -> &mut A {
+        &mut self.ahrs
+    }
+
+    /// Consumes the scheduler, returning the wrapped AHRS filter.
+    ///
+    /// Gyroscope samples still delayed by `HoldPolicy::Interpolate` are discarded, unless
+    /// processed with `flush` first.
+    pub fn into_inner(self) -> A {
+        self.ahrs
+    }
+
+    /// Stores an accelerometer measurement taken at `timestamp`, in seconds, to correct
+    /// subsequent gyroscope samples.
+    pub fn push_accelerometer(&mut self, timestamp: N, accelerometer: Vector3<N>) {
+        self.accelerometer.push(timestamp, accelerometer);
+    }
+
+    /// Stores a magnetometer measurement taken at `timestamp`, in seconds, to correct subsequent
+    /// gyroscope samples.
+    pub fn push_magnetometer(&mut self, timestamp: N, magnetometer: Vector3<N>) {
+        self.magnetometer.push(timestamp, magnetometer);
+    }
+
+    /// Propagates the filter with a gyroscope measurement taken at `timestamp`, in seconds,
+    /// correcting it with the stored measurements.
+    ///
+    /// Under `HoldPolicy::Interpolate`, the sample is only processed once it is ready, along with
+    /// any earlier delayed samples.
+    ///
+    /// Returns the quaternion of the most recently processed gyroscope sample on success, or the
+    /// `AhrsError` which caused an update to fail under `ErrorPolicy::Abort`.
+    pub fn push_gyroscope(
+        &mut self,
+        timestamp: N,
+        gyroscope: Vector3<N>,
+    ) -> Result<UnitQuaternion<N>, AhrsError> {
+        if self.policy == HoldPolicy::SampleAndHold {
+            return self.update(timestamp, gyroscope);
+        }
+
+        if self.gyroscope.is_full() {
+            self.process_delayed()?;
+        }
+        self.gyroscope.push(timestamp, gyroscope);
+
+        while let Some((delayed, _)) = self.gyroscope.first() {
+            if !self.is_ready(delayed, timestamp) {
+                break;
+            }
+            self.process_delayed()?;
+        }
+
+        Ok(self.ahrs.quat())
+    }
+
+    /// Processes every gyroscope sample delayed by `HoldPolicy::Interpolate`, holding the most
+    /// recent measurements for those which are not yet followed by one.
+    ///
+    /// Returns the quaternion of the most recently processed gyroscope sample on success, or the
+    /// `AhrsError` which caused an update to fail under `ErrorPolicy::Abort`.
+    pub fn flush(&mut self) -> Result<UnitQuaternion<N>, AhrsError> {
+        while !self.gyroscope.is_empty() {
+            self.process_delayed()?;
+        }
+
+        Ok(self.ahrs.quat())
+    }
+
+    /// Whether the delayed gyroscope sample at `timestamp` can be processed, given the timestamp
+    /// of the latest gyroscope sample.
+    fn is_ready(&self, timestamp: N, latest: N) -> bool {
+        let measured = |timeline: &Timeline<N>| match timeline.last() {
+            Some((last, _)) => last >= timestamp,
+            None => true,
+        };
+
+        (measured(&self.accelerometer) && measured(&self.magnetometer))
+            || matches!(self.max_age, Some(max_age) if latest - timestamp > max_age)
+    }
+
+    /// Processes the oldest delayed gyroscope sample.
+    fn process_delayed(&mut self) -> Result<UnitQuaternion<N>, AhrsError> {
+        match self.gyroscope.pop_first() {
+            Some((timestamp, gyroscope)) => self.update(timestamp, gyroscope),
+            None => Ok(self.ahrs.quat()),
+        }
+    }
+
+    /// Updates the filter with a gyroscope sample at `timestamp`, corrected by the measurements
+    /// selected at that time. A timestamp which does not increase is handled according to the
+    /// error policy, falling back to the previous sampling period.
+    fn update(
+        &mut self,
+        timestamp: N,
+        gyroscope: Vector3<N>,
+    ) -> Result<UnitQuaternion<N>, AhrsError> {
+        match self.gyroscope_timestamp {
+            Some(last) if timestamp <= last => match self.error_policy {
+                ErrorPolicy::Fallback => {}
+                ErrorPolicy::Skip => return Ok(self.ahrs.quat()),
+                ErrorPolicy::Abort => return Err(AhrsError::TimestampNotIncreasing),
+            },
+            last => {
+                if let Some(last) = last {
+                    self.ahrs.set_sample_period(timestamp - last);
+                }
+                self.gyroscope_timestamp = Some(timestamp);
+            }
+        }
+
+        let accelerometer = self.accelerometer.at(timestamp, self.policy, self.max_age);
+        let magnetometer = self.magnetometer.at(timestamp, self.policy, self.max_age);
+        self.accelerometer.discard_before(timestamp);
+        self.magnetometer.discard_before(timestamp);
+
+        match (accelerometer, magnetometer) {
+            (Some(accelerometer), magnetometer) => {
+                let sample = Sample {
+                    gyroscope,
+                    accelerometer,
+                    magnetometer,
+                    timestamp: Some(timestamp),
+                };
+                self.ahrs.update_sample(&sample, self.error_policy)
+            }
+            (None, Some(magnetometer)) => {
+                let quat = *self.ahrs.update_gyro(&gyroscope);
+                match (
+                    self.ahrs.update_mag(&magnetometer).copied(),
+                    self.error_policy,
+                ) {
+                    (Ok(quat), _) => Ok(quat),
+                    (Err(error), ErrorPolicy::Abort) => Err(error),
+                    (Err(_), _) => Ok(quat),
+                }
+            }
+            (None, None) => Ok(*self.ahrs.update_gyro(&gyroscope)),
+        }
+    }
+}
+
+/// Timestamped measurements of a sensor in time order, of which the `CAPACITY` most recent are
+/// kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Timeline<N: RealField + Copy> {
+    /// Measurements and their timestamps, of which the first `len` are stored.
+    entries: [Option<(N, Vector3<N>)>; CAPACITY],
+    /// Number of stored measurements.
+    len: usize,
+}
+
+impl<N: RealField + Copy> Default for Timeline<N> {
+    fn default() -> Self {
+        Timeline {
+            entries: [None; CAPACITY],
+            len: 0,
+        }
+    }
+}
+
+impl<N: RealField + Copy> Timeline<N> {
+    /// Whether no measurements are stored.
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether `CAPACITY` measurements are stored.
+    fn is_full(&self) -> bool {
+        self.len == CAPACITY
+    }
+
+    /// Earliest stored measurement and its timestamp.
+    fn first(&self) -> Option<(N, Vector3<N>)> {
+        self.entries[0]
+    }
+
+    /// Latest stored measurement and its timestamp.
+    fn last(&self) -> Option<(N, Vector3<N>)> {
+        self.entries[..self.len].last().copied().flatten()
+    }
+
+    /// Removes and returns the earliest stored measurement and its timestamp.
+    fn pop_first(&mut self) -> Option<(N, Vector3<N>)> {
+        let first = self.entries[0].take();
+        if first.is_some() {
+            self.entries[..self.len].rotate_left(1);
+            self.len -= 1;
+        }
+
+        first
+    }
+
+    /// Stores `measurement` taken at `timestamp` in time order, dropping the earliest one if full.
+    fn push(&mut self, timestamp: N, measurement: Vector3<N>) {
+        if self.is_full() {
+            self.pop_first();
+        }
+
+        // Measurements usually arrive in order, so search for the position from the back
+        let mut index = self.len;
+        while index > 0 && matches!(self.entries[index - 1], Some((t, _)) if t > timestamp) {
+            index -= 1;
+        }
+        self.entries[index..=self.len].rotate_right(1);
+        self.entries[index] = Some((timestamp, measurement));
+        self.len += 1;
+    }
+
+    /// Drops the measurements which can no longer be selected for a gyroscope sample later than
+    /// `timestamp`, keeping the latest one taken no later than it.
+    fn discard_before(&mut self, timestamp: N) {
+        while matches!(self.entries[1], Some((t, _)) if t <= timestamp) {
+            self.pop_first();
+        }
+    }
+
+    /// Measurement at `timestamp` according to `policy`, or `None` if none has been taken by then
+    /// or the selected ones are older than `max_age`.
+    fn at(&self, timestamp: N, policy: HoldPolicy, max_age: Option<N>) -> Option<Vector3<N>> {
+        let stored = &self.entries[..self.len];
+        let after = stored
+            .iter()
+            .position(|entry| matches!(entry, Some((t, _)) if *t > timestamp))
+            .unwrap_or(self.len);
+        let (before_timestamp, before) = stored[..after].last().copied().flatten()?;
+        let is_fresh = |t: N| !matches!(max_age, Some(max_age) if (timestamp - t).abs() > max_age);
+
+        match (policy, stored.get(after).copied().flatten()) {
+            (HoldPolicy::Interpolate, Some((after_timestamp, after)))
+                if is_fresh(before_timestamp) || is_fresh(after_timestamp) =>
+            {
+                let t = (timestamp - before_timestamp) / (after_timestamp - before_timestamp);
+                Some(before.lerp(&after, t))
+            }
+            _ if is_fresh(before_timestamp) => Some(before),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Output stages smoothing the quaternion returned by an AHRS filter, without altering its state.
+
+use nalgebra::UnitQuaternion;
+use simba::scalar::RealField;
+
+/// Trait for smoothing a stream of attitude estimates for display, such as the quaternions
+/// returned by `Ahrs::update`.
+///
+/// # Example
+/// ```
+/// # use ahrs::{Ahrs, Madgwick, OneEuro, OutputFilter};
+/// # use nalgebra::Vector3;
+/// let mut ahrs = Madgwick::new(0.01f64, 0.1);
+/// let mut output = OneEuro::new(0.01, 1.0, 0.5);
+///
+/// let gyroscope = Vector3::new(0.0, 0.0, 0.0);
+/// let accelerometer = Vector3::new(0.0, 0.0, 9.81);
+///
+/// let quat = ahrs.update_imu(&gyroscope, &accelerometer).unwrap();
+/// let smoothed = output.filter(quat);
+/// ```
+pub trait OutputFilter<N: RealField + Copy> {
+    /// Smooths the next attitude estimate `quat`, returning the output attitude.
+    fn filter(&mut self, quat: &UnitQuaternion<N>) -> UnitQuaternion<N>;
+
+    /// Forgets the previous estimates, so that the next one is output unchanged.
+    fn reset(&mut self);
+}
+
+/// Smoothing factor of a first-order low-pass filter with `cutoff` frequency, in hertz, sampled
+/// every `sample_period` seconds.
+fn smoothing_factor<N: RealField + Copy>(sample_period: N, cutoff: N) -> N {
+    let tau = N::one() / (N::two_pi() * cutoff);
+    sample_period / (tau + sample_period)
+}
+
+/// Spherical interpolation from `from` towards `to` by `t`, along the shortest path.
+fn slerp<N: RealField + Copy>(
+    from: &UnitQuaternion<N>,
+    to: &UnitQuaternion<N>,
+    t: N,
+) -> UnitQuaternion<N> {
+    from.try_slerp(to, t, N::default_epsilon()).unwrap_or(*to)
+}
+
+/// Exponential smoothing of the attitude by spherical interpolation, a first-order low-pass
+/// filter with a fixed cutoff frequency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlerpSmoother<N: RealField + Copy> {
+    /// Expected sampling period, in seconds.
+    sample_period: N,
+    /// Cutoff frequency, in hertz.
+    cutoff: N,
+    /// Previous output attitude.
+    quat: Option<UnitQuaternion<N>>,
+}
+
+impl<N: RealField + Copy> SlerpSmoother<N> {
+    /// Creates a new `SlerpSmoother` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sampling period of the estimates in seconds.
+    /// * `cutoff` - Cutoff frequency, in hertz.
+    pub fn new(sample_period: N, cutoff: N) -> Self {
+        SlerpSmoother {
+            sample_period,
+            cutoff,
+            quat: None,
+        }
+    }
+}
+
+impl<N: RealField + Copy> OutputFilter<N> for SlerpSmoother<N> {
+    fn filter(&mut self, quat: &UnitQuaternion<N>) -> UnitQuaternion<N> {
+        let output = match self.quat {
+            Some(previous) => slerp(
+                &previous,
+                quat,
+                smoothing_factor(self.sample_period, self.cutoff),
+            ),
+            None => *quat,
+        };
+        self.quat = Some(output);
+
+        output
+    }
+
+    fn reset(&mut self) {
+        self.quat = None;
+    }
+}
+
+/// One-euro filter of the attitude, a low-pass filter whose cutoff frequency rises with the
+/// angular speed, removing jitter at rest while keeping lag low during fast motion.
+///
+/// Based on G. Casiez et al., "1 € Filter: A Simple Speed-based Low-pass Filter for Noisy Input
+/// in Interactive Systems", CHI, 2012, with the speed taken as the angular rate between
+/// successive estimates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OneEuro<N: RealField + Copy> {
+    /// Expected sampling period, in seconds.
+    sample_period: N,
+    /// Cutoff frequency at rest, in hertz.
+    min_cutoff: N,
+    /// Increase of the cutoff frequency with angular speed, in hertz per radian per second.
+    beta: N,
+    /// Cutoff frequency of the angular speed low-pass filter, in hertz.
+    speed_cutoff: N,
+    /// Previous output attitude.
+    quat: Option<UnitQuaternion<N>>,
+    /// Previous unfiltered estimate.
+    raw: Option<UnitQuaternion<N>>,
+    /// Low-pass filtered angular speed, in radians per second.
+    speed: N,
+}
+
+impl<N: RealField + Copy> OneEuro<N> {
+    /// Creates a new `OneEuro` instance, with the angular speed filtered at 1 Hz.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sampling period of the estimates in seconds.
+    /// * `min_cutoff` - Cutoff frequency at rest, in hertz.
+    /// * `beta` - Increase of the cutoff frequency with angular speed, in hertz per radian per
+    ///   second.
+    pub fn new(sample_period: N, min_cutoff: N, beta: N) -> Self {
+        OneEuro {
+            sample_period,
+            min_cutoff,
+            beta,
+            speed_cutoff: N::one(),
+            quat: None,
+            raw: None,
+            speed: N::zero(),
+        }
+    }
+
+    /// Returns the filter with the angular speed low-pass filtered at `cutoff`, in hertz.
+    pub fn with_speed_cutoff(self, cutoff: N) -> Self {
+        OneEuro {
+            speed_cutoff: cutoff,
+            ..self
+        }
+    }
+}
+
+impl<N: RealField + Copy> OutputFilter<N> for OneEuro<N> {
+    fn filter(&mut self, quat: &UnitQuaternion<N>) -> UnitQuaternion<N> {
+        let (Some(previous), Some(raw)) = (self.quat, self.raw) else {
+            self.quat = Some(*quat);
+            self.raw = Some(*quat);
+            return *quat;
+        };
+        self.raw = Some(*quat);
+
+        // Low-pass filtered angular speed between successive estimates, setting the cutoff
+        // frequency
+        let speed = raw.angle_to(quat) / self.sample_period;
+        self.speed +=
+            smoothing_factor(self.sample_period, self.speed_cutoff) * (speed - self.speed);
+        let cutoff = self.min_cutoff + self.beta * self.speed;
+
+        let output = slerp(
+            &previous,
+            quat,
+            smoothing_factor(self.sample_period, cutoff),
+        );
+        self.quat = Some(output);
+
+        output
+    }
+
+    fn reset(&mut self) {
+        self.quat = None;
+        self.raw = None;
+        self.speed = N::zero();
+    }
+}
+
+/// Deadband holding the attitude until the estimate moves further than a threshold angle from
+/// it, and then following the estimate at that distance, removing jitter at rest without jumps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadband<N: RealField + Copy> {
+    /// Angle the estimate may move from the output without changing it, in radians.
+    threshold: N,
+    /// Previous output attitude.
+    quat: Option<UnitQuaternion<N>>,
+}
+
+impl<N: RealField + Copy> Deadband<N> {
+    /// Creates a new `Deadband` instance ignoring motion within `threshold`, in radians.
+    pub fn new(threshold: N) -> Self {
+        Deadband {
+            threshold,
+            quat: None,
+        }
+    }
+}
+
+impl<N: RealField + Copy> OutputFilter<N> for Deadband<N> {
+    fn filter(&mut self, quat: &UnitQuaternion<N>) -> UnitQuaternion<N> {
+        let output = match self.quat {
+            Some(previous) => {
+                let angle = previous.angle_to(quat);
+                if angle > self.threshold {
+                    slerp(&previous, quat, (angle - self.threshold) / angle)
+                } else {
+                    previous
+                }
+            }
+            None => *quat,
+        };
+        self.quat = Some(output);
+
+        output
+    }
+
+    fn reset(&mut self) {
+        self.quat = None;
+    }
+}
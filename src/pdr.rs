@@ -0,0 +1,209 @@
+use crate::{ahrs::AhrsState, units::STANDARD_GRAVITY};
+use nalgebra::{UnitQuaternion, Vector2, Vector3};
+use simba::scalar::RealField;
+
+/// A step detected by `Pdr`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step<N: RealField + Copy> {
+    /// Time the step was detected, in seconds since the first update.
+    pub timestamp: N,
+    /// Estimated step length, in meters.
+    pub length: N,
+    /// Heading the step was taken in, in radians counter-clockwise from north.
+    pub heading: N,
+    /// Horizontal position after the step, in meters north and west of the start.
+    pub position: Vector2<N>,
+}
+
+/// Pedestrian dead reckoning (PDR) built on the orientation of any AHRS filter.
+///
+/// The accelerometer is rotated into the Earth frame by the filter's orientation, and steps are
+/// detected as peaks of the low-pass filtered vertical acceleration above a threshold, separated
+/// by a minimum interval. Each step's length is estimated with the Weinberg model
+/// `gain * (max - min)^(1/4)` from the range of vertical acceleration over the step, and the
+/// step is taken along the heading of the body's x-axis, integrating a 2D track.
+///
+/// The accelerometer is expected in meters per second squared, which the step threshold is given
+/// in, and the device is expected to be carried with its x-axis pointing in the walking
+/// direction.
+///
+/// # Example
+/// ```
+/// # use ahrs::{Ahrs, Madgwick, Pdr};
+/// # use nalgebra::Vector3;
+/// let mut ahrs = Madgwick::new(0.01f64, 0.1);
+/// let mut pdr = Pdr::default();
+///
+/// let gyroscope = Vector3::new(0.0, 0.0, 0.0);
+/// let accelerometer = Vector3::new(0.0, 0.0, 9.81);
+///
+/// ahrs.update_imu(&gyroscope, &accelerometer).unwrap();
+/// if let Some(step) = pdr.update(&ahrs, &accelerometer) {
+///     println!("step of {} m to {:?}", step.length, step.position);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pdr<N: RealField + Copy> {
+    /// Vertical acceleration above which a step is detected, in meters per second squared.
+    step_threshold: N,
+    /// Minimum time between steps, in seconds.
+    min_step_interval: N,
+    /// Gain of the Weinberg step length model, in meters.
+    weinberg_gain: N,
+    /// Time constant of the vertical acceleration low-pass filter, in seconds.
+    tau: N,
+
+    /// Time since the first update, in seconds.
+    time: N,
+    /// Low-pass filtered vertical acceleration, in meters per second squared.
+    vertical: N,
+    /// Time of the most recent step, in seconds.
+    last_step: Option<N>,
+    /// Maximum vertical acceleration of the peak in progress, if any.
+    peak: Option<N>,
+    /// Minimum vertical acceleration since the most recent step.
+    valley: N,
+    /// Horizontal position, in meters north and west of the start.
+    position: Vector2<N>,
+    /// Number of steps detected.
+    steps: usize,
+}
+
+impl Default for Pdr<f64> {
+    /// Creates a new `Pdr` instance with default parameters for walking.
+    ///
+    /// ```
+    /// # use ahrs::Pdr;
+    /// dbg!(Pdr::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // Pdr {
+    /// //     step_threshold: 1.0f64,
+    /// //     min_step_interval: 0.3f64,
+    /// //     weinberg_gain: 0.45f64,
+    /// //     tau: 0.05f64,
+    /// //     ...
+    /// // };
+    /// ```
+    fn default() -> Pdr<f64> {
+        Pdr::new(1.0, 0.3, 0.45)
+    }
+}
+
+impl<N: RealField + Copy> Pdr<N> {
+    /// Creates a new `Pdr` instance at the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `step_threshold` - Vertical acceleration above which a step is detected, in meters per
+    ///   second squared.
+    /// * `min_step_interval` - Minimum time between steps, in seconds.
+    /// * `weinberg_gain` - Gain of the Weinberg step length model, calibrated per user.
+    pub fn new(step_threshold: N, min_step_interval: N, weinberg_gain: N) -> Self {
+        let zero: N = nalgebra::zero();
+
+        Pdr {
+            step_threshold,
+            min_step_interval,
+            weinberg_gain,
+            tau: nalgebra::convert(0.05),
+            time: zero,
+            vertical: zero,
+            last_step: None,
+            peak: None,
+            valley: zero,
+            position: Vector2::zeros(),
+            steps: 0,
+        }
+    }
+
+    /// Returns the instance with the vertical acceleration low-pass filtered with time constant
+    /// `tau`, in seconds.
+    pub fn with_time_constant(self, tau: N) -> Self {
+        Pdr { tau, ..self }
+    }
+
+    /// Horizontal position, in meters north and west of the start.
+    pub fn position(&self) -> Vector2<N> {
+        self.position
+    }
+
+    /// Number of steps detected.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Resets the track to `position`, in meters north and west of the origin, keeping the step
+    /// count.
+    pub fn set_position(&mut self, position: Vector2<N>) {
+        self.position = position;
+    }
+
+    /// Advances step detection with an `accelerometer` measurement, taken at the orientation and
+    /// sampling period of `ahrs` after it was updated with the same sample.
+    ///
+    /// Returns the step which ended with this measurement, if any.
+    pub fn update<A: AhrsState<N>>(
+        &mut self,
+        ahrs: &A,
+        accelerometer: &Vector3<N>,
+    ) -> Option<Step<N>> {
+        self.update_quat(&ahrs.quat(), accelerometer, ahrs.sample_period())
+    }
+
+    /// Equivalent of `update` taking the orientation `quat` and `sample_period`, in seconds,
+    /// directly.
+    pub fn update_quat(
+        &mut self,
+        quat: &UnitQuaternion<N>,
+        accelerometer: &Vector3<N>,
+        sample_period: N,
+    ) -> Option<Step<N>> {
+        let zero: N = nalgebra::zero();
+        let quarter: N = nalgebra::convert(0.25);
+
+        self.time += sample_period;
+
+        // Low-pass filtered vertical acceleration with gravity removed
+        let vertical = (quat * accelerometer)[2] - nalgebra::convert(STANDARD_GRAVITY);
+        let alpha = sample_period / (self.tau + sample_period);
+        self.vertical += alpha * (vertical - self.vertical);
+
+        let Some(peak) = self.peak else {
+            let ready = !matches!(
+                self.last_step,
+                Some(last) if self.time - last < self.min_step_interval
+            );
+            if ready && self.vertical > self.step_threshold {
+                self.peak = Some(self.vertical);
+            } else {
+                self.valley = self.valley.min(self.vertical);
+            }
+            return None;
+        };
+
+        if self.vertical >= zero {
+            self.peak = Some(peak.max(self.vertical));
+            return None;
+        }
+
+        // The peak has ended, completing a step
+        let length = self.weinberg_gain * (peak - self.valley).powf(quarter);
+        let x = quat * Vector3::x();
+        let heading = x[1].atan2(x[0]);
+        self.position += Vector2::new(heading.cos(), heading.sin()) * length;
+
+        self.peak = None;
+        self.valley = self.vertical;
+        self.last_step = Some(self.time);
+        self.steps += 1;
+
+        Some(Step {
+            timestamp: self.time,
+            length,
+            heading,
+            position: self.position,
+        })
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    ahrs::{Ahrs, AhrsReverse, AhrsState, AhrsUncertainty},
+    batch::{accept_timestamp, AhrsBatch, BatchError, ErrorPolicy, Sample},
+};
+use nalgebra::{UnitQuaternion, Vector3};
+use simba::scalar::RealField;
+
+/// Forward-backward smoother for offline attitude reconstruction from a recorded sequence of
+/// samples.
+///
+/// A copy of `ahrs` is run forward over `samples`, as by `AhrsBatch::update_batch`, and another,
+/// reversed by `AhrsReverse`, is run independently backward over the time-reversed samples with
+/// the gyroscope negated. Each sample's attitude fuses the forward estimate predicted from the
+/// earlier samples with the backward estimate corrected by that sample and the later ones, so the
+/// two are drawn from disjoint measurements. They are combined by spherical interpolation
+/// weighted by the inverse variance of their `AhrsUncertainty`, so neither the initial
+/// convergence nor the lag of the causal filter remain in the output. Filters estimating their
+/// uncertainty from residuals must have it enabled, or both estimates are weighted equally.
+///
+/// This two-filter form is used for every filter, including `Ukf`, for which no
+/// Rauch-Tung-Striebel pass over the covariance is implemented.
+///
+/// Whenever consecutive samples are both timestamped, the sampling period is set to the
+/// difference between them. Samples whose timestamp does not increase are handled by `policy` as
+/// in `AhrsBatch::update_batch`, and the backward pass processes exactly the samples the forward
+/// pass did, over the same sampling periods. Returns one quaternion per sample, or the
+/// `BatchError` which caused either pass to fail under `ErrorPolicy::Abort`.
+///
+/// # Example
+/// ```
+/// # use ahrs::{smooth, ErrorPolicy, Madgwick, Sample};
+/// # use nalgebra::Vector3;
+/// let samples = vec![
+///     Sample::new_imu(Vector3::new(0.01f64, 0.02, 0.03), Vector3::new(0.0, 0.0, 1.0));
+///     100
+/// ];
+///
+/// let ahrs = Madgwick::default().with_uncertainty(true);
+/// let quats = smooth(&ahrs, &samples, ErrorPolicy::Fallback).unwrap();
+/// assert_eq!(quats.len(), samples.len());
+/// ```
+pub fn smooth<N, A>(
+    ahrs: &A,
+    samples: &[Sample<N>],
+    policy: ErrorPolicy,
+) -> Result<Vec<UnitQuaternion<N>>, BatchError>
+where
+    N: RealField + Copy,
+    A: Ahrs<N> + AhrsState<N> + AhrsUncertainty<N> + AhrsReverse<N> + Clone,
+{
+    // Forward pass, recording the samples it processes with the sampling period each is
+    // propagated over, and the attitude predicted for each by its gyroscope alone with its variance
+    let mut forward = ahrs.clone();
+    let mut last_timestamp = None;
+    let mut processed = Vec::with_capacity(samples.len());
+    let mut predictions = Vec::with_capacity(samples.len());
+    for (index, sample) in samples.iter().enumerate() {
+        let mut predicted = forward.clone();
+        let accepted = accept_timestamp(&mut forward, &mut last_timestamp, sample, policy)
+            .map_err(|error| BatchError { index, error })?;
+        if !accepted {
+            continue;
+        }
+        forward
+            .update_sample(sample, policy)
+            .map_err(|error| BatchError { index, error })?;
+
+        predicted.set_sample_period(forward.sample_period());
+        let quat = *predicted.update_gyro(&sample.gyroscope);
+        predictions.push((quat, predicted.attitude_std_dev().norm_squared()));
+        processed.push((index, forward.sample_period()));
+    }
+
+    // Backward pass over the same samples from the initial state, correcting with the last sample
+    // without propagating, then propagating each sample's gyroscope in reverse over its sampling
+    // period to the previous sample before correcting with its measurements
+    let mut backward = ahrs.reversed();
+    let Some(&(last, _)) = processed.last() else {
+        return Ok(Vec::new());
+    };
+    let sample = Sample {
+        gyroscope: Vector3::zeros(),
+        ..samples[last]
+    };
+    let quat = backward
+        .update_sample(&sample, policy)
+        .map_err(|error| BatchError { index: last, error })?;
+    let mut fused = vec![fuse(predictions[processed.len() - 1], &backward, quat)];
+
+    for (step, pair) in processed.windows(2).enumerate().rev() {
+        let ((previous, _), (next, sample_period)) = (pair[0], pair[1]);
+        backward.set_sample_period(sample_period);
+
+        let sample = Sample {
+            gyroscope: -samples[next].gyroscope,
+            ..samples[previous]
+        };
+        let quat = backward
+            .update_sample(&sample, policy)
+            .map_err(|error| BatchError {
+                index: previous,
+                error,
+            })?;
+        fused.push(fuse(predictions[step], &backward, quat));
+    }
+
+    // Skipped samples repeat the attitude of the previous one, as in the forward pass
+    let mut output = Vec::with_capacity(samples.len());
+    for (&(index, _), quat) in processed.iter().zip(fused.into_iter().rev()) {
+        if let Some(&previous) = output.last() {
+            output.resize(index, previous);
+        }
+        output.push(quat);
+    }
+    if let Some(&previous) = output.last() {
+        output.resize(samples.len(), previous);
+    }
+
+    Ok(output)
+}
+
+/// Fuses the forward `prediction` and its variance with the attitude `quat` estimated by
+/// `backward`, by inverse variance, or equally if both are exact.
+fn fuse<N, A>(
+    prediction: (UnitQuaternion<N>, N),
+    backward: &A,
+    quat: UnitQuaternion<N>,
+) -> UnitQuaternion<N>
+where
+    N: RealField + Copy,
+    A: AhrsUncertainty<N>,
+{
+    let (forward_quat, forward_variance) = prediction;
+    let backward_variance = backward.attitude_std_dev().norm_squared();
+    let total = forward_variance + backward_variance;
+    let t = if total > N::zero() {
+        forward_variance / total
+    } else {
+        nalgebra::convert(0.5)
+    };
+
+    forward_quat
+        .try_slerp(&quat, t, N::default_epsilon())
+        .unwrap_or(forward_quat)
+}
@@ -0,0 +1,175 @@
+use crate::ahrs::AhrsState;
+use nalgebra::{UnitQuaternion, Vector3};
+use simba::{scalar::RealField, simd::SimdRealField};
+
+/// Rotation rate of the Earth, in radians per second.
+pub(crate) const EARTH_RATE: f64 = 7.292_115e-5;
+
+/// WGS-84 semi-major axis, in meters.
+const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+/// WGS-84 flattening.
+const FLATTENING: f64 = 1.0 / 298.257_223_563;
+/// WGS-84 first eccentricity squared.
+const ECCENTRICITY_SQUARED: f64 = 6.694_379_990_14e-3;
+/// WGS-84 normal gravity at the equator, in meters per second squared.
+const EQUATORIAL_GRAVITY: f64 = 9.780_325_335_9;
+/// WGS-84 normal gravity formula constant.
+const SOMIGLIANA: f64 = 1.931_852_652_41e-3;
+/// WGS-84 ratio of centrifugal to gravitational acceleration at the equator.
+const GRAVITY_RATIO: f64 = 3.449_786_506_84e-3;
+
+/// WGS-84 normal gravity at `latitude`, in radians, and ellipsoidal `altitude`, in meters.
+///
+/// Uses the Somigliana formula on the ellipsoid with the second-order free-air correction for
+/// altitude, and includes the centrifugal acceleration of the Earth's rotation.
+///
+/// # Example
+/// ```
+/// # use ahrs::normal_gravity;
+/// let gravity = normal_gravity(45.0f64.to_radians(), 0.0);
+/// assert!((gravity - 9.806_198).abs() < 1e-6);
+/// ```
+pub fn normal_gravity<N: RealField + Copy>(latitude: N, altitude: N) -> N {
+    let two: N = nalgebra::convert(2.0);
+    let three: N = nalgebra::convert(3.0);
+    let a: N = nalgebra::convert(SEMI_MAJOR_AXIS);
+    let f: N = nalgebra::convert(FLATTENING);
+    let m: N = nalgebra::convert(GRAVITY_RATIO);
+
+    let sin2 = latitude.sin().powi(2);
+    let surface = nalgebra::convert::<_, N>(EQUATORIAL_GRAVITY)
+        * (N::one() + nalgebra::convert::<_, N>(SOMIGLIANA) * sin2)
+        / (N::one() - nalgebra::convert::<_, N>(ECCENTRICITY_SQUARED) * sin2).sqrt();
+
+    surface
+        * (N::one() - two / a * (N::one() + f + m - two * f * sin2) * altitude
+            + three * (altitude / a).powi(2))
+}
+
+/// Rotation rate of the Earth at `latitude`, in radians, expressed in the local north, west and
+/// up tangent frame.
+pub(crate) fn earth_rate<N: SimdRealField + Copy>(latitude: N) -> Vector3<N> {
+    let rate: N = nalgebra::convert(EARTH_RATE);
+    Vector3::new(latitude.simd_cos(), N::zero(), latitude.simd_sin()) * rate
+}
+
+/// Strapdown integration of velocity and position in a local tangent frame.
+///
+/// The accelerometer is rotated into the Earth frame by the orientation of any AHRS filter and,
+/// together with the WGS-84 normal gravity at the current altitude, integrated into velocity and
+/// position relative to an origin at a given latitude and altitude. The Coriolis acceleration of
+/// the Earth's rotation may optionally be compensated, which is only significant with
+/// tactical-grade sensors.
+///
+/// Position and velocity are expressed in the tangent frame at the origin, north, west and up.
+/// The accelerometer is expected in meters per second squared. Without aiding, errors grow
+/// quickly, so this is meant to be combined with external position or velocity fixes.
+///
+/// # Example
+/// ```
+/// # use ahrs::{Ahrs, Madgwick, Strapdown};
+/// # use nalgebra::Vector3;
+/// let mut ahrs = Madgwick::new(0.01f64, 0.1);
+/// let mut strapdown = Strapdown::new(52.0f64.to_radians(), 100.0).with_earth_rate(true);
+///
+/// let gyroscope = Vector3::new(0.0, 0.0, 0.0);
+/// let accelerometer = Vector3::new(0.0, 0.0, 9.81);
+///
+/// ahrs.update_imu(&gyroscope, &accelerometer).unwrap();
+/// strapdown.update(&ahrs, &accelerometer);
+/// println!("position: {:?}", strapdown.position());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Strapdown<N: RealField + Copy> {
+    /// Latitude of the origin, in radians.
+    latitude: N,
+    /// Ellipsoidal altitude of the origin, in meters.
+    altitude: N,
+    /// Whether the Coriolis acceleration of the Earth's rotation is compensated.
+    earth_rate: bool,
+
+    /// Position in the tangent frame, in meters.
+    position: Vector3<N>,
+    /// Velocity in the tangent frame, in meters per second.
+    velocity: Vector3<N>,
+}
+
+impl<N: RealField + Copy> Strapdown<N> {
+    /// Creates a new `Strapdown` instance at rest at the origin, without Earth rate compensation.
+    ///
+    /// # Arguments
+    ///
+    /// * `latitude` - Latitude of the origin, in radians.
+    /// * `altitude` - Ellipsoidal altitude of the origin, in meters.
+    pub fn new(latitude: N, altitude: N) -> Self {
+        Strapdown {
+            latitude,
+            altitude,
+            earth_rate: false,
+            position: Vector3::zeros(),
+            velocity: Vector3::zeros(),
+        }
+    }
+
+    /// Returns the instance with compensation of the Coriolis acceleration of the Earth's
+    /// rotation enabled or disabled.
+    pub fn with_earth_rate(self, enabled: bool) -> Self {
+        Strapdown {
+            earth_rate: enabled,
+            ..self
+        }
+    }
+
+    /// Position in the tangent frame, in meters north, west and up from the origin.
+    pub fn position(&self) -> Vector3<N> {
+        self.position
+    }
+
+    /// Velocity in the tangent frame, in meters per second.
+    pub fn velocity(&self) -> Vector3<N> {
+        self.velocity
+    }
+
+    /// Sets the position in the tangent frame, such as from an external fix, in meters.
+    pub fn set_position(&mut self, position: Vector3<N>) {
+        self.position = position;
+    }
+
+    /// Sets the velocity in the tangent frame, such as from an external fix, in meters per
+    /// second.
+    pub fn set_velocity(&mut self, velocity: Vector3<N>) {
+        self.velocity = velocity;
+    }
+
+    /// Normal gravity at the current altitude, in meters per second squared.
+    pub fn gravity(&self) -> N {
+        normal_gravity(self.latitude, self.altitude + self.position[2])
+    }
+
+    /// Integrates an `accelerometer` measurement, taken at the orientation and sampling period of
+    /// `ahrs` after it was updated with the same sample.
+    pub fn update<A: AhrsState<N>>(&mut self, ahrs: &A, accelerometer: &Vector3<N>) {
+        self.update_quat(&ahrs.quat(), accelerometer, ahrs.sample_period())
+    }
+
+    /// Equivalent of `update` taking the orientation `quat` and `sample_period`, in seconds,
+    /// directly.
+    pub fn update_quat(
+        &mut self,
+        quat: &UnitQuaternion<N>,
+        accelerometer: &Vector3<N>,
+        sample_period: N,
+    ) {
+        let two: N = nalgebra::convert(2.0);
+        let half: N = nalgebra::convert(0.5);
+        let dt = sample_period;
+
+        let mut acceleration = quat * accelerometer - Vector3::z() * self.gravity();
+        if self.earth_rate {
+            acceleration -= earth_rate(self.latitude).cross(&self.velocity) * two;
+        }
+
+        self.position += (self.velocity + acceleration * (half * dt)) * dt;
+        self.velocity += acceleration * dt;
+    }
+}
@@ -0,0 +1,471 @@
+#![allow(non_snake_case)]
+
+use crate::{
+    ahrs::{
+        heading_error, rotate_heading, Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState,
+        AhrsUncertainty, Diagnostics, UpdateMode,
+    },
+    uncertainty::euler_std_dev,
+};
+use nalgebra::{Matrix6, SMatrix, SVector, UnitQuaternion, Vector2, Vector3, Vector6};
+use simba::scalar::RealField;
+
+/// Number of sigma points, two per error state dimension.
+const SIGMA_POINTS: usize = 12;
+
+/// Unscented Kalman filter (UKF) AHRS implementation.
+///
+/// Based on J. L. Crassidis and F. L. Markley, "Unscented Filtering for Spacecraft Attitude
+/// Estimation", Journal of Guidance, Control, and Dynamics, 2003. The state is the attitude and
+/// gyroscope bias, with a six-dimensional error state of a rotation vector in the Earth frame and
+/// a bias offset. Sigma points are drawn from the error covariance and mapped onto the rotation
+/// manifold, so the attitude is propagated and compared with the measurements without
+/// linearization.
+///
+/// The gyroscope is expected in radians per second. Accelerometer and magnetometer noise is given
+/// relative to the normalized measurements, and should include expected linear accelerations and
+/// magnetic disturbances.
+///
+/// # Example
+/// ```
+/// # use ahrs::Ukf;
+/// let mut ahrs = Ukf::new(0.002390625f64, 0.05, 0.05, 0.1);
+/// println!("ukf filter: {:?}", ahrs);
+///
+/// // Can now process IMU data using `Ahrs::update_imu`, etc.
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ukf<N: RealField + Copy> {
+    /// Expected sampling period, in seconds.
+    sample_period: N,
+    /// Standard deviation of the gyroscope noise, in radians per second.
+    gyro_noise: N,
+    /// Standard deviation of the gyroscope bias random walk, in radians per second per square
+    /// root second.
+    bias_noise: N,
+    /// Standard deviation of the normalized accelerometer noise.
+    accel_noise: N,
+    /// Standard deviation of the normalized magnetometer noise.
+    mag_noise: N,
+
+    /// Estimated gyroscope bias, in radians per second.
+    bias: Vector3<N>,
+    /// Covariance of the attitude error, in the Earth frame, and of the gyroscope bias.
+    covariance: Matrix6<N>,
+
+    /// Diagnostics of the most recent update.
+    diagnostics: Diagnostics<N>,
+    /// Whether the diagnostics measure the accelerometer and magnetometer errors.
+    diagnostics_enabled: bool,
+    /// Filter state quaternion.
+    quat: UnitQuaternion<N>,
+}
+
+/// Initial standard deviation of the attitude error, in radians.
+const INITIAL_ATTITUDE_STD_DEV: f64 = 1.0;
+/// Initial standard deviation of the gyroscope bias, in radians per second.
+const INITIAL_BIAS_STD_DEV: f64 = 0.01;
+
+impl Default for Ukf<f64> {
+    /// Creates a new `Ukf` instance with default filter parameters.
+    ///
+    /// ```
+    /// # use ahrs::Ukf;
+    /// dbg!(Ukf::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // Ukf {
+    /// //     sample_period: 1.0f64/256.0,
+    /// //     gyro_noise: 0.05f64,
+    /// //     bias_noise: 0.0001f64,
+    /// //     accel_noise: 0.05f64,
+    /// //     mag_noise: 0.1f64,
+    /// //     ...
+    /// // };
+    /// ```
+    fn default() -> Ukf<f64> {
+        Ukf::new(1.0 / 256.0, 0.05, 0.05, 0.1)
+    }
+}
+
+impl<N: RealField + Copy> Ukf<N> {
+    /// Creates a new `Ukf` AHRS instance with identity quaternion and zero gyroscope bias.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `gyro_noise` - Standard deviation of the gyroscope noise, in radians per second.
+    /// * `accel_noise` - Standard deviation of the normalized accelerometer noise.
+    /// * `mag_noise` - Standard deviation of the normalized magnetometer noise.
+    pub fn new(sample_period: N, gyro_noise: N, accel_noise: N, mag_noise: N) -> Self {
+        let attitude_variance: N = nalgebra::convert(INITIAL_ATTITUDE_STD_DEV.powi(2));
+        let bias_variance: N = nalgebra::convert(INITIAL_BIAS_STD_DEV.powi(2));
+
+        Ukf {
+            sample_period,
+            gyro_noise,
+            bias_noise: nalgebra::convert(1e-4),
+            accel_noise,
+            mag_noise,
+            bias: Vector3::zeros(),
+            covariance: Matrix6::from_diagonal(&Vector6::new(
+                attitude_variance,
+                attitude_variance,
+                attitude_variance,
+                bias_variance,
+                bias_variance,
+                bias_variance,
+            )),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat: UnitQuaternion::identity(),
+        }
+    }
+
+    /// Returns the filter with the given gyroscope bias random walk, in radians per second per
+    /// square root second. Zero keeps the bias at its initial estimate.
+    pub fn with_bias_noise(self, bias_noise: N) -> Self {
+        Ukf { bias_noise, ..self }
+    }
+
+    /// Returns the filter with the given initial error covariance.
+    pub fn with_covariance(self, covariance: Matrix6<N>) -> Self {
+        Ukf { covariance, ..self }
+    }
+
+    /// Returns the filter with measurement of the accelerometer and magnetometer errors reported
+    /// by `AhrsDiagnostics` enabled or disabled.
+    pub fn with_diagnostics(self, enabled: bool) -> Self {
+        Ukf {
+            diagnostics_enabled: enabled,
+            ..self
+        }
+    }
+
+    /// Estimated gyroscope bias, in radians per second.
+    pub fn bias(&self) -> Vector3<N> {
+        self.bias
+    }
+
+    /// Error covariance, of the attitude as a rotation vector in the Earth frame (in square
+    /// radians) followed by the gyroscope bias (in square radians per second squared).
+    pub fn covariance(&self) -> Matrix6<N> {
+        self.covariance
+    }
+
+    /// Sigma points of the error covariance, as the columns of its scaled square root and their
+    /// negations. Returns `None` if the covariance is not positive definite.
+    fn sigma_points(&self) -> Option<[Vector6<N>; SIGMA_POINTS]> {
+        let three: N = N::one() + N::one() + N::one();
+
+        let L = (self.covariance * (three + three)).cholesky()?.l();
+
+        Some(core::array::from_fn(|i| {
+            if i < 6 {
+                L.column(i).into_owned()
+            } else {
+                -L.column(i - 6)
+            }
+        }))
+    }
+
+    /// Attitude of the sigma point `point`.
+    fn sigma_attitude(&self, point: &Vector6<N>) -> UnitQuaternion<N> {
+        UnitQuaternion::from_scaled_axis(point.fixed_rows::<3>(0).into_owned()) * self.quat
+    }
+
+    /// Propagates the attitude and covariance through the integration of `gyroscope`.
+    fn predict(&mut self, gyroscope: &Vector3<N>) {
+        let dt = self.sample_period;
+        let weight = N::one() / nalgebra::convert(SIGMA_POINTS as f64);
+
+        let rotate = |quat: &UnitQuaternion<N>, bias: &Vector3<N>| {
+            quat * UnitQuaternion::from_scaled_axis((gyroscope - bias) * dt)
+        };
+        let predicted = rotate(&self.quat, &self.bias);
+
+        let Some(points) = self.sigma_points() else {
+            self.quat = predicted;
+            return;
+        };
+
+        // Errors of the propagated sigma points relative to the propagated estimate
+        let errors = points.map(|point| {
+            let bias = point.fixed_rows::<3>(3).into_owned();
+            let quat = rotate(&self.sigma_attitude(&point), &(self.bias + bias));
+            let error = (quat * predicted.inverse()).scaled_axis();
+            Vector6::new(error[0], error[1], error[2], bias[0], bias[1], bias[2])
+        });
+
+        let mean = errors.iter().sum::<Vector6<N>>() * weight;
+        let covariance = errors
+            .iter()
+            .map(|error| (error - mean) * (error - mean).transpose())
+            .sum::<Matrix6<N>>()
+            * weight;
+
+        // Process noise of the gyroscope over one sample, and of the bias random walk
+        let attitude_variance = (self.gyro_noise * dt).powi(2);
+        let bias_variance = self.bias_noise.powi(2) * dt;
+        let noise = Matrix6::from_diagonal(&Vector6::new(
+            attitude_variance,
+            attitude_variance,
+            attitude_variance,
+            bias_variance,
+            bias_variance,
+            bias_variance,
+        ));
+
+        // Errors are in the Earth frame, so the mean error is applied on the left
+        self.quat =
+            UnitQuaternion::from_scaled_axis(mean.fixed_rows::<3>(0).into_owned()) * predicted;
+        self.bias += mean.fixed_rows::<3>(3);
+        self.covariance = covariance + noise;
+    }
+
+    /// Corrects the state towards `measured`, given the measurement predicted for an attitude by
+    /// `measure` and the standard deviations of the measurement noise. Returns the norm of the
+    /// innovation, or `None` if the covariances are not positive definite.
+    fn correct<const M: usize>(
+        &mut self,
+        measured: &SVector<N, M>,
+        noise: &SVector<N, M>,
+        measure: impl Fn(&UnitQuaternion<N>) -> SVector<N, M>,
+    ) -> Option<N> {
+        let weight = N::one() / nalgebra::convert(SIGMA_POINTS as f64);
+        let half: N = N::one() / (N::one() + N::one());
+
+        let points = self.sigma_points()?;
+        let predictions = points.map(|point| measure(&self.sigma_attitude(&point)));
+
+        let mean = predictions.iter().sum::<SVector<N, M>>() * weight;
+        let P_zz = predictions
+            .iter()
+            .map(|z| (z - mean) * (z - mean).transpose())
+            .sum::<SMatrix<N, M, M>>()
+            * weight
+            + SMatrix::from_diagonal(&noise.component_mul(noise));
+        let P_xz = points
+            .iter()
+            .zip(&predictions)
+            .map(|(x, z)| x * (z - mean).transpose())
+            .sum::<SMatrix<N, 6, M>>()
+            * weight;
+
+        let K = P_zz.cholesky()?.solve(&P_xz.transpose()).transpose();
+
+        let innovation = measured - mean;
+        let correction = K * innovation;
+        self.quat = UnitQuaternion::from_scaled_axis(correction.fixed_rows::<3>(0).into_owned())
+            * self.quat;
+        self.bias += correction.fixed_rows::<3>(3);
+
+        let covariance = self.covariance - K * P_zz * K.transpose();
+        self.covariance = (covariance + covariance.transpose()) * half;
+
+        Some(innovation.norm())
+    }
+}
+
+impl<N: RealField + Copy> AhrsState<N> for Ukf<N> {
+    fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    fn set_sample_period(&mut self, sample_period: N) {
+        self.sample_period = sample_period;
+    }
+
+    fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    fn set_quat(&mut self, quat: UnitQuaternion<N>) {
+        self.quat = quat;
+    }
+}
+
+impl<N: RealField + Copy> AhrsUncertainty<N> for Ukf<N> {
+    fn attitude_std_dev(&self) -> Vector3<N> {
+        euler_std_dev(
+            &self.quat,
+            &self.covariance.fixed_view::<3, 3>(0, 0).into_owned(),
+        )
+    }
+}
+
+impl<N: RealField + Copy> AhrsReverse<N> for Ukf<N> {
+    fn reversed(&self) -> Self {
+        // Negating the bias negates its correlation with the attitude error
+        let mut covariance = self.covariance;
+        covariance.fixed_view_mut::<3, 3>(0, 3).neg_mut();
+        covariance.fixed_view_mut::<3, 3>(3, 0).neg_mut();
+
+        Ukf {
+            bias: -self.bias,
+            covariance,
+            ..*self
+        }
+    }
+}
+
+impl<N: RealField + Copy> AhrsDiagnostics<N> for Ukf<N> {
+    fn diagnostics(&self) -> Diagnostics<N> {
+        self.diagnostics
+    }
+}
+
+impl<N: RealField + Copy> Ahrs<N> for Ukf<N> {
+    fn update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize accelerometer measurement
+        let Some(accel) = accelerometer.try_normalize(zero) else {
+            return Err(AhrsError::AccelerometerNormZero);
+        };
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+
+        self.predict(gyroscope);
+
+        // Reference direction of Earth's magnetic field
+        let h = self.quat * mag;
+        let b = Vector3::new(Vector2::new(h[0], h[1]).norm(), zero, h[2]);
+
+        let predicted = self.quat;
+        let accel_noise = self.accel_noise;
+        let mag_noise = self.mag_noise;
+        let innovation = self.correct(
+            &Vector6::new(accel[0], accel[1], accel[2], mag[0], mag[1], mag[2]),
+            &Vector6::new(
+                accel_noise,
+                accel_noise,
+                accel_noise,
+                mag_noise,
+                mag_noise,
+                mag_noise,
+            ),
+            |quat| {
+                let f = quat.inverse_transform_vector(&Vector3::z());
+                let m = quat.inverse_transform_vector(&b);
+                Vector6::new(f[0], f[1], f[2], m[0], m[1], m[2])
+            },
+        );
+
+        self.diagnostics = measurement_diagnostics(
+            self.diagnostics_enabled,
+            &predicted,
+            &accel,
+            Some(&mag),
+            innovation,
+        );
+
+        Ok(&self.quat)
+    }
+
+    fn update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+
+        // Normalize accelerometer measurement
+        let Some(accel) = accelerometer.try_normalize(zero) else {
+            return Err(AhrsError::AccelerometerNormZero);
+        };
+
+        self.predict(gyroscope);
+
+        let predicted = self.quat;
+        let noise = Vector3::repeat(self.accel_noise);
+        let innovation = self.correct(&accel, &noise, |quat| {
+            quat.inverse_transform_vector(&Vector3::z())
+        });
+
+        self.diagnostics = measurement_diagnostics(
+            self.diagnostics_enabled,
+            &predicted,
+            &accel,
+            None,
+            innovation,
+        );
+
+        Ok(&self.quat)
+    }
+
+    fn update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N> {
+        self.diagnostics = Diagnostics::default();
+        self.predict(gyroscope);
+
+        &self.quat
+    }
+
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError> {
+        let zero: N = nalgebra::zero();
+        let half: N = N::one() / (N::one() + N::one());
+
+        // Normalize magnetometer measurement
+        let Some(mag) = magnetometer.try_normalize(zero) else {
+            return Err(AhrsError::MagnetometerNormZero);
+        };
+        let error = heading_error(&self.quat, &mag)?;
+        self.diagnostics =
+            Diagnostics::from_heading(self.diagnostics_enabled, &self.quat, &mag, error.abs());
+
+        // Variance of the heading measured by the horizontal component of the field
+        let variance = (self.mag_noise / (self.quat * mag).xy().norm()).powi(2);
+
+        // Kalman update of the yaw error, measured as the negated heading error, with the gain
+        // of the roll and pitch errors removed
+        let P = self.covariance;
+        let mut K = P.column(2) / (P[(2, 2)] + variance);
+        K[0] = zero;
+        K[1] = zero;
+
+        let correction = K * -error;
+        self.quat = rotate_heading(&self.quat, correction[2]);
+        self.bias += correction.fixed_rows::<3>(3);
+
+        // Joseph form of the covariance update, which remains valid for the modified gain
+        let A = Matrix6::identity() - K * Vector6::z().transpose();
+        let covariance = A * P * A.transpose() + K * K.transpose() * variance;
+        self.covariance = (covariance + covariance.transpose()) * half;
+
+        Ok(&self.quat)
+    }
+}
+
+/// Diagnostics of a correction by `accel` and `mag` from the `predicted` attitude, with the norm of
+/// its `innovation`, or of a gyroscope-only fallback if the correction failed. The measurement
+/// errors are only computed if `enabled`.
+fn measurement_diagnostics<N: RealField + Copy>(
+    enabled: bool,
+    predicted: &UnitQuaternion<N>,
+    accel: &Vector3<N>,
+    mag: Option<&Vector3<N>>,
+    innovation: Option<N>,
+) -> Diagnostics<N> {
+    let mode = if mag.is_some() {
+        UpdateMode::Marg
+    } else {
+        UpdateMode::Imu
+    };
+
+    match innovation {
+        Some(innovation) => {
+            Diagnostics::from_measurements(enabled, predicted, accel, mag, innovation, mode)
+        }
+        None => Diagnostics::from_measurements(enabled, predicted, accel, mag, N::zero(), mode)
+            .into_fallback(),
+    }
+}
@@ -0,0 +1,185 @@
+#![allow(non_snake_case)]
+
+use crate::util::wrap_to_pi;
+use nalgebra::{Matrix3, Scalar, UnitQuaternion, Vector3};
+use simba::{scalar::RealField, simd::SimdRealField};
+
+/// Time constant over which residuals are averaged, in seconds.
+const RESIDUAL_TAU: f64 = 1.0;
+
+/// Running mean square of the roll, pitch and yaw residuals of the measurements, used by filters
+/// without a covariance to estimate their uncertainty heuristically.
+///
+/// Tracking is opt-in, as it evaluates several `atan2`s per update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Residuals<N: Scalar> {
+    /// Whether residuals are tracked.
+    enabled: bool,
+    /// Time constant over which residuals are averaged, in seconds.
+    tau: N,
+    /// Sampling period for which `gain` was computed, in seconds.
+    sample_period: N,
+    /// Gain of the running mean square at `sample_period`.
+    gain: N,
+    /// Mean square roll, pitch and yaw residuals, in square radians.
+    mean_square: Vector3<N>,
+    /// Whether roll and pitch have been observed by an accelerometer measurement.
+    tilt_observed: bool,
+    /// Whether yaw has been observed by a magnetometer measurement.
+    heading_observed: bool,
+}
+
+impl<N: Scalar + num_traits::Zero> Residuals<N> {
+    /// Creates residuals which are not tracked.
+    pub(crate) fn new() -> Self {
+        Residuals {
+            enabled: false,
+            tau: N::zero(),
+            sample_period: N::zero(),
+            gain: N::zero(),
+            mean_square: Vector3::from_element(N::zero()),
+            tilt_observed: false,
+            heading_observed: false,
+        }
+    }
+}
+
+impl<N: SimdRealField + Copy> Residuals<N> {
+    /// Creates residuals for which no measurement has been observed, tracked if `enabled`, with
+    /// the averaging gain precomputed for `sample_period`.
+    pub(crate) fn with_tracking(enabled: bool, sample_period: N) -> Self {
+        let tau = nalgebra::convert(RESIDUAL_TAU);
+
+        Residuals {
+            enabled,
+            tau,
+            sample_period,
+            gain: sample_period / (tau + sample_period),
+            ..Residuals::new()
+        }
+    }
+}
+
+impl<N: RealField + Copy> Residuals<N> {
+    /// Accumulates the roll and pitch residuals between the normalized `accel` and the direction
+    /// of gravity predicted by `quat`.
+    pub(crate) fn update_tilt(
+        &mut self,
+        quat: &UnitQuaternion<N>,
+        accel: &Vector3<N>,
+        sample_period: N,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let roll = |v: &Vector3<N>| v[1].atan2(v[2]);
+        let pitch = |v: &Vector3<N>| (-v[0]).atan2((v[1] * v[1] + v[2] * v[2]).sqrt());
+
+        let up = quat.inverse_transform_vector(&Vector3::z());
+        let roll = wrap_to_pi(roll(accel) - roll(&up));
+        let pitch = pitch(accel) - pitch(&up);
+
+        self.accumulate(0, roll, self.tilt_observed, sample_period);
+        self.accumulate(1, pitch, self.tilt_observed, sample_period);
+        self.tilt_observed = true;
+    }
+
+    /// Accumulates the yaw residual between the normalized `mag`, rotated into the Earth frame by
+    /// `quat`, and magnetic north.
+    pub(crate) fn update_heading(
+        &mut self,
+        quat: &UnitQuaternion<N>,
+        mag: &Vector3<N>,
+        sample_period: N,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let h = quat * mag;
+        self.update_yaw(h[1].atan2(h[0]), sample_period);
+    }
+
+    /// Accumulates a yaw residual `error` against an external heading measurement.
+    pub(crate) fn update_yaw(&mut self, error: N, sample_period: N) {
+        if !self.enabled {
+            return;
+        }
+
+        self.accumulate(2, error, self.heading_observed, sample_period);
+        self.heading_observed = true;
+    }
+
+    /// Standard deviations of the roll, pitch and yaw residuals, in radians. Angles which have
+    /// not been observed, including all of them while tracking is disabled, are reported with the
+    /// standard deviation of a uniformly distributed angle.
+    pub(crate) fn std_dev(&self) -> Vector3<N> {
+        let three: N = N::one() + N::one() + N::one();
+        let unobserved = N::pi() / three.sqrt();
+
+        let tilt = |i: usize| {
+            if self.tilt_observed {
+                self.mean_square[i].sqrt()
+            } else {
+                unobserved
+            }
+        };
+        let yaw = if self.heading_observed {
+            self.mean_square[2].sqrt()
+        } else {
+            unobserved
+        };
+
+        Vector3::new(tilt(0), tilt(1), yaw)
+    }
+
+    /// Averages the square of `residual` into component `i`, starting from it if the component
+    /// has not been `observed` yet.
+    fn accumulate(&mut self, i: usize, residual: N, observed: bool, sample_period: N) {
+        let square = residual * residual;
+        if observed {
+            if sample_period != self.sample_period {
+                self.sample_period = sample_period;
+                self.gain = sample_period / (self.tau + sample_period);
+            }
+            let mean_square = self.mean_square[i];
+            self.mean_square[i] = mean_square + (square - mean_square) * self.gain;
+        } else {
+            self.mean_square[i] = square;
+        }
+    }
+}
+
+/// Standard deviations of the roll, pitch and yaw angles of `quat`, given the covariance of its
+/// error as a rotation vector in the Earth frame.
+///
+/// The yaw and roll standard deviations become infinite at a pitch of 90 degrees, where they are
+/// undefined.
+pub(crate) fn euler_std_dev<N: RealField + Copy>(
+    quat: &UnitQuaternion<N>,
+    covariance: &Matrix3<N>,
+) -> Vector3<N> {
+    let zero: N = nalgebra::zero();
+    let one: N = N::one();
+
+    let (roll, pitch, _) = quat.euler_angles();
+    let (sin_roll, cos_roll) = roll.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    let tan_pitch = sin_pitch / cos_pitch;
+
+    // Jacobian of the Euler angles with respect to a body frame rotation, which an Earth frame
+    // rotation is transformed into by the inverse attitude
+    #[rustfmt::skip]
+    let J = Matrix3::new(
+        one,  sin_roll * tan_pitch,  cos_roll * tan_pitch,
+        zero, cos_roll,             -sin_roll,
+        zero, sin_roll / cos_pitch,  cos_roll / cos_pitch,
+    );
+
+    let J = J * quat.to_rotation_matrix().matrix().transpose();
+
+    (J * covariance * J.transpose())
+        .diagonal()
+        .map(|variance| variance.sqrt())
+}
@@ -0,0 +1,167 @@
+//! Unit-safe wrappers for sensor measurements.
+
+use nalgebra::{Scalar, Vector3};
+use simba::scalar::RealField;
+
+/// Standard acceleration of gravity, in meters per second squared.
+pub(crate) const STANDARD_GRAVITY: f64 = 9.80665;
+
+/// Microtesla per gauss.
+const MICROTESLA_PER_GAUSS: f64 = 100.0;
+
+/// Gyroscope measurement, stored in radians per second.
+///
+/// # Example
+/// ```
+/// # use ahrs::AngularRate;
+/// # use approx::assert_relative_eq;
+/// # use core::f64::consts::{FRAC_PI_2, PI};
+/// # use nalgebra::Vector3;
+/// let rate = AngularRate::from_degrees_per_second(Vector3::new(180.0f64, 0.0, -90.0));
+///
+/// assert_relative_eq!(rate.radians_per_second(), &Vector3::new(PI, 0.0, -FRAC_PI_2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AngularRate<N: Scalar>(Vector3<N>);
+
+impl<N: Scalar> AngularRate<N> {
+    /// Creates an angular rate from a measurement in radians per second.
+    pub fn from_radians_per_second(rate: Vector3<N>) -> Self {
+        AngularRate(rate)
+    }
+
+    /// Angular rate in radians per second.
+    pub fn radians_per_second(&self) -> &Vector3<N> {
+        &self.0
+    }
+}
+
+impl<N: RealField + Copy> AngularRate<N> {
+    /// Creates an angular rate from a measurement in degrees per second.
+    pub fn from_degrees_per_second(rate: Vector3<N>) -> Self {
+        AngularRate(rate * (N::pi() / nalgebra::convert(180.0)))
+    }
+
+    /// Angular rate in degrees per second.
+    pub fn degrees_per_second(&self) -> Vector3<N> {
+        self.0 * (nalgebra::convert::<_, N>(180.0) / N::pi())
+    }
+}
+
+/// Accelerometer measurement, stored in meters per second squared.
+///
+/// # Example
+/// ```
+/// # use ahrs::Acceleration;
+/// # use nalgebra::Vector3;
+/// let acceleration = Acceleration::from_g(Vector3::new(0.0f64, 0.0, 1.0));
+///
+/// assert_eq!(acceleration.meters_per_second_squared(), &Vector3::new(0.0, 0.0, 9.80665));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Acceleration<N: Scalar>(Vector3<N>);
+
+impl<N: Scalar> Acceleration<N> {
+    /// Creates an acceleration from a measurement in meters per second squared.
+    pub fn from_meters_per_second_squared(acceleration: Vector3<N>) -> Self {
+        Acceleration(acceleration)
+    }
+
+    /// Acceleration in meters per second squared.
+    pub fn meters_per_second_squared(&self) -> &Vector3<N> {
+        &self.0
+    }
+}
+
+impl<N: RealField + Copy> Acceleration<N> {
+    /// Creates an acceleration from a measurement in multiples of standard gravity.
+    pub fn from_g(acceleration: Vector3<N>) -> Self {
+        Acceleration(acceleration * nalgebra::convert::<_, N>(STANDARD_GRAVITY))
+    }
+
+    /// Acceleration in multiples of standard gravity.
+    pub fn g(&self) -> Vector3<N> {
+        self.0 / nalgebra::convert::<_, N>(STANDARD_GRAVITY)
+    }
+}
+
+/// Magnetometer measurement, stored in microtesla.
+///
+/// # Example
+/// ```
+/// # use ahrs::MagneticField;
+/// # use nalgebra::Vector3;
+/// let field = MagneticField::from_gauss(Vector3::new(0.25f64, 0.0, -0.5));
+///
+/// assert_eq!(field.microtesla(), &Vector3::new(25.0, 0.0, -50.0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MagneticField<N: Scalar>(Vector3<N>);
+
+impl<N: Scalar> MagneticField<N> {
+    /// Creates a magnetic field from a measurement in microtesla.
+    pub fn from_microtesla(field: Vector3<N>) -> Self {
+        MagneticField(field)
+    }
+
+    /// Magnetic field in microtesla.
+    pub fn microtesla(&self) -> &Vector3<N> {
+        &self.0
+    }
+}
+
+impl<N: RealField + Copy> MagneticField<N> {
+    /// Creates a magnetic field from a measurement in gauss.
+    pub fn from_gauss(field: Vector3<N>) -> Self {
+        MagneticField(field * nalgebra::convert::<_, N>(MICROTESLA_PER_GAUSS))
+    }
+
+    /// Magnetic field in gauss.
+    pub fn gauss(&self) -> Vector3<N> {
+        self.0 / nalgebra::convert::<_, N>(MICROTESLA_PER_GAUSS)
+    }
+}
+
+#[cfg(feature = "uom")]
+macro_rules! impl_from_uom(
+    ($($V: ident),*) => {$(
+        impl From<[uom::si::$V::AngularVelocity; 3]> for AngularRate<$V> {
+            fn from([x, y, z]: [uom::si::$V::AngularVelocity; 3]) -> Self {
+                use uom::si::angular_velocity::radian_per_second;
+
+                AngularRate(Vector3::new(
+                    x.get::<radian_per_second>(),
+                    y.get::<radian_per_second>(),
+                    z.get::<radian_per_second>(),
+                ))
+            }
+        }
+
+        impl From<[uom::si::$V::Acceleration; 3]> for Acceleration<$V> {
+            fn from([x, y, z]: [uom::si::$V::Acceleration; 3]) -> Self {
+                use uom::si::acceleration::meter_per_second_squared;
+
+                Acceleration(Vector3::new(
+                    x.get::<meter_per_second_squared>(),
+                    y.get::<meter_per_second_squared>(),
+                    z.get::<meter_per_second_squared>(),
+                ))
+            }
+        }
+
+        impl From<[uom::si::$V::MagneticFluxDensity; 3]> for MagneticField<$V> {
+            fn from([x, y, z]: [uom::si::$V::MagneticFluxDensity; 3]) -> Self {
+                use uom::si::magnetic_flux_density::microtesla;
+
+                MagneticField(Vector3::new(
+                    x.get::<microtesla>(),
+                    y.get::<microtesla>(),
+                    z.get::<microtesla>(),
+                ))
+            }
+        }
+    )*}
+);
+
+#[cfg(feature = "uom")]
+impl_from_uom!(f32, f64);
@@ -0,0 +1,12 @@
+use simba::scalar::RealField;
+
+/// Wraps `angle` to the range `[-pi, pi]`.
+pub(crate) fn wrap_to_pi<N: RealField + Copy>(angle: N) -> N {
+    if angle > N::pi() {
+        angle - N::two_pi()
+    } else if angle < -N::pi() {
+        angle + N::two_pi()
+    } else {
+        angle
+    }
+}
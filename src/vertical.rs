@@ -0,0 +1,164 @@
+#![allow(non_snake_case)]
+
+use crate::{ahrs::AhrsState, units::STANDARD_GRAVITY};
+use nalgebra::{Matrix3, UnitQuaternion, Vector3};
+use simba::scalar::RealField;
+
+/// Barometer-aided vertical channel, estimating altitude and vertical speed.
+///
+/// A Kalman filter over the altitude, vertical speed and vertical accelerometer bias, propagated
+/// with the Earth frame vertical acceleration derived from the orientation of any AHRS filter and
+/// corrected with barometric altitude, which may be sampled at a lower rate than the
+/// accelerometer.
+///
+/// The accelerometer is expected in meters per second squared and altitudes in meters. The
+/// first barometer measurement initializes the altitude.
+///
+/// # Example
+/// ```
+/// # use ahrs::{Ahrs, Madgwick, VerticalChannel};
+/// # use nalgebra::Vector3;
+/// let mut ahrs = Madgwick::new(0.01f64, 0.1);
+/// let mut vertical = VerticalChannel::default();
+///
+/// let gyroscope = Vector3::new(0.0, 0.0, 0.0);
+/// let accelerometer = Vector3::new(0.0, 0.0, 9.81);
+///
+/// ahrs.update_imu(&gyroscope, &accelerometer).unwrap();
+/// vertical.predict(&ahrs, &accelerometer);
+/// vertical.update_baro(120.0);
+///
+/// println!("altitude: {}, climb rate: {}", vertical.altitude(), vertical.vertical_speed());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerticalChannel<N: RealField + Copy> {
+    /// Vertical accelerometer noise, in meters per second squared.
+    accel_noise: N,
+    /// Random walk of the accelerometer bias, in meters per second squared per square root of a
+    /// second.
+    bias_noise: N,
+    /// Barometric altitude noise, in meters.
+    baro_noise: N,
+
+    /// Altitude, vertical speed and vertical accelerometer bias.
+    state: Vector3<N>,
+    /// Covariance of the state.
+    covariance: Matrix3<N>,
+}
+
+impl Default for VerticalChannel<f64> {
+    /// Creates a new `VerticalChannel` instance with noise typical of MEMS accelerometers and
+    /// barometers.
+    ///
+    /// ```
+    /// # use ahrs::VerticalChannel;
+    /// dbg!(VerticalChannel::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // VerticalChannel {
+    /// //     accel_noise: 0.2f64,
+    /// //     bias_noise: 0.01f64,
+    /// //     baro_noise: 0.5f64,
+    /// //     ...
+    /// // };
+    /// ```
+    fn default() -> VerticalChannel<f64> {
+        VerticalChannel::new(0.2, 0.01, 0.5)
+    }
+}
+
+impl<N: RealField + Copy> VerticalChannel<N> {
+    /// Creates a new `VerticalChannel` instance at rest, with unknown altitude.
+    ///
+    /// # Arguments
+    ///
+    /// * `accel_noise` - Vertical accelerometer noise, in meters per second squared.
+    /// * `bias_noise` - Random walk of the accelerometer bias, in meters per second squared per
+    ///   square root of a second.
+    /// * `baro_noise` - Barometric altitude noise, in meters.
+    pub fn new(accel_noise: N, bias_noise: N, baro_noise: N) -> Self {
+        let large: N = nalgebra::convert(1e6);
+        let bias: N = nalgebra::convert(0.1);
+
+        VerticalChannel {
+            accel_noise,
+            bias_noise,
+            baro_noise,
+            state: Vector3::zeros(),
+            covariance: Matrix3::from_diagonal(&Vector3::new(large, N::one(), bias * bias)),
+        }
+    }
+
+    /// Altitude, in meters.
+    pub fn altitude(&self) -> N {
+        self.state[0]
+    }
+
+    /// Vertical speed, in meters per second, positive when climbing.
+    pub fn vertical_speed(&self) -> N {
+        self.state[1]
+    }
+
+    /// Estimated vertical accelerometer bias, in meters per second squared.
+    pub fn accel_bias(&self) -> N {
+        self.state[2]
+    }
+
+    /// Covariance of the altitude, vertical speed and accelerometer bias, in that order.
+    pub fn covariance(&self) -> Matrix3<N> {
+        self.covariance
+    }
+
+    /// Propagates the estimate with an `accelerometer` measurement, taken at the orientation and
+    /// sampling period of `ahrs` after it was updated with the same sample.
+    pub fn predict<A: AhrsState<N>>(&mut self, ahrs: &A, accelerometer: &Vector3<N>) {
+        self.predict_quat(&ahrs.quat(), accelerometer, ahrs.sample_period())
+    }
+
+    /// Equivalent of `predict` taking the orientation `quat` and `sample_period`, in seconds,
+    /// directly.
+    pub fn predict_quat(
+        &mut self,
+        quat: &UnitQuaternion<N>,
+        accelerometer: &Vector3<N>,
+        sample_period: N,
+    ) {
+        let half: N = nalgebra::convert(0.5);
+        let dt = sample_period;
+
+        // Earth frame vertical acceleration, with gravity and the estimated bias removed
+        let acceleration =
+            (quat * accelerometer)[2] - nalgebra::convert(STANDARD_GRAVITY) - self.state[2];
+        let speed = self.state[1];
+        self.state[0] += (speed + acceleration * half * dt) * dt;
+        self.state[1] += acceleration * dt;
+
+        #[rustfmt::skip]
+        let F = Matrix3::new(
+            N::one(), dt, -half * dt * dt,
+            N::zero(), N::one(), -dt,
+            N::zero(), N::zero(), N::one(),
+        );
+        let G = Vector3::new(half * dt * dt, dt, N::zero());
+        let mut Q = G * G.transpose() * self.accel_noise.powi(2);
+        Q[(2, 2)] += self.bias_noise.powi(2) * dt;
+
+        self.covariance = F * self.covariance * F.transpose() + Q;
+    }
+
+    /// Corrects the estimate with a barometric `altitude`, in meters.
+    pub fn update_baro(&mut self, altitude: N) {
+        let half: N = nalgebra::convert(0.5);
+        let variance = self.baro_noise.powi(2);
+
+        let P = self.covariance;
+        let K = P.column(0) / (P[(0, 0)] + variance);
+        self.state += K * (altitude - self.state[0]);
+
+        // Joseph form of the covariance update
+        let A = Matrix3::identity() - K * Vector3::x().transpose();
+        let covariance = A * P * A.transpose() + K * K.transpose() * variance;
+        self.covariance = (covariance + covariance.transpose()) * half;
+    }
+}
@@ -0,0 +1,873 @@
+#![allow(non_snake_case)]
+
+use crate::{
+    ahrs::{
+        Ahrs, AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState, AhrsUncertainty, Diagnostics,
+        UpdateMode,
+    },
+    uncertainty::Residuals,
+    util::wrap_to_pi,
+};
+use nalgebra::{Matrix3, Quaternion, UnitQuaternion, Vector3};
+use simba::scalar::RealField;
+
+/// Versatile Quaternion-based Filter (VQF) AHRS implementation.
+///
+/// Based on D. Laidig and T. Seel, "VQF: Highly Accurate IMU Orientation Estimation with Bias
+/// Estimation and Magnetic Disturbance Rejection", Information Fusion, 2023. The orientation is
+/// split into three parts:
+///
+/// * strapdown integration of the bias-corrected gyroscope,
+/// * an inclination correction aligning the low-pass filtered accelerometer, expressed in an
+///   almost-inertial frame, with the vertical, and
+/// * a heading offset about the vertical, corrected towards the magnetometer while the magnetic
+///   field matches its reference norm and dip angle. Disturbances are rejected for at most 60
+///   seconds, after which the heading is corrected with half the gain.
+///
+/// Gyroscope bias is estimated by a Kalman filter, measured by the low-pass filtered gyroscope
+/// while the sensor is detected to be at rest, and by the inclination corrections during motion,
+/// which leave the bias about the vertical unobservable.
+///
+/// The low-pass filters output the average of their samples for their first time constant, and
+/// then start in steady state at that average, as in the reference implementation. Unlike the
+/// reference, a new magnetic field is accepted as reference once it has been stable for 20
+/// seconds, whether or not the sensor is rotating.
+///
+/// The gyroscope is expected in radians per second and the accelerometer in meters per second
+/// squared, which the rest detection thresholds are given in.
+///
+/// # Example
+/// ```
+/// # use ahrs::Vqf;
+/// let mut ahrs = Vqf::new(0.01f64, 3.0, 9.0);
+/// println!("vqf filter: {:?}", ahrs);
+///
+/// // Can now process IMU data using `Ahrs::update_imu`, etc.
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vqf<N: RealField + Copy> {
+    /// Expected sampling period, in seconds.
+    sample_period: N,
+    /// Time constant of the inclination correction, in seconds.
+    tau_acc: N,
+    /// Time constant of the heading correction, in seconds.
+    tau_mag: N,
+    /// Whether gyroscope bias is estimated at rest.
+    rest_bias_estimation: bool,
+    /// Whether gyroscope bias is estimated during motion.
+    motion_bias_estimation: bool,
+    /// Maximum deviation of the gyroscope from its low-pass filtered value at rest, in radians
+    /// per second.
+    rest_threshold_gyr: N,
+    /// Maximum deviation of the accelerometer from its low-pass filtered value at rest, in meters
+    /// per second squared.
+    rest_threshold_acc: N,
+    /// Whether heading corrections are suspended during magnetic disturbances.
+    mag_dist_rejection: bool,
+    /// Maximum relative deviation of the magnetic field norm from its reference.
+    mag_norm_threshold: N,
+    /// Maximum deviation of the magnetic dip angle from its reference, in radians.
+    mag_dip_threshold: N,
+
+    /// Orientation of the body in the gyroscope-integrated frame.
+    gyr_quat: UnitQuaternion<N>,
+    /// Inclination correction of the gyroscope-integrated frame.
+    acc_quat: UnitQuaternion<N>,
+    /// Heading offset about the vertical, in radians.
+    delta: N,
+    /// Low-pass filter of the accelerometer in the gyroscope-integrated frame.
+    acc_lp: LowPass<N>,
+    /// Gain of the heading correction.
+    k_mag: N,
+    /// Gain used for fast initial convergence of the heading, zero once converged.
+    k_mag_init: N,
+
+    /// Estimated gyroscope bias, in radians per second.
+    bias: Vector3<N>,
+    /// Covariance of the bias estimate, in square hundredths of a degree per second.
+    bias_p: Matrix3<N>,
+    /// Increase of the bias covariance per accelerometer update.
+    bias_v: N,
+    /// Variance of the horizontal bias measured during motion.
+    bias_motion_w: N,
+    /// Variance of the vertical bias measured during motion.
+    bias_vertical_w: N,
+    /// Variance of the bias measured at rest.
+    bias_rest_w: N,
+    /// Low-pass filters of the rows of the rotation matrix of the 6D orientation, used for
+    /// motion bias estimation.
+    motion_r_lp: [LowPass<N>; 3],
+    /// Low-pass filter of the bias rotated by the 6D orientation, used for motion bias
+    /// estimation.
+    motion_bias_lp: LowPass<N>,
+    /// Low-pass filter of the gyroscope used for rest detection.
+    rest_gyr_lp: LowPass<N>,
+    /// Low-pass filtered gyroscope of the most recent rest detection update, in radians per
+    /// second.
+    rest_gyr: Vector3<N>,
+    /// Low-pass filter of the accelerometer used for rest detection.
+    rest_acc_lp: LowPass<N>,
+    /// Time the rest conditions have held for, in seconds.
+    rest_time: N,
+
+    /// Low-pass filter of the current magnetic field norm and dip angle, in its first two
+    /// components.
+    mag_current_lp: LowPass<N>,
+    /// Gain of the filter tracking the reference magnetic field norm and dip angle.
+    k_mag_ref: N,
+    /// Reference magnetic field norm and dip angle.
+    mag_ref: Option<(N, N)>,
+    /// Candidate for a new reference magnetic field norm and dip angle.
+    mag_candidate: Option<(N, N)>,
+    /// Time the candidate field has been stable for, in seconds.
+    mag_candidate_time: N,
+    /// Whether the magnetic field is currently disturbed.
+    mag_disturbed: bool,
+    /// Time the heading corrections have been suspended for, in seconds, decreasing again while
+    /// the field is undisturbed.
+    mag_reject_time: N,
+
+    /// Measurement residuals, from which the attitude uncertainty is estimated.
+    residuals: Residuals<N>,
+    /// Diagnostics of the most recent update.
+    diagnostics: Diagnostics<N>,
+    /// Whether the diagnostics measure the accelerometer and magnetometer errors.
+    diagnostics_enabled: bool,
+    /// Filter state quaternion.
+    quat: UnitQuaternion<N>,
+}
+
+/// Minimum time the rest conditions need to hold for, in seconds.
+const REST_MIN_TIME: f64 = 1.5;
+/// Time constant of the rest detection low-pass filters, in seconds.
+const REST_FILTER_TAU: f64 = 0.5;
+/// Maximum magnitude of the estimated gyroscope bias, in degrees per second.
+const BIAS_CLIP: f64 = 2.0;
+/// Initial standard deviation of the gyroscope bias, in degrees per second.
+const BIAS_SIGMA_INIT: f64 = 0.5;
+/// Time after which the standard deviation of the bias estimate has grown by 0.1 degrees per
+/// second without measurements, in seconds.
+const BIAS_FORGETTING_TIME: f64 = 100.0;
+/// Standard deviation of the bias measured during motion, in degrees per second.
+const BIAS_SIGMA_MOTION: f64 = 0.1;
+/// Factor by which the bias about the vertical, which is unobservable during motion, is forgotten
+/// more slowly than the horizontal bias.
+const BIAS_VERTICAL_FORGETTING_FACTOR: f64 = 0.0001;
+/// Standard deviation of the bias measured at rest, in degrees per second.
+const BIAS_SIGMA_REST: f64 = 0.03;
+/// Time constant of the filter tracking the current magnetic field, in seconds.
+const MAG_CURRENT_TAU: f64 = 0.05;
+/// Time constant of the filter tracking the reference magnetic field, in seconds.
+const MAG_REF_TAU: f64 = 20.0;
+/// Time a new magnetic field needs to be stable for before it is accepted as reference, in
+/// seconds.
+const MAG_NEW_TIME: f64 = 20.0;
+/// Maximum time heading corrections are suspended for during a magnetic disturbance, in seconds.
+const MAG_MAX_REJECTION_TIME: f64 = 60.0;
+/// Factor by which the heading correction gain is reduced once the maximum rejection time has
+/// passed, and by which the rejection time decreases faster than it grows.
+const MAG_REJECTION_FACTOR: f64 = 2.0;
+
+impl Default for Vqf<f64> {
+    /// Creates a new `Vqf` instance with default filter parameters.
+    ///
+    /// ```
+    /// # use ahrs::Vqf;
+    /// dbg!(Vqf::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // Vqf {
+    /// //     sample_period: 1.0f64/256.0,
+    /// //     tau_acc: 3.0f64,
+    /// //     tau_mag: 9.0f64,
+    /// //     ...
+    /// // };
+    /// ```
+    fn default() -> Vqf<f64> {
+        Vqf::new(1.0 / 256.0, 3.0, 9.0)
+    }
+}
+
+impl<N: RealField + Copy> Vqf<N> {
+    /// Creates a new `Vqf` AHRS instance with identity quaternion.
+    ///
+    /// Rest and motion bias estimation and magnetic disturbance rejection are enabled, with the
+    /// thresholds of the reference implementation.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_period` - The expected sensor sampling period in seconds.
+    /// * `tau_acc` - Time constant of the inclination correction, in seconds.
+    /// * `tau_mag` - Time constant of the heading correction, in seconds.
+    pub fn new(sample_period: N, tau_acc: N, tau_mag: N) -> Self {
+        let zero: N = nalgebra::zero();
+
+        let mut vqf = Vqf {
+            sample_period,
+            tau_acc,
+            tau_mag,
+            rest_bias_estimation: true,
+            motion_bias_estimation: true,
+            rest_threshold_gyr: N::pi() * nalgebra::convert(2.0 / 180.0),
+            rest_threshold_acc: nalgebra::convert(0.5),
+            mag_dist_rejection: true,
+            mag_norm_threshold: nalgebra::convert(0.1),
+            mag_dip_threshold: N::pi() * nalgebra::convert(10.0 / 180.0),
+            gyr_quat: UnitQuaternion::identity(),
+            acc_quat: UnitQuaternion::identity(),
+            delta: zero,
+            acc_lp: LowPass::default(),
+            k_mag: zero,
+            k_mag_init: N::one(),
+            bias: Vector3::zeros(),
+            bias_p: Matrix3::from_diagonal_element(nalgebra::convert(
+                (BIAS_SIGMA_INIT * 100.0) * (BIAS_SIGMA_INIT * 100.0),
+            )),
+            bias_v: zero,
+            bias_motion_w: zero,
+            bias_vertical_w: zero,
+            bias_rest_w: zero,
+            motion_r_lp: [LowPass::default(); 3],
+            motion_bias_lp: LowPass::default(),
+            rest_gyr_lp: LowPass::default(),
+            rest_gyr: Vector3::zeros(),
+            rest_acc_lp: LowPass::default(),
+            rest_time: zero,
+            mag_current_lp: LowPass::default(),
+            k_mag_ref: zero,
+            mag_ref: None,
+            mag_candidate: None,
+            mag_candidate_time: zero,
+            mag_disturbed: false,
+            mag_reject_time: zero,
+            residuals: Residuals::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: false,
+            quat: UnitQuaternion::identity(),
+        };
+        vqf.set_coefficients();
+
+        vqf
+    }
+
+    /// Returns the filter with gyroscope bias estimation at rest enabled or disabled.
+    pub fn with_rest_bias_estimation(self, enabled: bool) -> Self {
+        Vqf {
+            rest_bias_estimation: enabled,
+            ..self
+        }
+    }
+
+    /// Returns the filter with gyroscope bias estimation during motion enabled or disabled.
+    pub fn with_motion_bias_estimation(self, enabled: bool) -> Self {
+        Vqf {
+            motion_bias_estimation: enabled,
+            ..self
+        }
+    }
+
+    /// Returns the filter with the given rest detection thresholds.
+    ///
+    /// # Arguments
+    ///
+    /// * `gyroscope` - Maximum deviation of the gyroscope from its low-pass filtered value, in
+    ///   radians per second.
+    /// * `accelerometer` - Maximum deviation of the accelerometer from its low-pass filtered
+    ///   value, in meters per second squared.
+    pub fn with_rest_thresholds(self, gyroscope: N, accelerometer: N) -> Self {
+        Vqf {
+            rest_threshold_gyr: gyroscope,
+            rest_threshold_acc: accelerometer,
+            ..self
+        }
+    }
+
+    /// Returns the filter with magnetic disturbance rejection enabled or disabled.
+    pub fn with_mag_dist_rejection(self, enabled: bool) -> Self {
+        Vqf {
+            mag_dist_rejection: enabled,
+            ..self
+        }
+    }
+
+    /// Returns the filter with tracking of the measurement residuals reported by
+    /// `AhrsUncertainty` enabled or disabled.
+    pub fn with_uncertainty(self, enabled: bool) -> Self {
+        Vqf {
+            residuals: Residuals::with_tracking(enabled, self.sample_period),
+            ..self
+        }
+    }
+
+    /// Returns the filter with measurement of the accelerometer and magnetometer errors reported
+    /// by `AhrsDiagnostics` enabled or disabled.
+    pub fn with_diagnostics(self, enabled: bool) -> Self {
+        Vqf {
+            diagnostics_enabled: enabled,
+            ..self
+        }
+    }
+
+    /// Returns the filter with the given magnetic disturbance thresholds.
+    ///
+    /// # Arguments
+    ///
+    /// * `norm` - Maximum relative deviation of the magnetic field norm from its reference.
+    /// * `dip` - Maximum deviation of the magnetic dip angle from its reference, in radians.
+    pub fn with_mag_thresholds(self, norm: N, dip: N) -> Self {
+        Vqf {
+            mag_norm_threshold: norm,
+            mag_dip_threshold: dip,
+            ..self
+        }
+    }
+
+    /// Orientation estimated from the gyroscope and accelerometer only, whose heading is
+    /// relative to the initial heading.
+    pub fn quat_6d(&self) -> UnitQuaternion<N> {
+        self.acc_quat * self.gyr_quat
+    }
+
+    /// Estimated gyroscope bias, in radians per second.
+    pub fn bias(&self) -> Vector3<N> {
+        self.bias
+    }
+
+    /// Whether the sensor is currently detected to be at rest.
+    pub fn is_resting(&self) -> bool {
+        self.rest_time >= nalgebra::convert(REST_MIN_TIME)
+    }
+
+    /// Whether the magnetic field is currently detected to be disturbed.
+    pub fn is_mag_disturbed(&self) -> bool {
+        self.mag_disturbed
+    }
+
+    /// Recomputes the coefficients which depend on the sampling period.
+    fn set_coefficients(&mut self) {
+        let sample_period = self.sample_period;
+
+        self.acc_lp.set_coefficients(self.tau_acc, sample_period);
+        for lp in &mut self.motion_r_lp {
+            lp.set_coefficients(self.tau_acc, sample_period);
+        }
+        self.motion_bias_lp
+            .set_coefficients(self.tau_acc, sample_period);
+        self.rest_gyr_lp
+            .set_coefficients(nalgebra::convert(REST_FILTER_TAU), sample_period);
+        self.rest_acc_lp
+            .set_coefficients(nalgebra::convert(REST_FILTER_TAU), sample_period);
+        self.k_mag = gain_from_tau(self.tau_mag, sample_period);
+        self.mag_current_lp
+            .set_coefficients(nalgebra::convert(MAG_CURRENT_TAU), sample_period);
+        self.k_mag_ref = gain_from_tau(nalgebra::convert(MAG_REF_TAU), sample_period);
+
+        // Bias variances in square hundredths of a degree per second, as in the reference
+        let square = |sigma: f64| nalgebra::convert::<f64, N>((sigma * 100.0) * (sigma * 100.0));
+        self.bias_v = square(0.1) * sample_period / nalgebra::convert(BIAS_FORGETTING_TIME);
+        let p_motion = square(BIAS_SIGMA_MOTION);
+        self.bias_motion_w = p_motion * p_motion / self.bias_v + p_motion;
+        self.bias_vertical_w =
+            self.bias_motion_w / nalgebra::convert(BIAS_VERTICAL_FORGETTING_FACTOR);
+        let p_rest = square(BIAS_SIGMA_REST);
+        self.bias_rest_w = p_rest * p_rest / self.bias_v + p_rest;
+    }
+
+    /// Updates rest detection with the raw sensor measurements.
+    fn update_rest(&mut self, gyroscope: &Vector3<N>, accelerometer: &Vector3<N>) {
+        let gyr_lp = self.rest_gyr_lp.filter(gyroscope);
+        let acc_lp = self.rest_acc_lp.filter(accelerometer);
+
+        let bias_clip = N::pi() * nalgebra::convert(BIAS_CLIP / 180.0);
+        let moving = (gyroscope - gyr_lp).norm_squared()
+            >= self.rest_threshold_gyr * self.rest_threshold_gyr
+            || (accelerometer - acc_lp).norm_squared()
+                >= self.rest_threshold_acc * self.rest_threshold_acc
+            || gyr_lp.norm() > bias_clip;
+
+        if moving {
+            self.rest_time = nalgebra::zero();
+        } else {
+            self.rest_time += self.sample_period;
+        }
+        self.rest_gyr = gyr_lp;
+    }
+
+    /// Integrates the bias-corrected `gyroscope` into the gyroscope-integrated orientation.
+    fn update_gyr(&mut self, gyroscope: &Vector3<N>) {
+        let step = UnitQuaternion::from_scaled_axis((gyroscope - self.bias) * self.sample_period);
+
+        self.gyr_quat = UnitQuaternion::from_quaternion(*(self.gyr_quat * step).quaternion());
+    }
+
+    /// Corrects the inclination towards the low-pass filtered `accelerometer`, returning the angle
+    /// of the correction, or `None` if the filtered acceleration is zero.
+    fn update_acc(&mut self, accelerometer: &Vector3<N>) -> Option<N> {
+        let zero: N = nalgebra::zero();
+        let one: N = N::one();
+        let half: N = one / (one + one);
+
+        // Low-pass filter in the gyroscope-integrated frame, then rotate into the corrected frame
+        let acc_lp = self.acc_lp.filter(&(self.gyr_quat * accelerometer));
+        let acc = (self.acc_quat * acc_lp).try_normalize(zero)?;
+
+        // Rotation aligning the filtered acceleration with the vertical, about a horizontal axis
+        let w = ((acc[2] + one) * half).sqrt();
+        let correction = if w > nalgebra::convert(1e-6) {
+            Quaternion::new(w, half * acc[1] / w, -half * acc[0] / w, zero)
+        } else {
+            Quaternion::new(zero, one, zero, zero)
+        };
+
+        let correction = UnitQuaternion::from_quaternion(correction);
+        self.acc_quat = correction * self.acc_quat;
+        self.update_bias(&acc);
+
+        Some(correction.angle())
+    }
+
+    /// Updates the bias estimate by a Kalman filter step, measuring it by the low-pass filtered
+    /// gyroscope at rest, and otherwise by the inclination correction towards the normalized
+    /// `acc` in the 6D Earth frame.
+    fn update_bias(&mut self, acc: &Vector3<N>) {
+        if !self.rest_bias_estimation && !self.motion_bias_estimation {
+            return;
+        }
+        let bias_clip = N::pi() * nalgebra::convert(BIAS_CLIP / 180.0);
+
+        // Rotation into the 6D Earth frame and the bias rotated by it, low-pass filtered like
+        // the accelerometer
+        let rotation = self.quat_6d().to_rotation_matrix().into_inner();
+        let bias_lp = self.motion_bias_lp.filter(&(rotation * self.bias));
+        let rotation = Matrix3::from_rows(&[
+            self.motion_r_lp[0]
+                .filter(&rotation.row(0).transpose())
+                .transpose(),
+            self.motion_r_lp[1]
+                .filter(&rotation.row(1).transpose())
+                .transpose(),
+            self.motion_r_lp[2]
+                .filter(&rotation.row(2).transpose())
+                .transpose(),
+        ]);
+
+        // Measurement error, measurement model and measurement variance
+        let measurement = if self.rest_bias_estimation && self.is_resting() {
+            Some((
+                self.rest_gyr - self.bias,
+                Matrix3::identity(),
+                Vector3::repeat(self.bias_rest_w),
+            ))
+        } else if self.motion_bias_estimation {
+            // Angular rate of the inclination correction, about the horizontal axes only
+            let rate = Vector3::new(
+                -acc[1] / self.sample_period + bias_lp[0],
+                acc[0] / self.sample_period + bias_lp[1],
+                N::zero(),
+            );
+            Some((
+                rate - rotation * self.bias,
+                rotation,
+                Vector3::new(self.bias_motion_w, self.bias_motion_w, self.bias_vertical_w),
+            ))
+        } else {
+            None
+        };
+
+        // Grow the covariance up to its initial value, even without a measurement
+        let bias_p0 = nalgebra::convert((BIAS_SIGMA_INIT * 100.0) * (BIAS_SIGMA_INIT * 100.0));
+        for i in 0..3 {
+            if self.bias_p[(i, i)] < bias_p0 {
+                self.bias_p[(i, i)] += self.bias_v;
+            }
+        }
+        let Some((e, R, w)) = measurement else {
+            return;
+        };
+
+        // Kalman update with the disagreement clipped to the bias range
+        let e = e.map(|e| e.clamp(-bias_clip, bias_clip));
+        let P = self.bias_p;
+        let Some(S_inv) = (Matrix3::from_diagonal(&w) + R * P * R.transpose()).try_inverse() else {
+            return;
+        };
+        let K = P * R.transpose() * S_inv;
+        self.bias = (self.bias + K * e).map(|b| b.clamp(-bias_clip, bias_clip));
+        self.bias_p = P - K * R * P;
+    }
+
+    /// Corrects the heading offset towards `magnetometer`, returning the heading error, or `None`
+    /// if the correction is suspended during a magnetic disturbance.
+    fn update_delta(&mut self, magnetometer: &Vector3<N>) -> Option<N> {
+        let zero: N = nalgebra::zero();
+        let mag = self.quat_6d() * magnetometer;
+
+        let mut k = self.k_mag;
+        if self.mag_dist_rejection {
+            // Disturbances are rejected for a limited time only, after which the heading is
+            // corrected with a reduced gain
+            let factor: N = nalgebra::convert(MAG_REJECTION_FACTOR);
+            if self.mag_is_disturbed(&mag) {
+                if self.mag_reject_time <= nalgebra::convert(MAG_MAX_REJECTION_TIME) {
+                    self.mag_reject_time += self.sample_period;
+                    k = zero;
+                } else {
+                    k /= factor;
+                }
+            } else {
+                self.mag_reject_time =
+                    (self.mag_reject_time - factor * self.sample_period).max(zero);
+            }
+        }
+
+        if self.k_mag_init != zero {
+            // Converge quickly from the initial heading, with gains 1, 1/2, 1/3, ...
+            k = k.max(self.k_mag_init);
+            self.k_mag_init /= self.k_mag_init + N::one();
+            if self.k_mag_init * self.tau_mag < self.sample_period {
+                self.k_mag_init = zero;
+            }
+        }
+        if k == zero {
+            return None;
+        }
+
+        // Heading offset which aligns the horizontal field with the Earth frame's x-axis
+        let error = wrap_to_pi(-mag[1].atan2(mag[0]) - self.delta);
+        self.delta = wrap_to_pi(self.delta + k * error);
+
+        Some(error)
+    }
+
+    /// Tracks the norm and dip angle of `mag`, expressed in the inclination-corrected frame, and
+    /// returns whether they deviate from the reference.
+    fn mag_is_disturbed(&mut self, mag: &Vector3<N>) -> bool {
+        let norm = mag.norm();
+        let dip = -(mag[2] / norm).clamp(-N::one(), N::one()).asin();
+
+        let current = self
+            .mag_current_lp
+            .filter(&Vector3::new(norm, dip, nalgebra::zero()));
+        let (norm, dip) = (current[0], current[1]);
+
+        let (norm_threshold, dip_threshold) = (self.mag_norm_threshold, self.mag_dip_threshold);
+        let deviates = |(ref_norm, ref_dip): (N, N)| {
+            (norm - ref_norm).abs() >= norm_threshold * ref_norm
+                || (dip - ref_dip).abs() >= dip_threshold
+        };
+
+        let Some((ref_norm, ref_dip)) = self.mag_ref.filter(|&reference| !deviates(reference))
+        else {
+            // Accept a new reference once a different field has been stable for long enough
+            match self.mag_candidate {
+                Some(candidate) if !deviates(candidate) => {
+                    self.mag_candidate_time += self.sample_period
+                }
+                _ => {
+                    self.mag_candidate = Some((norm, dip));
+                    self.mag_candidate_time = nalgebra::zero();
+                }
+            }
+
+            self.mag_disturbed =
+                self.mag_ref.is_some() && self.mag_candidate_time < nalgebra::convert(MAG_NEW_TIME);
+            if !self.mag_disturbed {
+                self.mag_ref = self.mag_candidate.take();
+            }
+            return self.mag_disturbed;
+        };
+
+        self.mag_ref = Some((
+            ref_norm + self.k_mag_ref * (norm - ref_norm),
+            ref_dip + self.k_mag_ref * (dip - ref_dip),
+        ));
+        self.mag_candidate = None;
+        self.mag_disturbed = false;
+
+        false
+    }
+
+    /// Recomputes the output quaternion from its parts.
+    fn update_quat(&mut self) -> &UnitQuaternion<N> {
+        self.quat =
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), self.delta) * self.quat_6d();
+
+        &self.quat
+    }
+}
+
+impl<N: RealField + Copy> AhrsState<N> for Vqf<N> {
+    fn sample_period(&self) -> N {
+        self.sample_period
+    }
+
+    fn set_sample_period(&mut self, sample_period: N) {
+        self.sample_period = sample_period;
+        self.set_coefficients();
+    }
+
+    fn quat(&self) -> UnitQuaternion<N> {
+        self.quat
+    }
+
+    fn set_quat(&mut self, quat: UnitQuaternion<N>) {
+        self.gyr_quat = quat;
+        self.acc_quat = UnitQuaternion::identity();
+        self.delta = nalgebra::zero();
+        self.acc_lp.reset();
+        for lp in &mut self.motion_r_lp {
+            lp.reset();
+        }
+        self.motion_bias_lp.reset();
+        self.quat = quat;
+    }
+}
+
+impl<N: RealField + Copy> AhrsUncertainty<N> for Vqf<N> {
+    fn attitude_std_dev(&self) -> Vector3<N> {
+        self.residuals.std_dev()
+    }
+}
+
+impl<N: RealField + Copy> AhrsReverse<N> for Vqf<N> {
+    fn reversed(&self) -> Self {
+        let mut rest_gyr_lp = self.rest_gyr_lp;
+        rest_gyr_lp.negate();
+        let mut motion_bias_lp = self.motion_bias_lp;
+        motion_bias_lp.negate();
+
+        Vqf {
+            bias: -self.bias,
+            rest_gyr_lp,
+            rest_gyr: -self.rest_gyr,
+            motion_bias_lp,
+            ..*self
+        }
+    }
+}
+
+impl<N: RealField + Copy> AhrsDiagnostics<N> for Vqf<N> {
+    fn diagnostics(&self) -> Diagnostics<N> {
+        self.diagnostics
+    }
+}
+
+impl<N: RealField + Copy> Ahrs<N> for Vqf<N> {
+    fn update(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        magnetometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        if accelerometer.norm_squared() == nalgebra::zero() {
+            return Err(AhrsError::AccelerometerNormZero);
+        }
+        if magnetometer.norm_squared() == nalgebra::zero() {
+            return Err(AhrsError::MagnetometerNormZero);
+        }
+
+        // Residuals of the measurements against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, accelerometer, self.sample_period);
+        self.residuals
+            .update_heading(&self.quat, magnetometer, self.sample_period);
+
+        let quat = self.quat;
+        self.update_rest(gyroscope, accelerometer);
+        self.update_gyr(gyroscope);
+        let tilt = self.update_acc(accelerometer);
+        let heading = self.update_delta(magnetometer);
+
+        // Heading error is only used when the magnetic field is undisturbed
+        let mode = match (tilt, heading) {
+            (_, Some(_)) => UpdateMode::Marg,
+            (Some(_), None) => UpdateMode::Imu,
+            (None, None) => UpdateMode::Gyro,
+        };
+        let tilt = tilt.unwrap_or_else(N::zero);
+        let yaw = heading.unwrap_or_else(N::zero);
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &quat,
+            accelerometer,
+            heading.map(|_| magnetometer),
+            (tilt * tilt + yaw * yaw).sqrt(),
+            mode,
+        );
+
+        Ok(self.update_quat())
+    }
+
+    fn update_imu(
+        &mut self,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> Result<&UnitQuaternion<N>, AhrsError> {
+        if accelerometer.norm_squared() == nalgebra::zero() {
+            return Err(AhrsError::AccelerometerNormZero);
+        }
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_tilt(&self.quat, accelerometer, self.sample_period);
+
+        let quat = self.quat;
+        self.update_rest(gyroscope, accelerometer);
+        self.update_gyr(gyroscope);
+        let tilt = self.update_acc(accelerometer);
+
+        let (step_norm, mode) = match tilt {
+            Some(tilt) => (tilt, UpdateMode::Imu),
+            None => (N::zero(), UpdateMode::Gyro),
+        };
+        self.diagnostics = Diagnostics::from_measurements(
+            self.diagnostics_enabled,
+            &quat,
+            accelerometer,
+            None,
+            step_norm,
+            mode,
+        );
+
+        Ok(self.update_quat())
+    }
+
+    fn update_gyro(&mut self, gyroscope: &Vector3<N>) -> &UnitQuaternion<N> {
+        self.diagnostics = Diagnostics::default();
+        self.update_gyr(gyroscope);
+
+        self.update_quat()
+    }
+
+    fn update_mag(&mut self, magnetometer: &Vector3<N>) -> Result<&UnitQuaternion<N>, AhrsError> {
+        if magnetometer.norm_squared() == nalgebra::zero() {
+            return Err(AhrsError::MagnetometerNormZero);
+        }
+
+        // Residual of the measurement against the current estimate
+        self.residuals
+            .update_heading(&self.quat, magnetometer, self.sample_period);
+
+        // A disturbed field is rejected, leaving the estimate unchanged
+        let quat = self.quat;
+        self.diagnostics = match self.update_delta(magnetometer) {
+            Some(error) => Diagnostics::from_heading(
+                self.diagnostics_enabled,
+                &quat,
+                magnetometer,
+                error.abs(),
+            ),
+            None => Diagnostics::default(),
+        };
+
+        Ok(self.update_quat())
+    }
+}
+
+/// Second-order Butterworth low-pass filter of a vector.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LowPass<N: RealField + Copy> {
+    /// Time constant, in seconds.
+    tau: N,
+    /// Sampling period, in seconds.
+    sample_period: N,
+    /// Numerator coefficients.
+    b: [N; 3],
+    /// Denominator coefficients, excluding the leading one.
+    a: [N; 2],
+    /// Filter state.
+    state: LowPassState<N>,
+}
+
+/// State of a `LowPass` filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LowPassState<N: RealField + Copy> {
+    /// Averaging the samples until the time constant has passed, with their count and sum, so
+    /// that the filter does not depend on a single initial sample.
+    Averaging(N, Vector3<N>),
+    /// Filtering, with the state of the direct form II transposed.
+    Filtering([Vector3<N>; 2]),
+}
+
+impl<N: RealField + Copy> Default for LowPass<N> {
+    fn default() -> Self {
+        LowPass {
+            tau: nalgebra::zero(),
+            sample_period: nalgebra::zero(),
+            b: [nalgebra::zero(); 3],
+            a: [nalgebra::zero(); 2],
+            state: LowPassState::Averaging(nalgebra::zero(), Vector3::zeros()),
+        }
+    }
+}
+
+impl<N: RealField + Copy> LowPass<N> {
+    /// Sets the coefficients for time constant `tau` and sampling period `sample_period`, both in
+    /// seconds.
+    fn set_coefficients(&mut self, tau: N, sample_period: N) {
+        let one = N::one();
+        let two = one + one;
+        let sqrt_2 = two.sqrt();
+
+        let cutoff = sqrt_2 / (two * N::pi() * tau);
+        let c = (N::pi() * cutoff * sample_period).tan();
+        let d = c * c + sqrt_2 * c + one;
+
+        let b0 = c * c / d;
+        self.tau = tau;
+        self.sample_period = sample_period;
+        self.b = [b0, two * b0, b0];
+        self.a = [two * (c * c - one) / d, (one - sqrt_2 * c + c * c) / d];
+    }
+
+    /// Discards the filter state.
+    fn reset(&mut self) {
+        self.state = LowPassState::Averaging(nalgebra::zero(), Vector3::zeros());
+    }
+
+    /// Negates the filter state, as if every sample so far had been negated.
+    fn negate(&mut self) {
+        self.state = match self.state {
+            LowPassState::Averaging(count, sum) => LowPassState::Averaging(count, -sum),
+            LowPassState::Filtering([s0, s1]) => LowPassState::Filtering([-s0, -s1]),
+        };
+    }
+
+    /// Filters `x`, returning the filtered value.
+    fn filter(&mut self, x: &Vector3<N>) -> Vector3<N> {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+
+        let [s0, s1] = match self.state {
+            LowPassState::Averaging(count, sum) => {
+                let (count, sum) = (count + N::one(), sum + x);
+                let mean = sum / count;
+
+                // Start in steady state at the average once the time constant has passed
+                self.state = if count * self.sample_period >= self.tau {
+                    LowPassState::Filtering([mean * (N::one() - b0), mean * (b2 - a2)])
+                } else {
+                    LowPassState::Averaging(count, sum)
+                };
+                return mean;
+            }
+            LowPassState::Filtering(state) => state,
+        };
+
+        // Direct form II transposed
+        let y = x * b0 + s0;
+        self.state = LowPassState::Filtering([x * b1 - y * a1 + s1, x * b2 - y * a2]);
+
+        y
+    }
+}
+
+/// Gain of a first-order low-pass filter with time constant `tau`, in seconds.
+fn gain_from_tau<N: RealField + Copy>(tau: N, sample_period: N) -> N {
+    if tau <= nalgebra::zero() {
+        N::one()
+    } else {
+        N::one() - (-sample_period / tau).exp()
+    }
+}
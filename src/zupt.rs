@@ -0,0 +1,255 @@
+#![allow(non_snake_case)]
+
+use crate::{ahrs::AhrsState, units::STANDARD_GRAVITY};
+use nalgebra::{Matrix3, Matrix3x6, Matrix6, UnitQuaternion, Vector3, Vector6};
+use simba::scalar::RealField;
+
+/// Zero-velocity-update (ZUPT) aided inertial navigation for foot-mounted sensors.
+///
+/// The accelerometer is rotated into the Earth frame by the orientation of any AHRS filter and
+/// integrated into velocity and position. Stance phases are detected with the stance hypothesis
+/// optimal detector (SHOE) of I. Skog et al., "Zero-Velocity Detection—An Algorithm Evaluation",
+/// IEEE Transactions on Biomedical Engineering, 2010, over a window of the last `W` samples, and
+/// during stance a zero-velocity measurement corrects the velocity and position through an
+/// error-state Kalman filter.
+///
+/// Position and velocity are expressed in the Earth frame, north, west and up from the start.
+/// The gyroscope is expected in radians per second and the accelerometer in meters per second
+/// squared.
+///
+/// # Example
+/// ```
+/// # use ahrs::{Ahrs, Madgwick, Zupt};
+/// # use nalgebra::Vector3;
+/// let mut ahrs = Madgwick::new(0.01f64, 0.1);
+/// let mut ins = Zupt::default();
+///
+/// let gyroscope = Vector3::new(0.0, 0.0, 0.0);
+/// let accelerometer = Vector3::new(0.0, 0.0, 9.80665);
+///
+/// ahrs.update_imu(&gyroscope, &accelerometer).unwrap();
+/// let stance = ins.update(&ahrs, &gyroscope, &accelerometer);
+/// println!("stance: {}, position: {:?}", stance, ins.position());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zupt<N: RealField + Copy, const W: usize = 3> {
+    /// Accelerometer noise of the stance detector, in meters per second squared.
+    detector_accel_noise: N,
+    /// Gyroscope noise of the stance detector, in radians per second.
+    detector_gyro_noise: N,
+    /// Stance detector threshold, below which the sensor is considered stationary.
+    threshold: N,
+    /// Accelerometer noise of the velocity propagation, in meters per second squared.
+    accel_noise: N,
+    /// Noise of the zero-velocity measurement, in meters per second.
+    velocity_noise: N,
+
+    /// Position in the Earth frame, in meters.
+    position: Vector3<N>,
+    /// Velocity in the Earth frame, in meters per second.
+    velocity: Vector3<N>,
+    /// Covariance of the position and velocity errors.
+    covariance: Matrix6<N>,
+    /// Recent gyroscope measurements, as a ring buffer.
+    gyroscope: [Vector3<N>; W],
+    /// Recent accelerometer measurements, as a ring buffer.
+    accelerometer: [Vector3<N>; W],
+    /// Number of measurements received, saturating at the window length.
+    samples: usize,
+    /// Index of the next measurement in the ring buffers.
+    next: usize,
+    /// Whether the most recent sample was detected as stance.
+    stance: bool,
+}
+
+impl Default for Zupt<f64> {
+    /// Creates a new `Zupt` instance with the detector parameters of the OpenShoe reference
+    /// implementation.
+    ///
+    /// ```
+    /// # use ahrs::Zupt;
+    /// dbg!(Zupt::default());
+    ///
+    /// // prints (roughly):
+    /// //
+    /// // Zupt {
+    /// //     detector_accel_noise: 0.01f64,
+    /// //     detector_gyro_noise: 0.1f64 * PI / 180.0,
+    /// //     threshold: 0.3e5f64,
+    /// //     accel_noise: 0.5f64,
+    /// //     velocity_noise: 0.01f64,
+    /// //     ...
+    /// // };
+    /// ```
+    fn default() -> Zupt<f64> {
+        Zupt::new(0.01, 0.1f64.to_radians(), 0.3e5)
+    }
+}
+
+impl<N: RealField + Copy, const W: usize> Zupt<N, W> {
+    /// Creates a new `Zupt` instance at rest at the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `accel_noise` - Accelerometer noise of the stance detector, in meters per second
+    ///   squared.
+    /// * `gyro_noise` - Gyroscope noise of the stance detector, in radians per second.
+    /// * `threshold` - Stance detector threshold, below which the sensor is considered
+    ///   stationary.
+    pub fn new(accel_noise: N, gyro_noise: N, threshold: N) -> Self {
+        Zupt {
+            detector_accel_noise: accel_noise,
+            detector_gyro_noise: gyro_noise,
+            threshold,
+            accel_noise: nalgebra::convert(0.5),
+            velocity_noise: nalgebra::convert(0.01),
+            position: Vector3::zeros(),
+            velocity: Vector3::zeros(),
+            covariance: Matrix6::zeros(),
+            gyroscope: [Vector3::zeros(); W],
+            accelerometer: [Vector3::zeros(); W],
+            samples: 0,
+            next: 0,
+            stance: false,
+        }
+    }
+
+    /// Returns the instance with the given noise of the velocity propagation by `accelerometer`,
+    /// in meters per second squared, and of the zero-velocity measurement `velocity`, in meters
+    /// per second.
+    pub fn with_noise(self, accelerometer: N, velocity: N) -> Self {
+        Zupt {
+            accel_noise: accelerometer,
+            velocity_noise: velocity,
+            ..self
+        }
+    }
+
+    /// Position in the Earth frame, in meters north, west and up from the start.
+    pub fn position(&self) -> Vector3<N> {
+        self.position
+    }
+
+    /// Velocity in the Earth frame, in meters per second.
+    pub fn velocity(&self) -> Vector3<N> {
+        self.velocity
+    }
+
+    /// Covariance of the position and velocity errors, in that order.
+    pub fn covariance(&self) -> Matrix6<N> {
+        self.covariance
+    }
+
+    /// Whether the most recent sample was detected as stance, and corrected with a
+    /// zero-velocity update.
+    pub fn is_stationary(&self) -> bool {
+        self.stance
+    }
+
+    /// Advances navigation with a `gyroscope` and `accelerometer` measurement, taken at the
+    /// orientation and sampling period of `ahrs` after it was updated with the same sample.
+    ///
+    /// Returns whether the sample was detected as stance.
+    pub fn update<A: AhrsState<N>>(
+        &mut self,
+        ahrs: &A,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+    ) -> bool {
+        self.update_quat(&ahrs.quat(), gyroscope, accelerometer, ahrs.sample_period())
+    }
+
+    /// Equivalent of `update` taking the orientation `quat` and `sample_period`, in seconds,
+    /// directly.
+    pub fn update_quat(
+        &mut self,
+        quat: &UnitQuaternion<N>,
+        gyroscope: &Vector3<N>,
+        accelerometer: &Vector3<N>,
+        sample_period: N,
+    ) -> bool {
+        let half: N = nalgebra::convert(0.5);
+        let dt = sample_period;
+
+        // Strapdown integration of the Earth frame acceleration, with gravity removed
+        let gravity = Vector3::z() * nalgebra::convert::<_, N>(STANDARD_GRAVITY);
+        let acceleration = quat * accelerometer - gravity;
+        self.position += (self.velocity + acceleration * (half * dt)) * dt;
+        self.velocity += acceleration * dt;
+
+        // Propagation of the position and velocity error covariance
+        let mut F = Matrix6::identity();
+        F.fixed_view_mut::<3, 3>(0, 3)
+            .copy_from(&(Matrix3::identity() * dt));
+        let mut Q = Matrix6::zeros();
+        Q.fixed_view_mut::<3, 3>(3, 3)
+            .copy_from(&(Matrix3::identity() * (self.accel_noise * dt).powi(2)));
+        self.covariance = F * self.covariance * F.transpose() + Q;
+
+        self.gyroscope[self.next] = *gyroscope;
+        self.accelerometer[self.next] = *accelerometer;
+        self.next = (self.next + 1) % W;
+        self.samples = (self.samples + 1).min(W);
+
+        self.stance = self.detect_stance();
+        if self.stance {
+            self.correct_velocity();
+        }
+
+        self.stance
+    }
+
+    /// SHOE test statistic of the window of recent measurements against the threshold.
+    fn detect_stance(&self) -> bool {
+        if self.samples < W {
+            return false;
+        }
+
+        let window: N = nalgebra::convert(W as f64);
+        let gravity: N = nalgebra::convert(STANDARD_GRAVITY);
+
+        let mean = self.accelerometer.iter().sum::<Vector3<N>>() / window;
+        let Some(up) = mean.try_normalize(nalgebra::zero()) else {
+            return false;
+        };
+
+        let statistic = self
+            .accelerometer
+            .iter()
+            .zip(self.gyroscope.iter())
+            .map(|(accel, gyro)| {
+                (accel - up * gravity).norm_squared() / self.detector_accel_noise.powi(2)
+                    + gyro.norm_squared() / self.detector_gyro_noise.powi(2)
+            })
+            .fold(nalgebra::zero::<N>(), |sum, t| sum + t)
+            / window;
+
+        statistic < self.threshold
+    }
+
+    /// Error-state Kalman update with a zero-velocity measurement, resetting the errors into the
+    /// position and velocity.
+    fn correct_velocity(&mut self) {
+        let half: N = nalgebra::convert(0.5);
+
+        let P = self.covariance;
+        let mut H = Matrix3x6::zeros();
+        H.fixed_view_mut::<3, 3>(0, 3).fill_with_identity();
+        let R = Matrix3::identity() * self.velocity_noise.powi(2);
+
+        let S: Matrix3<N> = H * P * H.transpose() + R;
+        let Some(S_inv) = S.try_inverse() else {
+            return;
+        };
+        let K = P * H.transpose() * S_inv;
+
+        let error: Vector6<N> = K * -self.velocity;
+        self.position += error.fixed_rows::<3>(0);
+        self.velocity += error.fixed_rows::<3>(3);
+
+        // Joseph form of the covariance update
+        let A = Matrix6::identity() - K * H;
+        let covariance = A * P * A.transpose() + K * R * K.transpose();
+        self.covariance = (covariance + covariance.transpose()) * half;
+    }
+}
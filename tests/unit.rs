@@ -1,7 +1,20 @@
-use ahrs::{Ahrs, Madgwick, Mahony};
+#[cfg(feature = "fixed")]
+use ahrs::I16F16;
+use ahrs::{
+    aqua, flae, fqa, gyrocompass, normal_gravity, saam, Acceleration, Ahrs, AhrsBatch,
+    AhrsDiagnostics, AhrsError, AhrsReverse, AhrsState, AhrsUncertainty, AngularRate, Aqua,
+    Complementary, Deadband, Diagnostics, ErrorPolicy, Fourati, Gyrocompass, HoldPolicy, Madgwick,
+    MagneticField, Mahony, MultiRate, OneEuro, OutputFilter, Pdr, Sample, SlerpSmoother, Strapdown,
+    Ukf, UpdateMode, VerticalChannel, Vqf, Zupt,
+};
 use approx::relative_eq;
-use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+use nalgebra::{Matrix6, Quaternion, UnitQuaternion, Vector3};
 use std::f64;
+#[cfg(feature = "std")]
+use {
+    ahrs::{smooth, SimdAhrs},
+    simba::simd::{SimdBool, SimdValue, WideF32x4},
+};
 
 // accel, gyro, mag values
 macro_rules! default_sensors(
@@ -202,3 +215,1549 @@ fn test_mahony_update_imu() {
         expected
     );
 }
+
+/// Accelerometer and magnetometer measurements of a stationary body with attitude `quat`.
+fn stationary_sensors(quat: &UnitQuaternion<f64>) -> (Vector3<f64>, Vector3<f64>) {
+    let accel = quat.inverse_transform_vector(&Vector3::z());
+    let mag = quat.inverse_transform_vector(&Vector3::new(0.5, 0.0, -0.8));
+    (accel, mag)
+}
+
+#[test]
+fn test_complementary_update_converges() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    let mut ahrs = Complementary::default();
+    for _ in 0..2000 {
+        ahrs.update(&Vector3::zeros(), &accel, &mag).unwrap();
+    }
+
+    assert!(ahrs.quat.angle_to(&attitude) < 1e-6);
+}
+
+#[test]
+fn test_complementary_update_imu_corrects_tilt() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, _) = stationary_sensors(&attitude);
+
+    let mut ahrs = Complementary::new_with_quat(
+        1.0 / 256.0,
+        0.02,
+        UnitQuaternion::from_euler_angles(0.0, 0.0, 1.0),
+    );
+    for _ in 0..2000 {
+        ahrs.update_imu(&Vector3::zeros(), &accel).unwrap();
+    }
+
+    let up = ahrs.quat.inverse_transform_vector(&Vector3::z());
+    assert!(relative_eq!(up, accel, epsilon = 1e-6));
+}
+
+#[test]
+fn test_fourati_update_converges() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    let mut ahrs = Fourati::new(1.0 / 256.0, 1.0);
+    for _ in 0..5120 {
+        ahrs.update(&Vector3::zeros(), &accel, &mag).unwrap();
+    }
+
+    assert!(ahrs.quat.angle_to(&attitude) < 1e-4);
+}
+
+#[test]
+fn test_fourati_update_imu_corrects_tilt() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, _) = stationary_sensors(&attitude);
+
+    let mut ahrs = Fourati::new(1.0 / 256.0, 1.0);
+    for _ in 0..2560 {
+        ahrs.update_imu(&Vector3::zeros(), &accel).unwrap();
+    }
+
+    let up = ahrs.quat.inverse_transform_vector(&Vector3::z());
+    assert!(relative_eq!(up, accel, epsilon = 1e-4));
+}
+
+#[test]
+fn test_algebraic_estimators_recover_attitude() {
+    // Includes upside-down attitudes and headings near south, where AQUA switches branches
+    let attitudes = [
+        (0.3, -0.2, 1.0),
+        (3.0, 0.4, -2.5),
+        (0.1, 1.5, 3.0),
+        (-2.9, -0.3, 0.2),
+        (0.0, 0.0, 3.1),
+    ];
+
+    for (roll, pitch, yaw) in attitudes {
+        let attitude = UnitQuaternion::from_euler_angles(roll, pitch, yaw);
+        let (accel, mag) = stationary_sensors(&attitude);
+
+        for estimator in [aqua, fqa, saam, flae] {
+            let quat = estimator(&(accel * 9.81), &(mag * 50.0)).unwrap();
+            assert!(quat.angle_to(&attitude) < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn test_algebraic_estimators_reject_degenerate_inputs() {
+    let accel = Vector3::new(0.0, 0.0, 1.0);
+    let mag = Vector3::new(0.5, 0.0, -0.8);
+
+    for estimator in [aqua, fqa, saam, flae] {
+        assert!(estimator(&Vector3::zeros(), &mag).is_err());
+        assert!(estimator(&accel, &Vector3::zeros()).is_err());
+        // Magnetometer parallel to gravity has no heading information
+        assert!(estimator(&accel, &(accel * -0.8)).is_err());
+    }
+}
+
+#[test]
+fn test_aqua_update_converges() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    let mut ahrs = Aqua::new(1.0 / 256.0, 0.05, 0.05);
+    for _ in 0..2560 {
+        ahrs.update(&Vector3::zeros(), &accel, &mag).unwrap();
+    }
+
+    assert!(ahrs.quat.angle_to(&attitude) < 1e-6);
+}
+
+#[test]
+fn test_aqua_adaptive_gain_ignores_large_accelerations() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, _) = stationary_sensors(&attitude);
+
+    let mut ahrs = Aqua::default().with_adaptive_gain(9.81);
+    ahrs.update_imu(&Vector3::zeros(), &(accel * 9.81 * 1.5))
+        .unwrap();
+    assert_eq!(ahrs.quat, UnitQuaternion::identity());
+
+    for _ in 0..2560 {
+        ahrs.update_imu(&Vector3::zeros(), &(accel * 9.81)).unwrap();
+    }
+    let up = ahrs.quat.inverse_transform_vector(&Vector3::z());
+    assert!(relative_eq!(up, accel, epsilon = 1e-6));
+}
+
+#[test]
+fn test_aqua_rejected_sample_leaves_state_unchanged() {
+    let mut ahrs = Aqua::new(1.0 / 256.0, 0.1, 0.1).with_uncertainty(true);
+    ahrs.update(
+        &Vector3::zeros(),
+        &Vector3::new(0.1, 0.0, 1.0),
+        &Vector3::new(1.0, 0.0, 0.0),
+    )
+    .unwrap();
+    ahrs.set_quat(UnitQuaternion::identity());
+    let before = ahrs;
+
+    // A magnetic field parallel to gravity has no heading
+    let accel = Vector3::new(0.0, 0.0, 1.0);
+    assert!(ahrs.update(&Vector3::zeros(), &accel, &accel).is_err());
+    assert_eq!(ahrs, before);
+    assert_eq!(ahrs.attitude_std_dev(), before.attitude_std_dev());
+
+    // Upside down, a tenth of the half turn is corrected
+    let mut ahrs = Aqua::new(1.0 / 256.0, 0.1, 0.1);
+    ahrs.update_imu(&Vector3::zeros(), &Vector3::new(0.0, 0.0, -1.0))
+        .unwrap();
+    assert!(relative_eq!(
+        ahrs.quat.angle(),
+        0.1 * std::f64::consts::PI,
+        epsilon = 1e-9
+    ));
+}
+
+#[test]
+fn test_ukf_correction_follows_covariance() {
+    let attitude = UnitQuaternion::from_euler_angles(0.2, 0.0, 0.0);
+    let (accel, _) = stationary_sensors(&attitude);
+
+    // An uncertain attitude is mostly corrected by a single measurement, a confident one is not
+    let mut uncertain = Ukf::default().with_covariance(Matrix6::identity() * 1e-2);
+    let mut confident = Ukf::default().with_covariance(Matrix6::identity() * 1e-6);
+    uncertain.update_imu(&Vector3::zeros(), &accel).unwrap();
+    confident.update_imu(&Vector3::zeros(), &accel).unwrap();
+
+    assert!(AhrsState::quat(&uncertain).angle_to(&attitude) < 0.05);
+    assert!(AhrsState::quat(&confident).angle_to(&attitude) > 0.19);
+}
+
+#[test]
+fn test_ukf_estimates_bias_and_covariance() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+    let bias = Vector3::new(0.01, -0.02, 0.015);
+
+    // The bias is observed through the drift it causes between the reference measurements
+    let mut ahrs = Ukf::new(0.1, 0.005, 0.005, 0.005);
+    ahrs.set_quat(attitude);
+    let initial = ahrs.covariance();
+    for _ in 0..40 {
+        ahrs.update(&bias, &accel, &mag).unwrap();
+    }
+
+    assert!(AhrsState::quat(&ahrs).angle_to(&attitude) < 1e-3);
+    assert!(relative_eq!(ahrs.bias(), bias, epsilon = 1e-3));
+    assert!(ahrs.covariance().trace() < initial.trace() * 1e-2);
+}
+
+#[test]
+fn test_residual_uncertainty_reflects_disturbances() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    // Residuals are only tracked once enabled
+    let unobserved = Vector3::repeat(f64::consts::PI / 3.0f64.sqrt());
+    let mut untracked = Madgwick::default();
+    let mut ahrs = Madgwick::default().with_uncertainty(true);
+    assert!(relative_eq!(ahrs.attitude_std_dev(), unobserved));
+    assert_ne!(ahrs, untracked);
+    assert_ne!(Mahony::default().with_uncertainty(true), Mahony::default());
+
+    ahrs.set_quat(attitude);
+    untracked.set_quat(attitude);
+    for _ in 0..64 {
+        ahrs.update(&Vector3::zeros(), &accel, &mag).unwrap();
+        untracked.update(&Vector3::zeros(), &accel, &mag).unwrap();
+    }
+    assert!(relative_eq!(untracked.attitude_std_dev(), unobserved));
+    let settled = ahrs.attitude_std_dev();
+    assert!(settled.norm() < 1e-2);
+    assert!(ahrs.confidence() > 0.99);
+
+    // Magnetic disturbance rotating the field by 90 degrees about the vertical
+    let disturbed = attitude.inverse_transform_vector(&Vector3::new(0.0, 0.5, -0.8));
+    for _ in 0..32 {
+        ahrs.update(&Vector3::zeros(), &accel, &disturbed).unwrap();
+    }
+    assert!(ahrs.attitude_std_dev()[2] > 0.1);
+    assert!(ahrs.confidence() < 0.99);
+}
+
+#[test]
+fn test_ukf_uncertainty_decreases_with_measurements() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    let mut ahrs = Ukf::default();
+    assert!(relative_eq!(ahrs.attitude_std_dev(), Vector3::repeat(1.0)));
+
+    for _ in 0..32 {
+        ahrs.update_imu(&Vector3::zeros(), &accel).unwrap();
+    }
+    // Heading is unobservable without a magnetometer
+    let std_dev = ahrs.attitude_std_dev();
+    assert!(std_dev[0] < 0.01 && std_dev[1] < 0.01);
+    assert!(std_dev[2] > 0.5);
+
+    for _ in 0..32 {
+        ahrs.update(&Vector3::zeros(), &accel, &mag).unwrap();
+    }
+    assert!(ahrs.attitude_std_dev().norm() < 0.05);
+    assert!(ahrs.confidence() > 0.95);
+}
+
+#[test]
+fn test_diagnostics_report_measurement_errors() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, 0.0, 0.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    let mut madgwick = Madgwick::default().with_diagnostics(true);
+    madgwick.update(&Vector3::zeros(), &accel, &mag).unwrap();
+    let diagnostics = madgwick.diagnostics();
+    assert!(relative_eq!(
+        diagnostics.accel_error.unwrap(),
+        0.3,
+        epsilon = 1e-9
+    ));
+    assert!(diagnostics.mag_error.unwrap() > 0.0);
+    assert!(diagnostics.step_norm > 0.0);
+    assert_eq!(diagnostics.mode, UpdateMode::Marg);
+    assert!(!diagnostics.gyro_fallback);
+
+    let mut mahony = Mahony::default().with_diagnostics(true);
+    mahony.update_imu(&Vector3::zeros(), &accel).unwrap();
+    let diagnostics = mahony.diagnostics();
+    assert!(relative_eq!(
+        diagnostics.accel_error.unwrap(),
+        0.3,
+        epsilon = 1e-9
+    ));
+    assert!(relative_eq!(
+        diagnostics.step_norm,
+        0.3f64.sin(),
+        epsilon = 1e-9
+    ));
+    assert_eq!(diagnostics.mag_error, None);
+    assert_eq!(diagnostics.mode, UpdateMode::Imu);
+
+    mahony.update_gyro(&Vector3::zeros());
+    assert_eq!(mahony.diagnostics().accel_error, None);
+    assert_eq!(mahony.diagnostics().mode, UpdateMode::Gyro);
+
+    // Without diagnostics enabled, only the measurement errors are left out
+    let mut untracked = Madgwick::default();
+    untracked.update(&Vector3::zeros(), &accel, &mag).unwrap();
+    assert_eq!(
+        untracked.diagnostics(),
+        Diagnostics {
+            accel_error: None,
+            mag_error: None,
+            ..madgwick.diagnostics()
+        }
+    );
+}
+
+#[test]
+fn test_diagnostics_report_gyro_fallback() {
+    // Measurements matching the estimate exactly leave no gradient to normalize
+    let (accel, mag) = stationary_sensors(&UnitQuaternion::identity());
+
+    let mut ahrs = Madgwick::default().with_diagnostics(true);
+    ahrs.update(&Vector3::zeros(), &accel, &mag).unwrap();
+
+    let diagnostics = ahrs.diagnostics();
+    assert!(diagnostics.gyro_fallback);
+    assert_eq!(diagnostics.mode, UpdateMode::Gyro);
+    assert_eq!(diagnostics.accel_error, Some(0.0));
+    assert_eq!(diagnostics.step_norm, 0.0);
+}
+
+#[test]
+fn test_diagnostics_report_update_mode() {
+    fn check<A: Ahrs<f64> + AhrsDiagnostics<f64>>(
+        mut ahrs: A,
+        accel: &Vector3<f64>,
+        mag: &Vector3<f64>,
+    ) {
+        ahrs.update(&Vector3::zeros(), accel, mag).unwrap();
+        let diagnostics = ahrs.diagnostics();
+        assert_eq!(diagnostics.mode, UpdateMode::Marg);
+        assert!(relative_eq!(
+            diagnostics.accel_error.unwrap(),
+            0.3,
+            epsilon = 1e-6
+        ));
+        assert!(diagnostics.mag_error.is_some());
+        assert!(diagnostics.step_norm > 0.0);
+
+        ahrs.update_imu(&Vector3::zeros(), accel).unwrap();
+        let diagnostics = ahrs.diagnostics();
+        assert_eq!(diagnostics.mode, UpdateMode::Imu);
+        assert_eq!(diagnostics.mag_error, None);
+
+        ahrs.update_mag(mag).unwrap();
+        let diagnostics = ahrs.diagnostics();
+        assert_eq!(diagnostics.mode, UpdateMode::Mag);
+        assert_eq!(diagnostics.accel_error, None);
+
+        ahrs.update_gyro(&Vector3::zeros());
+        assert_eq!(ahrs.diagnostics(), Default::default());
+    }
+
+    let attitude = UnitQuaternion::from_euler_angles(0.3, 0.0, 0.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    check(Madgwick::default().with_diagnostics(true), &accel, &mag);
+    check(Mahony::default().with_diagnostics(true), &accel, &mag);
+    check(
+        Complementary::default().with_diagnostics(true),
+        &accel,
+        &mag,
+    );
+    check(Fourati::default().with_diagnostics(true), &accel, &mag);
+    check(Aqua::default().with_diagnostics(true), &accel, &mag);
+    check(Vqf::default().with_diagnostics(true), &accel, &mag);
+    check(Ukf::default().with_diagnostics(true), &accel, &mag);
+}
+
+#[test]
+fn test_update_mag_corrects_heading_only() {
+    fn check<A: Ahrs<f64> + AhrsState<f64>>(
+        mut ahrs: A,
+        attitude: &UnitQuaternion<f64>,
+        mag: &Vector3<f64>,
+    ) {
+        // Every update reduces the heading error while leaving the tilt untouched
+        ahrs.set_quat(UnitQuaternion::from_euler_angles(0.0, 0.0, 0.2) * attitude);
+        let mut error = 0.2;
+        for _ in 0..16 {
+            let quat = *ahrs.update_mag(mag).unwrap();
+            assert!(quat.angle_to(attitude) < error + 1e-12);
+            assert!(relative_eq!(
+                quat.inverse_transform_vector(&Vector3::z()),
+                attitude.inverse_transform_vector(&Vector3::z()),
+                epsilon = 1e-9
+            ));
+            error = quat.angle_to(attitude);
+        }
+        assert!(error < 0.2);
+    }
+
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (_, mag) = stationary_sensors(&attitude);
+
+    check(Madgwick::default(), &attitude, &mag);
+    check(Mahony::default(), &attitude, &mag);
+    check(Complementary::default(), &attitude, &mag);
+    check(Fourati::default(), &attitude, &mag);
+    check(Aqua::default(), &attitude, &mag);
+    check(Vqf::default(), &attitude, &mag);
+    check(Ukf::default(), &attitude, &mag);
+
+    // A vertical field carries no heading
+    assert!(Madgwick::default().update_mag(&Vector3::z()).is_err());
+    assert!(Mahony::default().update_mag(&Vector3::zeros()).is_err());
+}
+
+#[test]
+fn test_update_heading_corrects_towards_external_yaw() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let start = UnitQuaternion::from_euler_angles(0.0, 0.0, 0.2) * attitude;
+    let up = attitude.inverse_transform_vector(&Vector3::z());
+    let yaw = attitude.euler_angles().2;
+
+    // The correction towards the measured yaw is proportional to its weight
+    let corrections = |weight: f64| {
+        let mut madgwick = Madgwick::default();
+        let mut mahony = Mahony::default();
+        madgwick.set_quat(start);
+        mahony.set_quat(start);
+        [
+            *madgwick.update_heading(yaw, weight),
+            *mahony.update_heading(yaw, weight),
+        ]
+        .map(|quat| {
+            assert!(relative_eq!(
+                quat.inverse_transform_vector(&Vector3::z()),
+                up,
+                epsilon = 1e-9
+            ));
+            0.2 - quat.angle_to(&attitude)
+        })
+    };
+    for (full, half) in corrections(1.0).iter().zip(corrections(0.5)) {
+        assert!(*full > 0.0);
+        assert!(relative_eq!(half, 0.5 * full, epsilon = 1e-6));
+    }
+
+    let mut madgwick = Madgwick::default();
+    let mut mahony = Mahony::default();
+    madgwick.update_heading(yaw, 1.0);
+    let diagnostics = madgwick.diagnostics();
+    assert_eq!(diagnostics.mode, UpdateMode::Heading);
+    assert_eq!(diagnostics.mag_error, None);
+
+    // A zero or negative weight ignores the measurement
+    madgwick.set_quat(start);
+    mahony.set_quat(start);
+    madgwick.update_heading(yaw, 0.0);
+    madgwick.update_heading(yaw, -1.0);
+    mahony.update_heading(yaw, -1.0);
+    assert_eq!(AhrsState::quat(&madgwick), start);
+    assert_eq!(AhrsState::quat(&mahony), start);
+}
+
+#[test]
+fn test_update_with_velocity_compensates_turns() {
+    // Level left turn at 20 m/s and 0.5 rad/s, starting from the true attitude
+    let gyroscope = Vector3::new(0.0, 0.0, 0.5);
+    let velocity = Vector3::new(20.0, 0.0, 0.0);
+    let accelerometer = Vector3::new(0.0, 10.0, 9.81);
+
+    let mut compensated = Madgwick::default();
+    let mut uncompensated = Madgwick::default();
+    for _ in 0..256 {
+        compensated
+            .update_imu_with_velocity(&gyroscope, &accelerometer, &velocity)
+            .unwrap();
+        uncompensated
+            .update_imu(&gyroscope, &accelerometer)
+            .unwrap();
+    }
+
+    let tilt = |ahrs: &Madgwick<f64>| {
+        let (roll, pitch, _) = ahrs.quat.euler_angles();
+        roll.hypot(pitch)
+    };
+    assert!(tilt(&compensated) < 1e-9);
+    assert!(tilt(&uncompensated) > 0.01);
+}
+
+#[test]
+fn test_pdr_tracks_steps_along_heading() {
+    let attitude = UnitQuaternion::from_euler_angles(0.1, -0.05, 0.5);
+    let mut ahrs = Madgwick::new(0.01, 0.1);
+    ahrs.set_quat(attitude);
+
+    // Walking at two steps per second for 10 s
+    let mut pdr = Pdr::default();
+    let mut steps = Vec::new();
+    for i in 0..1000 {
+        let t = i as f64 * 0.01;
+        let vertical = 9.80665 + 3.0 * (2.0 * f64::consts::PI * 2.0 * t).sin();
+        let accel = attitude.inverse_transform_vector(&Vector3::new(0.0, 0.0, vertical));
+        steps.extend(pdr.update(&ahrs, &accel));
+    }
+
+    assert!((19..=20).contains(&steps.len()));
+    assert_eq!(pdr.steps(), steps.len());
+    for step in &steps {
+        assert!(relative_eq!(step.heading, 0.5, epsilon = 1e-9));
+    }
+    // Steps after the first, which starts from rest, share the same acceleration range
+    for step in &steps[1..] {
+        assert!(relative_eq!(step.length, 0.671, epsilon = 1e-3));
+    }
+    let position = pdr.position();
+    assert_eq!(steps.last().unwrap().position, position);
+    assert!(relative_eq!(
+        position[1].atan2(position[0]),
+        0.5,
+        epsilon = 1e-9
+    ));
+}
+
+#[test]
+fn test_zupt_bounds_drift_between_strides() {
+    let ahrs = Madgwick::new(0.01, 0.1);
+    let bias = Vector3::new(0.02, -0.01, 0.0);
+
+    // Three 1 m strides north, each taking 1 s between 1 s of stance
+    let mut ins = Zupt::default();
+    let gravity = Vector3::new(0.0, 0.0, 9.80665);
+    for _ in 0..3 {
+        for _ in 0..100 {
+            ins.update(&ahrs, &Vector3::zeros(), &(gravity + bias));
+        }
+        for i in 0..100 {
+            let t = i as f64 * 0.01;
+            let accel = 2.0 * f64::consts::PI * (2.0 * f64::consts::PI * t).sin();
+            let accel = gravity + bias + Vector3::new(accel, 0.0, 0.0);
+            // The swinging foot pitches, which only drives the stance detector here
+            let gyro = Vector3::new(0.0, 3.0 * (f64::consts::PI * t).sin(), 0.0);
+            ins.update(&ahrs, &gyro, &accel);
+        }
+    }
+    for _ in 0..100 {
+        ins.update(&ahrs, &Vector3::zeros(), &(gravity + bias));
+    }
+
+    assert!(ins.is_stationary());
+    assert!(ins.velocity().norm() < 1e-3);
+    assert!((ins.position() - Vector3::new(3.0, 0.0, 0.0)).norm() < 0.05);
+}
+
+#[test]
+fn test_vertical_channel_tracks_altitude() {
+    let attitude = UnitQuaternion::from_euler_angles(0.2, 0.1, 1.0);
+    let mut ahrs = Madgwick::new(0.05, 0.1);
+    ahrs.set_quat(attitude);
+
+    // Oscillating around 100 m with a biased accelerometer, and the barometer at 10 Hz
+    let mut vertical = VerticalChannel::default();
+    let bias = 0.1;
+    for i in 0..1200 {
+        let t = i as f64 * 0.05;
+        let acceleration = -1.25 * (0.5 * t).sin();
+        let accel = attitude.inverse_transform_vector(&Vector3::new(
+            0.0,
+            0.0,
+            9.80665 + acceleration + bias,
+        ));
+        vertical.predict(&ahrs, &accel);
+        if i % 2 == 0 {
+            vertical.update_baro(100.0 + 5.0 * (0.5 * t).sin() + 0.3 * (7.3 * t).sin());
+        }
+    }
+
+    let t = 60.0;
+    assert!((vertical.altitude() - (100.0 + 5.0 * (0.5f64 * t).sin())).abs() < 0.1);
+    assert!((vertical.vertical_speed() - 2.5 * (0.5f64 * t).cos()).abs() < 0.05);
+    assert!((vertical.accel_bias() - bias).abs() < 0.01);
+}
+
+#[test]
+fn test_strapdown_with_earth_model() {
+    // WGS-84 normal gravity at the equator and pole, decreasing with altitude
+    assert!(relative_eq!(
+        normal_gravity(0.0, 0.0),
+        9.7803253359,
+        epsilon = 1e-9
+    ));
+    assert!(relative_eq!(
+        normal_gravity(f64::consts::FRAC_PI_2, 0.0),
+        9.8321849379,
+        epsilon = 1e-9
+    ));
+    assert!(normal_gravity(0.5, 1000.0) < normal_gravity(0.5, 0.0) - 3e-3);
+
+    let latitude = 45.0f64.to_radians();
+    let attitude = UnitQuaternion::from_euler_angles(0.1, -0.2, 0.3);
+    let mut ahrs = Madgwick::new(0.01, 0.1);
+    ahrs.set_quat(attitude);
+
+    // At rest, the accelerometer only measures normal gravity
+    let mut strapdown = Strapdown::new(latitude, 200.0).with_earth_rate(true);
+    let accel =
+        attitude.inverse_transform_vector(&(Vector3::z() * normal_gravity(latitude, 200.0)));
+    for _ in 0..600 {
+        strapdown.update(&ahrs, &accel);
+    }
+    assert!(strapdown.position().norm() < 1e-6);
+
+    // Moving north, Coriolis acceleration deflects east in the northern hemisphere
+    let mut strapdown = Strapdown::new(latitude, 0.0).with_earth_rate(true);
+    strapdown.set_velocity(Vector3::new(10.0, 0.0, 0.0));
+    let accel = attitude.inverse_transform_vector(&(Vector3::z() * normal_gravity(latitude, 0.0)));
+    for _ in 0..1000 {
+        strapdown.update(&ahrs, &accel);
+    }
+    let deflection = -1000.0 * 7.292115e-5 * latitude.sin();
+    assert!(relative_eq!(
+        strapdown.position()[1],
+        deflection,
+        epsilon = 1e-6
+    ));
+    assert!(relative_eq!(strapdown.position()[0], 100.0, epsilon = 1e-3));
+}
+
+#[test]
+fn test_earth_rate_compensation() {
+    let latitude = 45.0f64.to_radians();
+    let attitude = UnitQuaternion::from_euler_angles(0.0, 0.0, 0.7);
+    let (accel, _) = stationary_sensors(&attitude);
+
+    // A stationary gyroscope only senses the rotation of the Earth
+    let earth_rate = Vector3::new(latitude.cos(), 0.0, latitude.sin()) * 7.292115e-5;
+    let gyro = attitude.inverse_transform_vector(&earth_rate);
+
+    let yaw_drift = |ahrs: &mut dyn Ahrs<f64>| {
+        let mut quat = UnitQuaternion::identity();
+        for _ in 0..1000 {
+            quat = *ahrs.update_imu(&gyro, &accel).unwrap();
+        }
+        (quat.euler_angles().2 - 0.7).abs()
+    };
+
+    let madgwick = Madgwick::new_with_quat(0.1, 0.1, attitude);
+    let mahony = Mahony::new_with_quat(0.1, 0.5, 0.0, attitude);
+    assert!(yaw_drift(&mut madgwick.clone()) > 0.003);
+    assert!(yaw_drift(&mut mahony.clone()) > 0.003);
+    assert!(yaw_drift(&mut madgwick.with_latitude(latitude)) < 1e-6);
+    assert!(yaw_drift(&mut mahony.with_latitude(latitude)) < 1e-6);
+}
+
+#[test]
+fn test_gyrocompass_finds_north() {
+    let latitude = 45.0f64.to_radians();
+    let attitude = UnitQuaternion::from_euler_angles(0.1, -0.05, 2.5);
+    let earth_rate = Vector3::new(latitude.cos(), 0.0, latitude.sin()) * 7.292115e-5;
+
+    // Gyroscope noise far above the Earth rate, averaged over the stationary interval
+    let mut alignment = Gyrocompass::new();
+    assert_eq!(alignment.quat(), None);
+    for i in 0..10_000 {
+        let noise = Vector3::new(1.0, -0.7, 0.3) * (1e-3 * (i as f64 * 1.3).sin());
+        let gyro = attitude.inverse_transform_vector(&earth_rate) + noise;
+        let accel = attitude.inverse_transform_vector(&Vector3::new(0.0, 0.0, 9.81));
+        alignment.update(&gyro, &accel);
+    }
+
+    assert!(alignment.quat().unwrap().angle_to(&attitude) < 1e-3);
+    assert!(relative_eq!(
+        alignment.latitude().unwrap(),
+        latitude,
+        epsilon = 1e-3
+    ));
+
+    // At the poles, the Earth rate carries no heading
+    assert_eq!(gyrocompass::<f64>(&Vector3::z(), &Vector3::z()), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_smooth_removes_convergence_transient() {
+    // Rotating about a fixed body axis, with the filter starting far from the true attitude
+    let start = UnitQuaternion::from_euler_angles(0.4, -0.3, 1.5);
+    let gyro = Vector3::new(0.1, -0.2, 0.3);
+    let attitude = |i: usize| start * UnitQuaternion::from_scaled_axis(gyro * (i as f64 * 0.01));
+    let samples: Vec<_> = (0..1000)
+        .map(|i| {
+            let (accel, mag) = stationary_sensors(&attitude(i));
+            Sample::new(gyro, accel, mag).with_timestamp(i as f64 * 0.01)
+        })
+        .collect();
+
+    let ahrs = Madgwick::new(0.01, 0.5).with_uncertainty(true);
+    let forward = ahrs
+        .clone()
+        .update_batch(samples.clone(), ErrorPolicy::Abort)
+        .unwrap();
+    let smoothed = smooth(&ahrs, &samples, ErrorPolicy::Abort).unwrap();
+    assert_eq!(smoothed.len(), samples.len());
+
+    let max_error = |quats: &[UnitQuaternion<f64>]| {
+        (0..quats.len())
+            .map(|i| quats[i].angle_to(&attitude(i)))
+            .fold(0.0, f64::max)
+    };
+    // The fixed gradient step of `Madgwick` chatters by about `beta * sample_period`
+    assert!(max_error(&forward) > 1.0);
+    assert!(max_error(&smoothed) < 0.02);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_smooth_handles_repeated_timestamps_like_forward_pass() {
+    let gyro = Vector3::new(0.1, -0.2, 0.3);
+    let samples: Vec<_> = (0..100)
+        .map(|i| {
+            let attitude = UnitQuaternion::from_scaled_axis(gyro * (i as f64 * 0.01));
+            let (accel, mag) = stationary_sensors(&attitude);
+            Sample::new(gyro, accel, mag).with_timestamp(i as f64 * 0.01)
+        })
+        .collect();
+    let mut repeated = samples.clone();
+    repeated.insert(
+        50,
+        Sample {
+            gyroscope: gyro * 10.0,
+            ..samples[49]
+        },
+    );
+
+    let ahrs = Madgwick::new(0.01, 0.5).with_uncertainty(true);
+    let err = smooth(&ahrs, &repeated, ErrorPolicy::Abort).unwrap_err();
+    assert_eq!(err.index, 50);
+    assert!(matches!(err.error, AhrsError::TimestampNotIncreasing));
+
+    // Skipped by both passes, repeating the attitude of the previous sample
+    let skipped = smooth(&ahrs, &repeated, ErrorPolicy::Skip).unwrap();
+    let mut expected = smooth(&ahrs, &samples, ErrorPolicy::Skip).unwrap();
+    expected.insert(50, expected[49]);
+    assert_eq!(skipped, expected);
+
+    // Processed by both passes over the previous sampling period
+    let fallback = smooth(&ahrs, &repeated, ErrorPolicy::Fallback).unwrap();
+    let untimed: Vec<_> = repeated
+        .iter()
+        .map(|sample| Sample {
+            timestamp: None,
+            ..*sample
+        })
+        .collect();
+    let expected = smooth(&ahrs, &untimed, ErrorPolicy::Fallback).unwrap();
+    assert_eq!(fallback.len(), expected.len());
+    for (quat, expected) in fallback.iter().zip(&expected) {
+        assert!(quat.angle_to(expected) < 1e-6);
+    }
+}
+
+#[test]
+fn test_reversed_filters_negate_rate_states() {
+    fn check<A: Ahrs<f64> + AhrsState<f64> + AhrsReverse<f64> + Clone>(mut ahrs: A) {
+        let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+        let (accel, mag) = stationary_sensors(&attitude);
+        let bias = Vector3::new(0.01, -0.005, 0.015);
+
+        // Learn the bias of a stationary gyroscope, then replay the samples backward in time
+        ahrs.set_quat(attitude);
+        for _ in 0..200 {
+            ahrs.update(&bias, &accel, &mag).unwrap();
+        }
+        let max_error = |mut ahrs: A| {
+            (0..40)
+                .map(|_| {
+                    ahrs.update(&-bias, &accel, &mag)
+                        .unwrap()
+                        .angle_to(&attitude)
+                })
+                .fold(0.0, f64::max)
+        };
+
+        assert!(max_error(ahrs.reversed()) < 0.01);
+        assert!(max_error(ahrs.clone()) > 0.03);
+    }
+
+    check(Mahony::new(0.05, 2.0, 1.0));
+    check(Ukf::new(0.05, 0.01, 0.05, 0.05));
+    check(Vqf::new(0.05, 3.0, 9.0));
+}
+
+#[test]
+fn test_output_filters_smooth_jitter() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let jitter = |i: usize| {
+        let sign = (-1.0f64).powi(i as i32);
+        UnitQuaternion::from_scaled_axis(Vector3::new(0.5, -0.3, 0.8).normalize() * 0.01 * sign)
+            * attitude
+    };
+
+    fn max_jitter<F: OutputFilter<f64>>(
+        output: &mut F,
+        jitter: impl Fn(usize) -> UnitQuaternion<f64>,
+    ) -> f64 {
+        let mut previous = output.filter(&jitter(0));
+        (1..500)
+            .map(|i| {
+                let quat = output.filter(&jitter(i));
+                let step = quat.angle_to(&previous);
+                previous = quat;
+                if i > 400 {
+                    step
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f64::max)
+    }
+
+    // The raw estimates alternate by 0.02 rad
+    let mut slerp = SlerpSmoother::new(0.01, 1.0);
+    let mut one_euro = OneEuro::new(0.01, 1.0, 0.5);
+    let mut deadband = Deadband::new(0.02);
+    assert!(max_jitter(&mut slerp, jitter) < 0.002);
+    assert!(max_jitter(&mut one_euro, jitter) < 0.005);
+    assert_eq!(max_jitter(&mut deadband, jitter), 0.0);
+
+    // After a large step, the deadband output trails by the threshold, and the others converge
+    let target = UnitQuaternion::from_euler_angles(0.0, 0.0, -2.5) * attitude;
+    for _ in 0..500 {
+        slerp.filter(&target);
+        one_euro.filter(&target);
+        deadband.filter(&target);
+    }
+    assert!(slerp.filter(&target).angle_to(&target) < 1e-6);
+    assert!(one_euro.filter(&target).angle_to(&target) < 1e-6);
+    assert!(relative_eq!(
+        deadband.filter(&target).angle_to(&target),
+        0.02,
+        epsilon = 1e-9
+    ));
+
+    // Without history, the estimate is output unchanged
+    slerp.reset();
+    assert_eq!(slerp.filter(&attitude), attitude);
+}
+
+#[test]
+fn test_multirate_fuses_sensors_at_different_rates() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    // Gyroscope at 1 kHz, accelerometer at 400 Hz and magnetometer at 100 Hz
+    let mut fusion = MultiRate::new(
+        Madgwick::new_with_quat(0.002, 0.5, attitude),
+        HoldPolicy::SampleAndHold,
+    );
+    let (mut next_accel, mut next_mag) = (0.0, 0.0);
+    for i in 1..=100 {
+        let t = i as f64 * 0.001;
+        while next_accel <= t {
+            fusion.push_accelerometer(next_accel, accel * 9.81);
+            next_accel += 0.0025;
+        }
+        while next_mag <= t {
+            fusion.push_magnetometer(next_mag, mag * 50.0);
+            next_mag += 0.01;
+        }
+        fusion.push_gyroscope(t, Vector3::zeros()).unwrap();
+    }
+
+    assert!(relative_eq!(
+        AhrsState::sample_period(fusion.ahrs()),
+        0.001,
+        epsilon = 1e-9
+    ));
+    assert_eq!(fusion.ahrs().diagnostics().mode, UpdateMode::Marg);
+    assert!(AhrsState::quat(fusion.ahrs()).angle_to(&attitude) < 1e-3);
+}
+
+#[test]
+fn test_multirate_hold_policy() {
+    let theta: f64 = 0.4;
+    let tilted = Vector3::new(theta.sin(), 0.0, theta.cos());
+
+    let accel_error = |policy, timestamp| {
+        let mut fusion = MultiRate::new(Madgwick::default().with_diagnostics(true), policy);
+        fusion.push_accelerometer(0.0, Vector3::z());
+        fusion.push_accelerometer(1.0, tilted);
+        fusion.push_gyroscope(timestamp, Vector3::zeros()).unwrap();
+        fusion.flush().unwrap();
+        fusion.ahrs().diagnostics().accel_error
+    };
+
+    // Between the two measurements
+    assert_eq!(accel_error(HoldPolicy::SampleAndHold, 0.5), Some(0.0));
+    assert!(relative_eq!(
+        accel_error(HoldPolicy::Interpolate, 0.5).unwrap(),
+        theta / 2.0,
+        epsilon = 1e-9
+    ));
+
+    // After the latest measurement, which is held by both policies
+    assert!(relative_eq!(
+        accel_error(HoldPolicy::Interpolate, 1.5).unwrap(),
+        theta,
+        epsilon = 1e-9
+    ));
+
+    // Before any measurement was taken, so only the gyroscope is integrated
+    assert_eq!(accel_error(HoldPolicy::SampleAndHold, -0.5), None);
+    assert_eq!(accel_error(HoldPolicy::Interpolate, -0.5), None);
+}
+
+#[test]
+fn test_multirate_rejects_repeated_gyroscope_timestamps() {
+    let gyro = Vector3::new(0.0, 0.0, 1.0);
+    let repeated = |policy, error_policy| {
+        let mut fusion =
+            MultiRate::new(Madgwick::new(0.01, 0.1), policy).with_error_policy(error_policy);
+        fusion.push_gyroscope(0.0, gyro).unwrap();
+        fusion.push_gyroscope(0.02, gyro).unwrap();
+        fusion.flush().unwrap();
+        let quat = AhrsState::quat(fusion.ahrs());
+
+        let result = fusion
+            .push_gyroscope(0.02, gyro)
+            .and_then(|_| fusion.flush());
+        assert_eq!(AhrsState::sample_period(fusion.ahrs()), 0.02);
+        (result, AhrsState::quat(fusion.ahrs()).angle_to(&quat))
+    };
+
+    for policy in [HoldPolicy::SampleAndHold, HoldPolicy::Interpolate] {
+        let (result, rotation) = repeated(policy, ErrorPolicy::Abort);
+        assert!(matches!(result, Err(AhrsError::TimestampNotIncreasing)));
+        assert_eq!(rotation, 0.0);
+
+        let (result, rotation) = repeated(policy, ErrorPolicy::Skip);
+        assert!(result.is_ok());
+        assert_eq!(rotation, 0.0);
+
+        // Integrated over the previous sampling period
+        let (result, rotation) = repeated(policy, ErrorPolicy::Fallback);
+        assert!(result.is_ok());
+        assert!(relative_eq!(rotation, 0.02, epsilon = 1e-5));
+    }
+}
+
+#[test]
+fn test_multirate_buffers_bursts() {
+    // A FIFO burst of accelerometer measurements, each tilted further, read ahead of the gyroscope
+    let tilted = |i: usize| Vector3::new((0.1 * i as f64).sin(), 0.0, (0.1 * i as f64).cos());
+    let mut fusion = MultiRate::new(
+        Madgwick::default().with_diagnostics(true),
+        HoldPolicy::SampleAndHold,
+    );
+    for i in 0..8 {
+        fusion.push_accelerometer(i as f64, tilted(i));
+    }
+
+    for i in 0..8 {
+        fusion.ahrs_mut().set_quat(UnitQuaternion::identity());
+        fusion
+            .push_gyroscope(i as f64 + 0.5, Vector3::zeros())
+            .unwrap();
+        assert!(relative_eq!(
+            fusion.ahrs().diagnostics().accel_error.unwrap(),
+            0.1 * i as f64,
+            epsilon = 1e-9
+        ));
+    }
+}
+
+#[test]
+fn test_multirate_drops_stale_measurements() {
+    let (accel, mag) = stationary_sensors(&UnitQuaternion::from_euler_angles(0.3, 0.0, 0.0));
+    let mut fusion =
+        MultiRate::new(Madgwick::default(), HoldPolicy::SampleAndHold).with_max_age(0.1);
+    fusion.push_accelerometer(0.0, accel);
+
+    fusion.push_gyroscope(0.05, Vector3::zeros()).unwrap();
+    assert_eq!(fusion.ahrs().diagnostics().mode, UpdateMode::Imu);
+    fusion.push_gyroscope(0.5, Vector3::zeros()).unwrap();
+    assert_eq!(fusion.ahrs().diagnostics().mode, UpdateMode::Gyro);
+
+    // Without an accelerometer measurement, the magnetometer only corrects the heading
+    fusion.push_magnetometer(0.5, mag);
+    let start = AhrsState::quat(fusion.ahrs());
+    fusion.push_gyroscope(0.55, Vector3::zeros()).unwrap();
+    assert_eq!(fusion.ahrs().diagnostics().mode, UpdateMode::Mag);
+    assert!(relative_eq!(
+        AhrsState::quat(fusion.ahrs()).inverse_transform_vector(&Vector3::z()),
+        start.inverse_transform_vector(&Vector3::z()),
+        epsilon = 1e-9
+    ));
+}
+
+#[test]
+fn test_multirate_interpolates_in_order_stream() {
+    let theta: f64 = 0.4;
+    let tilted = Vector3::new(theta.sin(), 0.0, theta.cos());
+    let mut fusion = MultiRate::new(
+        Madgwick::default().with_diagnostics(true),
+        HoldPolicy::Interpolate,
+    );
+
+    // The gyroscope sample is delayed until the next accelerometer measurement arrives
+    fusion.push_accelerometer(0.0, Vector3::z());
+    fusion.push_gyroscope(0.5, Vector3::zeros()).unwrap();
+    assert_eq!(fusion.ahrs().diagnostics().mode, UpdateMode::Gyro);
+    fusion.push_accelerometer(1.0, tilted);
+    fusion.push_gyroscope(1.5, Vector3::zeros()).unwrap();
+    assert!(relative_eq!(
+        fusion.ahrs().diagnostics().accel_error.unwrap(),
+        theta / 2.0,
+        epsilon = 1e-9
+    ));
+
+    // Flushing holds the latest measurement for the remaining sample
+    fusion.ahrs_mut().set_quat(UnitQuaternion::identity());
+    fusion.flush().unwrap();
+    assert!(relative_eq!(
+        fusion.ahrs().diagnostics().accel_error.unwrap(),
+        theta,
+        epsilon = 1e-9
+    ));
+}
+
+#[test]
+fn test_decoupled_heading_preserves_tilt() {
+    // Largest tilt error while correcting a heading error of 0.5 rad, and the final attitude error
+    fn converge<A: Ahrs<f64> + AhrsState<f64>>(mut ahrs: A) -> (f64, f64) {
+        let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+        let (accel, mag) = stationary_sensors(&attitude);
+
+        ahrs.set_quat(UnitQuaternion::from_euler_angles(0.0, 0.0, 0.5) * attitude);
+        let mut max_tilt: f64 = 0.0;
+        for _ in 0..256 {
+            let quat = *ahrs.update(&Vector3::zeros(), &accel, &mag).unwrap();
+            max_tilt = max_tilt.max(quat.inverse_transform_vector(&Vector3::z()).angle(&accel));
+        }
+
+        (max_tilt, AhrsState::quat(&ahrs).angle_to(&attitude))
+    }
+
+    assert!(converge(Madgwick::default()).0 > 0.05);
+    let (max_tilt, error) = converge(Madgwick::default().with_decoupled_heading(true));
+    assert!(max_tilt < 1e-3);
+    assert!(error < 0.45);
+
+    assert!(converge(Mahony::default()).0 > 0.05);
+    let (max_tilt, error) = converge(Mahony::default().with_decoupled_heading(true));
+    assert!(max_tilt < 1e-6);
+    assert!(error < 0.45);
+}
+
+// Published VQF reference outputs are not vendored in this repository, so `Vqf` is checked
+// against the analytically known attitude of a stationary body instead.
+#[test]
+fn test_vqf_update_converges() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+
+    let mut ahrs = Vqf::default();
+    for _ in 0..2560 {
+        ahrs.update(&Vector3::zeros(), &(accel * 9.81), &(mag * 50.0))
+            .unwrap();
+    }
+
+    // The bias estimated from the initial inclination correction decays only slowly
+    assert!(AhrsState::quat(&ahrs).angle_to(&attitude) < 1e-4);
+}
+
+#[test]
+fn test_vqf_estimates_bias_at_rest() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, _) = stationary_sensors(&attitude);
+    let bias = Vector3::new(0.01, -0.02, 0.005);
+
+    let mut ahrs = Vqf::new(0.05, 3.0, 9.0);
+    ahrs.set_quat(attitude);
+    for _ in 0..200 {
+        ahrs.update_imu(&bias, &(accel * 9.81)).unwrap();
+    }
+
+    assert!(ahrs.is_resting());
+    assert!(relative_eq!(ahrs.bias(), bias, epsilon = 2e-4));
+
+    // Heading drifts only until the bias has been estimated
+    let (_, _, yaw) = AhrsState::quat(&ahrs).euler_angles();
+    assert!((yaw - 1.0).abs() < 0.05);
+}
+
+#[test]
+fn test_vqf_estimates_bias_during_motion() {
+    // Turning about the vertical at a constant rate, which is never detected as rest
+    let tilt = UnitQuaternion::from_euler_angles(0.3, -0.2, 0.0);
+    let gyro = tilt.inverse_transform_vector(&Vector3::new(0.0, 0.0, 0.5));
+    let bias = Vector3::new(0.01, -0.02, 0.005);
+
+    let run = |ahrs: Vqf<f64>| {
+        let mut ahrs = ahrs;
+        ahrs.set_quat(tilt);
+        let mut tilt_error = 0.0;
+        for i in 0..1000 {
+            let attitude = UnitQuaternion::from_euler_angles(0.0, 0.0, 0.005 * i as f64) * tilt;
+            let up = attitude.inverse_transform_vector(&Vector3::z());
+            let quat = ahrs.update_imu(&(gyro + bias), &(up * 9.81)).unwrap();
+            tilt_error = quat.inverse_transform_vector(&Vector3::z()).angle(&up);
+        }
+        assert!(!ahrs.is_resting());
+        ((ahrs.bias() - bias).norm(), tilt_error)
+    };
+
+    let (bias_error, tilt_error) = run(Vqf::new(0.01, 3.0, 9.0));
+    let (fixed_bias_error, fixed_tilt_error) =
+        run(Vqf::new(0.01, 3.0, 9.0).with_motion_bias_estimation(false));
+    assert_eq!(fixed_bias_error, bias.norm());
+    assert!(bias_error < 0.7 * bias.norm());
+    assert!(tilt_error < 0.75 * fixed_tilt_error);
+}
+
+#[test]
+fn test_vqf_rejects_mag_disturbance() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+    let accel = accel * 9.81;
+
+    // Coarsely sampled, as the disturbance detection runs on time constants of seconds, and past
+    // the fast initial heading convergence, which does not reject disturbances
+    let mut ahrs = Vqf::new(0.1, 3.0, 9.0);
+    for _ in 0..(10 * 10) {
+        ahrs.update(&Vector3::zeros(), &accel, &(mag * 50.0))
+            .unwrap();
+    }
+
+    // Stronger field pointing 30 degrees away from magnetic north
+    let disturbed = UnitQuaternion::from_euler_angles(0.0, 0.0, -f64::consts::FRAC_PI_6) * attitude;
+    let (_, disturbed_mag) = stationary_sensors(&disturbed);
+    for _ in 0..(5 * 10) {
+        ahrs.update(&Vector3::zeros(), &accel, &(disturbed_mag * 75.0))
+            .unwrap();
+    }
+
+    assert!(ahrs.is_mag_disturbed());
+    assert_eq!(ahrs.diagnostics().mode, UpdateMode::Imu);
+    assert_eq!(ahrs.diagnostics().mag_error, None);
+    assert!(AhrsState::quat(&ahrs).angle_to(&attitude) < 0.01);
+
+    // The disturbed field is eventually accepted as the new reference
+    for _ in 0..(80 * 10) {
+        ahrs.update(&Vector3::zeros(), &accel, &(disturbed_mag * 75.0))
+            .unwrap();
+    }
+
+    assert!(!ahrs.is_mag_disturbed());
+    assert_eq!(ahrs.diagnostics().mode, UpdateMode::Marg);
+    assert!(AhrsState::quat(&ahrs).angle_to(&disturbed) < 0.01);
+}
+
+#[test]
+fn test_vqf_limits_mag_rejection_time() {
+    let attitude = UnitQuaternion::from_euler_angles(0.3, -0.2, 1.0);
+    let (accel, mag) = stationary_sensors(&attitude);
+    let accel = accel * 9.81;
+
+    let mut ahrs = Vqf::new(0.1, 3.0, 9.0);
+    for _ in 0..(10 * 10) {
+        ahrs.update(&Vector3::zeros(), &accel, &(mag * 50.0))
+            .unwrap();
+    }
+
+    // A field growing by 2 % per sample never becomes a candidate for the new reference
+    let mut strength = 75.0;
+    let mut modes = Vec::new();
+    for _ in 0..(70 * 10) {
+        strength *= 1.02;
+        ahrs.update(&Vector3::zeros(), &accel, &(mag * strength))
+            .unwrap();
+        assert!(ahrs.is_mag_disturbed());
+        modes.push(ahrs.diagnostics().mode);
+    }
+
+    // Heading corrections resume with a reduced gain after 60 seconds
+    assert!(modes[..(60 * 10)]
+        .iter()
+        .all(|&mode| mode == UpdateMode::Imu));
+    assert!(modes[(61 * 10)..]
+        .iter()
+        .all(|&mode| mode == UpdateMode::Marg));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_batch_matches_sequential_updates() {
+    let (accel, gyro, mag) = default_sensors!();
+    let gyro = gyro * (f64::consts::PI / 180.0);
+
+    let samples = vec![(gyro, accel, mag); 10];
+
+    let mut expected = Madgwick::default();
+    let mut ahrs = Madgwick::default();
+
+    let actual = ahrs.update_batch(samples, ErrorPolicy::Abort).unwrap();
+
+    for quat in actual {
+        assert_eq!(&quat, expected.update(&gyro, &accel, &mag).unwrap());
+    }
+}
+
+#[test]
+fn test_batch_error_policies() {
+    let (accel, gyro, mag) = default_sensors!();
+    let gyro = gyro * (f64::consts::PI / 180.0);
+    let zero = Vector3::zeros();
+
+    let samples = [
+        Sample::new(gyro, accel, mag),
+        Sample::new(gyro, accel, zero),
+        Sample::new(gyro, zero, mag),
+    ];
+    let mut output = [UnitQuaternion::identity(); 3];
+
+    let mut ahrs = Madgwick::default();
+    let err = ahrs
+        .update_batch_into(samples.iter().copied(), &mut output, ErrorPolicy::Abort)
+        .unwrap_err();
+    assert_eq!(err.index, 1);
+
+    let mut ahrs = Madgwick::default();
+    let written = ahrs
+        .update_batch_into(samples.iter().copied(), &mut output, ErrorPolicy::Skip)
+        .unwrap();
+    assert_eq!(written, 3);
+    assert_eq!(output[0], output[1]);
+    assert_eq!(output[1], output[2]);
+
+    let mut ahrs = Madgwick::default();
+    let mut expected = Madgwick::default();
+    ahrs.update_batch_into(samples.iter().copied(), &mut output, ErrorPolicy::Fallback)
+        .unwrap();
+    assert_eq!(&output[0], expected.update(&gyro, &accel, &mag).unwrap());
+    assert_eq!(&output[1], expected.update_imu(&gyro, &accel).unwrap());
+    assert_eq!(&output[2], expected.update_gyro(&gyro));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_batch_timestamps_set_sample_period() {
+    let (accel, gyro, mag) = default_sensors!();
+    let gyro = gyro * (f64::consts::PI / 180.0);
+
+    let samples = (0..5).map(|n| Sample::new(gyro, accel, mag).with_timestamp(n as f64 * 0.25));
+
+    let mut ahrs = Mahony::default();
+    let actual = ahrs.update_batch(samples, ErrorPolicy::Abort).unwrap();
+
+    let mut expected = Mahony::default();
+    expected.update(&gyro, &accel, &mag).unwrap();
+    expected.set_sample_period(0.25);
+    for _ in 1..5 {
+        expected.update(&gyro, &accel, &mag).unwrap();
+    }
+
+    assert_eq!(ahrs.sample_period(), 0.25);
+    assert_eq!(actual.last(), Some(&expected.quat));
+}
+
+#[test]
+fn test_batch_rejects_non_increasing_timestamps() {
+    let (accel, gyro, mag) = default_sensors!();
+    let gyro = gyro * (f64::consts::PI / 180.0);
+
+    let samples =
+        [0.0, 0.5, 0.5, 0.25, 1.0].map(|t| Sample::new(gyro, accel, mag).with_timestamp(t));
+    let mut output = [UnitQuaternion::identity(); 5];
+
+    let mut ahrs = Madgwick::default();
+    let err = ahrs
+        .update_batch_into(samples, &mut output, ErrorPolicy::Abort)
+        .unwrap_err();
+    assert_eq!(err.index, 2);
+    assert!(matches!(err.error, AhrsError::TimestampNotIncreasing));
+
+    // Skipped samples repeat the estimate, and the period is measured from the last valid one
+    let mut ahrs = Madgwick::default();
+    ahrs.update_batch_into(samples, &mut output, ErrorPolicy::Skip)
+        .unwrap();
+    assert_eq!(output[1], output[2]);
+    assert_eq!(output[2], output[3]);
+    assert_eq!(ahrs.sample_period(), 0.5);
+
+    // Fallback keeps the previous period
+    let mut ahrs = Madgwick::default();
+    let mut expected = Madgwick::default();
+    ahrs.update_batch_into(samples, &mut output, ErrorPolicy::Fallback)
+        .unwrap();
+    expected.update(&gyro, &accel, &mag).unwrap();
+    expected.set_sample_period(0.5);
+    for quat in &output[1..4] {
+        assert_eq!(quat, expected.update(&gyro, &accel, &mag).unwrap());
+    }
+}
+
+#[cfg(feature = "std")]
+fn simd_lanes(vectors: [Vector3<f32>; 4]) -> Vector3<WideF32x4> {
+    Vector3::from_fn(|i, _| WideF32x4::from(vectors.map(|v| v[i])))
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_madgwick_simd_update_matches_scalar() {
+    let (accel, gyro, mag) = default_sensors!();
+    let (accel, gyro, mag): (Vector3<f32>, Vector3<f32>, Vector3<f32>) = (
+        accel.cast(),
+        (gyro * (f64::consts::PI / 180.0)).cast(),
+        mag.cast(),
+    );
+    let zero = Vector3::zeros();
+
+    let gyros = [gyro, gyro * 0.5, -gyro, gyro];
+    let accels = [accel, accel, -accel, zero];
+    let mags = [mag, zero, mag, mag];
+
+    for decoupled in [false, true] {
+        let mut simd = Madgwick::new(WideF32x4::splat(1.0 / 256.0), WideF32x4::splat(0.1))
+            .with_decoupled_heading(decoupled);
+        let mut scalars = [Madgwick::new(1.0f32 / 256.0, 0.1).with_decoupled_heading(decoupled); 4];
+
+        let (_, status) =
+            simd.simd_update(&simd_lanes(gyros), &simd_lanes(accels), &simd_lanes(mags));
+
+        assert_eq!(status.accelerometer_norm_zero.bitmask(), 0b1000);
+        assert_eq!(status.magnetometer_norm_zero.bitmask(), 0b0010);
+
+        for (lane, scalar) in scalars.iter_mut().enumerate() {
+            let expected = match scalar.update(&gyros[lane], &accels[lane], &mags[lane]) {
+                Ok(quat) => *quat,
+                Err(_) => scalar.quat,
+            };
+            assert!(relative_eq!(
+                simd.quat.extract(lane),
+                expected,
+                epsilon = 1e-6
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_mahony_simd_update_imu_matches_scalar() {
+    let (accel, gyro, _) = default_sensors!();
+    let (accel, gyro): (Vector3<f32>, Vector3<f32>) =
+        (accel.cast(), (gyro * (f64::consts::PI / 180.0)).cast());
+
+    let gyros = [gyro, gyro * 0.5, -gyro, gyro];
+    let accels = [accel, accel * 2.0, -accel, Vector3::zeros()];
+
+    let mut simd = Mahony::new(
+        WideF32x4::splat(1.0 / 256.0),
+        WideF32x4::splat(0.5),
+        WideF32x4::splat(0.1),
+    );
+    let mut scalars = [Mahony::new(1.0f32 / 256.0, 0.5, 0.1); 4];
+
+    for _ in 0..10 {
+        let (_, status) = simd.simd_update_imu(&simd_lanes(gyros), &simd_lanes(accels));
+        assert_eq!(status.failed().bitmask(), 0b1000);
+
+        for (lane, scalar) in scalars.iter_mut().enumerate() {
+            let _ = scalar.update_imu(&gyros[lane], &accels[lane]);
+        }
+    }
+
+    for (lane, scalar) in scalars.iter().enumerate() {
+        assert!(relative_eq!(
+            simd.quat.extract(lane),
+            scalar.quat,
+            epsilon = 1e-5
+        ));
+    }
+}
+
+#[test]
+fn test_update_with_units_matches_update() {
+    let (accel, gyro, mag) = default_sensors!();
+
+    let mut expected = Madgwick::default();
+    expected
+        .update(&(gyro * (f64::consts::PI / 180.0)), &accel, &mag)
+        .unwrap();
+
+    let mut actual = Madgwick::default();
+    actual
+        .update_with_units(
+            &AngularRate::from_degrees_per_second(gyro),
+            &Acceleration::from_g(accel),
+            &MagneticField::from_gauss(mag),
+        )
+        .unwrap();
+
+    assert!(relative_eq!(actual.quat, expected.quat, epsilon = 1e-12));
+}
+
+#[cfg(feature = "uom")]
+#[test]
+fn test_units_from_uom() {
+    use uom::si::{
+        acceleration::standard_gravity, angular_velocity::degree_per_second, f64,
+        magnetic_flux_density::gauss,
+    };
+
+    let (accel, gyro, mag) = default_sensors!();
+
+    let rate: [f64::AngularVelocity; 3] = gyro
+        .map(f64::AngularVelocity::new::<degree_per_second>)
+        .into();
+    let acceleration: [f64::Acceleration; 3] =
+        accel.map(f64::Acceleration::new::<standard_gravity>).into();
+    let field: [f64::MagneticFluxDensity; 3] =
+        mag.map(f64::MagneticFluxDensity::new::<gauss>).into();
+
+    let rate = AngularRate::from(rate);
+    let acceleration = Acceleration::from(acceleration);
+    let field = MagneticField::from(field);
+
+    assert!(relative_eq!(
+        rate.radians_per_second(),
+        AngularRate::from_degrees_per_second(gyro).radians_per_second()
+    ));
+    assert!(relative_eq!(acceleration.g(), accel));
+    assert!(relative_eq!(field.gauss(), mag));
+}
+
+/// Runs a fixed-point and an f64 filter over the same sensor trajectory, returning the largest
+/// angle between their estimates.
+#[cfg(feature = "fixed")]
+fn fixed_point_trajectory_error<F, R>(fixed: &mut F, reference: &mut R, marg: bool) -> f64
+where
+    F: Ahrs<I16F16>,
+    R: Ahrs<f64>,
+{
+    let (accel, _, mag) = default_sensors!();
+    let to_fixed = |v: Vector3<f64>| v.map(I16F16::from_num);
+
+    (0..1000)
+        .map(|n| {
+            let t = n as f64 / 256.0;
+            let gyro = Vector3::new(0.5 * t.sin(), 0.3 * t.cos(), 0.2);
+            let accel = accel + Vector3::new(0.05 * (3.0 * t).sin(), 0.0, 0.02);
+            let mag = mag + Vector3::new(0.0, 0.03 * (2.0 * t).cos(), 0.0);
+
+            let (fixed_quat, reference_quat) = if marg {
+                (
+                    *fixed
+                        .update(&to_fixed(gyro), &to_fixed(accel), &to_fixed(mag))
+                        .unwrap(),
+                    *reference.update(&gyro, &accel, &mag).unwrap(),
+                )
+            } else {
+                (
+                    *fixed.update_imu(&to_fixed(gyro), &to_fixed(accel)).unwrap(),
+                    *reference.update_imu(&gyro, &accel).unwrap(),
+                )
+            };
+
+            let fixed_quat = UnitQuaternion::from_quaternion(Quaternion::from(
+                fixed_quat.coords.map(|x| x.0.to_num::<f64>()),
+            ));
+            fixed_quat.angle_to(&reference_quat)
+        })
+        .fold(0.0, f64::max)
+}
+
+#[cfg(feature = "fixed")]
+#[test]
+fn test_fixed_point_transcendentals_match_f64() {
+    use num_traits::Num;
+    use simba::scalar::ComplexField;
+
+    type Case = (fn(I16F16) -> I16F16, fn(f64) -> f64, f64);
+    let cases: [Case; 14] = [
+        (I16F16::exp, f64::exp, 7.5),
+        (I16F16::exp_m1, f64::exp_m1, -0.3),
+        (I16F16::ln, f64::ln, 1234.5),
+        (I16F16::ln_1p, f64::ln_1p, 0.01),
+        (I16F16::log2, f64::log2, 0.1),
+        (I16F16::log10, f64::log10, 20.0),
+        (I16F16::cbrt, f64::cbrt, -27.5),
+        (I16F16::sinh, f64::sinh, -0.2),
+        (I16F16::cosh, f64::cosh, 3.0),
+        (I16F16::tanh, f64::tanh, 0.7),
+        (I16F16::asinh, f64::asinh, 100.0),
+        (I16F16::acosh, f64::acosh, 1.5),
+        (I16F16::atanh, f64::atanh, -0.9),
+        (|x| x.powf(I16F16::from_num(-1.5)), |x| x.powf(-1.5), 4.0),
+    ];
+    for (fixed, float, x) in cases {
+        let expected = float(x);
+        let actual: f64 = fixed(I16F16::from_num(x)).0.to_num();
+        assert!(
+            (actual - expected).abs() < 2e-4 * expected.abs().max(1.0),
+            "x: {}, expected: {}, actual: {}",
+            x,
+            expected,
+            actual
+        );
+    }
+
+    // Results saturate instead of overflowing, and undefined ones do not panic
+    assert_eq!(I16F16::from_num(20).exp(), I16F16::from_bits(i32::MAX));
+    assert_eq!(I16F16::from_num(-20).exp(), I16F16::from_bits(0));
+    assert_eq!(I16F16::from_bits(0).ln(), I16F16::from_bits(i32::MIN));
+    assert_eq!(
+        I16F16::from_num(-2).powf(I16F16::from_num(0.5)),
+        I16F16::from_bits(0)
+    );
+
+    assert!(I16F16::from_str_radix("1.5", 7).is_err());
+    assert_eq!(
+        I16F16::from_str_radix("-1.1", 2).unwrap(),
+        I16F16::from_num(-1.5)
+    );
+}
+
+#[cfg(feature = "fixed")]
+#[test]
+fn test_madgwick_fixed_point_matches_f64() {
+    let sample_period = 1.0 / 256.0;
+    let beta = 0.1;
+
+    for marg in [true, false] {
+        let mut fixed = Madgwick::new(I16F16::from_num(sample_period), I16F16::from_num(beta));
+        let mut reference = Madgwick::new(sample_period, beta);
+
+        let error = fixed_point_trajectory_error(&mut fixed, &mut reference, marg);
+        assert!(error < 0.05, "marg: {}, error: {}", marg, error);
+    }
+}
+
+#[cfg(feature = "fixed")]
+#[test]
+fn test_mahony_fixed_point_matches_f64() {
+    let sample_period = 1.0 / 256.0;
+    let (kp, ki) = (0.5, 0.1);
+
+    for marg in [true, false] {
+        let mut fixed = Mahony::new(
+            I16F16::from_num(sample_period),
+            I16F16::from_num(kp),
+            I16F16::from_num(ki),
+        );
+        let mut reference = Mahony::new(sample_period, kp, ki);
+
+        let error = fixed_point_trajectory_error(&mut fixed, &mut reference, marg);
+        assert!(error < 0.05, "marg: {}, error: {}", marg, error);
+    }
+}